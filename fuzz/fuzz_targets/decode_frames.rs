@@ -0,0 +1,12 @@
+//! Decodes arbitrary bytes as a websocket frame from a CM server, including expanding `Multi` 
+//! messages. Malformed input must return an error rather than panic.
+//! 
+//! Run with `cargo +nightly fuzz run decode_frames` from the repository root.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use steam_session::transports::websocket::decode_frames;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_frames(data);
+});
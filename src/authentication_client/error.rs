@@ -20,12 +20,50 @@ pub enum Error {
     BadUint(String),
     #[error("RSA error: {}", .0)]
     RSA(#[from] rsa::Error),
+    #[error("Invalid hex in RSA key: {}", .0)]
+    InvalidRsaKeyHex(String),
+    #[error("Invalid RSA key size: {} bits", .0)]
+    InvalidRsaKeySize(usize),
+    #[error("RSA encryption failed: {}", .0)]
+    RsaEncryption(rsa::Error),
     #[error("reqwest error: {}", .0)]
     Reqwest(#[from] reqwest::Error),
     #[error("Websocket CM: {}", .0)]
     WebSocketCM(#[from] crate::transports::websocket::Error),
     #[error("WebAPI: {}", .0)]
     WebAPI(#[from] crate::transports::web_api::Error),
-    #[error("Received EResult other than OK: {:?}", .0)]
+    #[error("Error with protobuf message: {}", .0)]
+    Proto(#[from] protobuf::Error),
+    #[error("Steam returned EResult {}", .0)]
     EResultNotOK(EResult),
+    /// A request couldn't be replayed from a recording, or the recorded request failed.
+    #[error("Replay: {}", .0)]
+    Replay(String),
+}
+
+impl Error {
+    /// Gets the [`EResult`] carried by this error, if any. This looks through the errors of both 
+    /// transports so the result can be inspected regardless of which transport produced it.
+    pub fn eresult(&self) -> Option<EResult> {
+        match self {
+            Self::EResultNotOK(eresult) => Some(*eresult),
+            Self::WebAPI(crate::transports::web_api::Error::EResultNotOK(eresult)) => Some(*eresult),
+            Self::WebAPI(crate::transports::web_api::Error::EResultWithMessage { eresult, .. }) => Some(*eresult),
+            Self::WebAPI(crate::transports::web_api::Error::TooManyRequests { .. }) => Some(EResult::RateLimitExceeded),
+            Self::WebSocketCM(crate::transports::websocket::Error::EResultNotOK(eresult)) => Some(*eresult),
+            Self::WebSocketCM(crate::transports::websocket::Error::EResultWithMessage { eresult, .. }) => Some(*eresult),
+            Self::WebSocketCM(crate::transports::websocket::Error::ProtocolVersionRejected(_)) => Some(EResult::InvalidProtocolVer),
+            _ => None,
+        }
+    }
+    
+    /// Gets the error message Steam sent along with the [`EResult`], if any, e.g. to display 
+    /// Steam's own explanation of the failure.
+    pub fn extended_error_message(&self) -> Option<&str> {
+        match self {
+            Self::WebAPI(crate::transports::web_api::Error::EResultWithMessage { message, .. }) |
+            Self::WebSocketCM(crate::transports::websocket::Error::EResultWithMessage { message, .. }) => Some(message),
+            _ => None,
+        }
+    }
 }
\ No newline at end of file
@@ -1,4 +1,6 @@
-use crate::enums::{EOSType, EResult};
+use super::Error;
+use crate::enums::{EOSType, EResult, ELanguage};
+use crate::helpers::encode_base64;
 use crate::proto::steammessages_auth_steamclient::{
     CAuthentication_DeviceDetails,
     EAuthTokenPlatformType,
@@ -6,8 +8,13 @@ use crate::proto::steammessages_auth_steamclient::{
 use crate::serializers::from_number_or_string;
 use reqwest::Client;
 use reqwest::header::HeaderMap;
+use rsa::{RsaPublicKey, Pkcs1v15Encrypt, BigUint};
 use serde::Deserialize;
 use steam_machine_id::MachineID;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct EncryptedPassword {
@@ -15,17 +22,80 @@ pub struct EncryptedPassword {
     pub key_timestamp: u64,
 }
 
+/// Specifies the machine ID sent when logging in with 
+/// [`EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum MachineId {
+    /// Don't send a machine ID.
+    None,
+    /// Generate a machine ID from the account name.
+    #[default]
+    Generate,
+    /// Use the given machine ID.
+    Bytes(Vec<u8>),
+}
+
+impl MachineId {
+    /// Gets the bytes of the machine ID for `account_name`, generating them if needed.
+    pub fn resolve(&self, account_name: &str) -> Option<Vec<u8>> {
+        match self {
+            Self::None => None,
+            Self::Generate => Some(get_machine_id(account_name)),
+            Self::Bytes(bytes) => Some(bytes.clone()),
+        }
+    }
+    
+    /// Checks if the machine ID is valid. Only [`MachineId::Bytes`] can be invalid; see 
+    /// [`is_valid_machine_id`].
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Self::Bytes(bytes) => is_valid_machine_id(bytes),
+            _ => true,
+        }
+    }
+}
+
+impl From<Vec<u8>> for MachineId {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for MachineId {
+    fn from(bytes: &[u8]) -> Self {
+        Self::Bytes(bytes.to_vec())
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for MachineId {
+    fn from(bytes: [u8; N]) -> Self {
+        Self::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<Option<Vec<u8>>> for MachineId {
+    /// `None` generates a machine ID.
+    fn from(bytes: Option<Vec<u8>>) -> Self {
+        bytes.map(Self::Bytes).unwrap_or_default()
+    }
+}
+
 #[derive(Debug)]
 pub struct AuthenticationClientConstructorOptions<T> {
     pub platform_type: EAuthTokenPlatformType,
     pub transport: T,
     pub client: Client,
     pub user_agent: &'static str,
-    pub machine_id: Option<Vec<u8>>,
+    pub language: ELanguage,
 }
 
-#[derive(Debug, Clone)]
+/// The longest device friendly name accepted, in characters.
+pub const MAX_DEVICE_FRIENDLY_NAME_LENGTH: usize = 256;
+
+/// The details of the device logging in, shown in the account's list of authorized devices.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DeviceDetails {
+    /// The name the device is listed under.
     pub device_friendly_name: String,
     pub platform_type: EAuthTokenPlatformType,
     pub os_type: Option<EOSType>,
@@ -53,7 +123,6 @@ impl From<DeviceDetails> for CAuthentication_DeviceDetails {
 
 #[derive(Debug, Clone)]
 pub struct PlatformData {
-    pub website_id: &'static str,
     pub headers: HeaderMap,
     pub device_details: DeviceDetails,
 }
@@ -66,7 +135,238 @@ pub struct CheckMachineAuthResponse {
     pub result: EResult,
 }
 
+/// The smallest RSA modulus accepted when encrypting passwords.
+const MIN_RSA_KEY_BITS: usize = 1024;
+/// The largest RSA modulus accepted when encrypting passwords.
+const MAX_RSA_KEY_BITS: usize = 4096;
+
+/// Encrypts `password` using the RSA public key given by the hex-encoded modulus `mod_hex` and 
+/// exponent `exp_hex`, as returned by `GetPasswordRSAPublicKey`. Returns the base64-encoded 
+/// ciphertext.
+pub fn encrypt_password(
+    password: &str,
+    mod_hex: &str,
+    exp_hex: &str,
+) -> Result<String, Error> {
+    let n = parse_hex_uint(mod_hex)
+        .ok_or_else(|| Error::InvalidRsaKeyHex(mod_hex.into()))?;
+    let e = parse_hex_uint(exp_hex)
+        .ok_or_else(|| Error::InvalidRsaKeyHex(exp_hex.into()))?;
+    let bits = n.bits();
+    
+    if !(MIN_RSA_KEY_BITS..=MAX_RSA_KEY_BITS).contains(&bits) {
+        return Err(Error::InvalidRsaKeySize(bits));
+    }
+    
+    let key = RsaPublicKey::new(n, e)?;
+    let encrypted_password = key.encrypt(
+        &mut rand::thread_rng(),
+        Pkcs1v15Encrypt,
+        password.as_bytes(),
+    )
+        .map_err(Error::RsaEncryption)?;
+    
+    Ok(encode_base64(encrypted_password))
+}
+
+fn parse_hex_uint(hex: &str) -> Option<BigUint> {
+    if hex.is_empty() {
+        return None;
+    }
+    
+    BigUint::parse_bytes(hex.as_bytes(), 16)
+}
+
 /// Generates a machine ID.
 pub fn get_machine_id(account_name: &str) -> Vec<u8> {
     MachineID::from_account_name(account_name).into()
-}
\ No newline at end of file
+}
+
+/// The hash fields of a machine ID.
+const MACHINE_ID_FIELDS: [&[u8]; 3] = [b"BB3", b"FF2", b"3B3"];
+
+/// Checks if `bytes` is a well-formed machine ID, i.e. a binary `MessageObject` containing the 
+/// hex-encoded SHA-1 hashes `BB3`, `FF2` and `3B3`, as generated by the Steam client. This 
+/// catches machine IDs which were mangled e.g. by being copied as text, which Steam would 
+/// otherwise reject with an unhelpful result.
+pub fn is_valid_machine_id(bytes: &[u8]) -> bool {
+    /// Reads a null-terminated string from the start of `bytes`.
+    fn read_c_str<'a>(bytes: &mut &'a [u8]) -> Option<&'a [u8]> {
+        let end = bytes.iter().position(|byte| *byte == 0)?;
+        let value = &bytes[..end];
+        
+        *bytes = &bytes[end + 1..];
+        Some(value)
+    }
+    
+    let Some(mut rest) = bytes.strip_prefix(b"\x00MessageObject\x00") else {
+        return false;
+    };
+    let mut fields = Vec::with_capacity(MACHINE_ID_FIELDS.len());
+    
+    // each field is a string, marked by 1, until the object and message end, each marked by 8
+    while let Some(remaining) = rest.strip_prefix(&[1]) {
+        rest = remaining;
+        
+        let (Some(name), Some(value)) = (read_c_str(&mut rest), read_c_str(&mut rest)) else {
+            return false;
+        };
+        
+        if value.len() != 40 || !value.iter().all(u8::is_ascii_hexdigit) {
+            return false;
+        }
+        
+        fields.push(name);
+    }
+    
+    rest == [8, 8] &&
+    fields.len() == MACHINE_ID_FIELDS.len() &&
+    MACHINE_ID_FIELDS.iter().all(|field| fields.contains(field))
+}
+
+/// Gets the default directory machine IDs are persisted to by [`load_or_generate_machine_id`]. 
+/// This is `%APPDATA%\steam-session\machine-ids` on Windows, 
+/// `~/Library/Application Support/steam-session/machine-ids` on macOS and 
+/// `$XDG_DATA_HOME/steam-session/machine-ids` (defaulting to `~/.local/share`) elsewhere.
+/// 
+/// Returns `None` if the directory can't be determined from the environment.
+pub fn machine_id_default_path() -> Option<PathBuf> {
+    default_data_dir(|name| std::env::var_os(name))
+        .map(|dir| dir.join("steam-session").join("machine-ids"))
+}
+
+/// Loads the machine ID for `account_name` persisted in [`machine_id_default_path`], generating 
+/// and saving one if none exists. Reusing the same machine ID across runs avoids Steam treating 
+/// each login as coming from a new device.
+/// 
+/// The result can be passed as [`MachineId::Bytes`].
+pub fn load_or_generate_machine_id(account_name: &str) -> Result<Vec<u8>, io::Error> {
+    let dir = machine_id_default_path()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No data directory for machine IDs"))?;
+    
+    load_or_generate_machine_id_in(&dir, account_name)
+}
+
+/// Gets the per-user data directory for the current platform, reading variables using `var`.
+fn default_data_dir(var: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    let non_empty = |name: &str| var(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    
+    if cfg!(windows) {
+        non_empty("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        non_empty("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        non_empty("XDG_DATA_HOME")
+            .or_else(|| non_empty("HOME").map(|home| home.join(".local").join("share")))
+    }
+}
+
+/// Loads the machine ID for `account_name` from `dir`, generating and saving one if none exists.
+fn load_or_generate_machine_id_in(dir: &Path, account_name: &str) -> Result<Vec<u8>, io::Error> {
+    if account_name.is_empty() || account_name.contains(['/', '\\', '.']) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid account name"));
+    }
+    
+    let path = dir.join(account_name);
+    
+    match fs::read(&path) {
+        Ok(machine_id) if is_valid_machine_id(&machine_id) => return Ok(machine_id),
+        Ok(_) => log::warn!("Machine ID saved at {} is invalid; generating a new one", path.display()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {},
+        Err(error) => return Err(error),
+    }
+    
+    let machine_id = get_machine_id(account_name);
+    
+    fs::create_dir_all(dir)?;
+    fs::write(&path, &machine_id)?;
+    log::debug!("Saved machine ID for {account_name} to {}", path.display());
+    
+    Ok(machine_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::decode_base64;
+    use rsa::RsaPrivateKey;
+    use rsa::traits::PublicKeyParts;
+    
+    #[test]
+    fn encrypts_password_with_key() {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let mod_hex = private_key.n().to_str_radix(16);
+        let exp_hex = private_key.e().to_str_radix(16);
+        let encrypted_password = encrypt_password("hunter2", &mod_hex, &exp_hex).unwrap();
+        let ciphertext = decode_base64(&encrypted_password).unwrap();
+        let decrypted = private_key.decrypt(Pkcs1v15Encrypt, &ciphertext).unwrap();
+        
+        assert_eq!(decrypted, b"hunter2");
+    }
+    
+    #[test]
+    fn rejects_bad_hex() {
+        let error = encrypt_password("hunter2", "not hex", "010001").unwrap_err();
+        
+        assert!(matches!(error, Error::InvalidRsaKeyHex(hex) if hex == "not hex"));
+        
+        let error = encrypt_password("hunter2", "ff", "").unwrap_err();
+        
+        assert!(matches!(error, Error::InvalidRsaKeyHex(_)));
+    }
+    
+    #[test]
+    fn rejects_invalid_key_size() {
+        let error = encrypt_password("hunter2", "ffff", "010001").unwrap_err();
+        
+        assert!(matches!(error, Error::InvalidRsaKeySize(16)));
+    }
+    
+    #[test]
+    fn persists_machine_id() {
+        let dir = std::env::temp_dir().join(format!("steam-session-test-{}", rand::random::<u64>()));
+        let machine_id = load_or_generate_machine_id_in(&dir, "accountname").unwrap();
+        
+        assert_eq!(fs::read(dir.join("accountname")).unwrap(), machine_id);
+        
+        fs::write(dir.join("accountname"), get_machine_id("other")).unwrap();
+        
+        assert_eq!(load_or_generate_machine_id_in(&dir, "accountname").unwrap(), get_machine_id("other"));
+        
+        fs::write(dir.join("accountname"), [1, 2, 3]).unwrap();
+        
+        assert_eq!(load_or_generate_machine_id_in(&dir, "accountname").unwrap(), machine_id);
+        assert!(load_or_generate_machine_id_in(&dir, "../accountname").is_err());
+        
+        fs::remove_dir_all(dir).unwrap();
+    }
+    
+    #[test]
+    fn validates_machine_id() {
+        let machine_id = get_machine_id("accountname");
+        
+        assert!(is_valid_machine_id(&machine_id));
+        assert!(is_valid_machine_id(&Vec::<u8>::from(MachineID::random())));
+        assert!(!is_valid_machine_id(&machine_id[..machine_id.len() - 1]));
+        assert!(!is_valid_machine_id(String::from_utf8_lossy(&machine_id).replace('\0', "").as_bytes()));
+        assert!(!is_valid_machine_id(&[]));
+        assert!(!MachineId::Bytes(vec![1, 2, 3]).is_valid());
+        assert!(MachineId::Generate.is_valid());
+    }
+    
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn prefers_xdg_data_home() {
+        let var = |name: &str| match name {
+            "XDG_DATA_HOME" => Some(OsString::from("/data")),
+            "HOME" => Some(OsString::from("/home/user")),
+            _ => None,
+        };
+        
+        assert_eq!(default_data_dir(var), Some(PathBuf::from("/data")));
+        assert_eq!(
+            default_data_dir(|name| (name == "HOME").then(|| OsString::from("/home/user"))),
+            Some(PathBuf::from("/home/user/.local/share")),
+        );
+    }
+}
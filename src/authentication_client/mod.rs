@@ -2,15 +2,31 @@ mod error;
 mod helpers;
 
 pub use error::Error;
+pub use helpers::{
+    MachineId,
+    DeviceDetails,
+    MAX_DEVICE_FRIENDLY_NAME_LENGTH,
+    machine_id_default_path,
+    load_or_generate_machine_id,
+    is_valid_machine_id,
+};
 pub (crate) use helpers::{EncryptedPassword, AuthenticationClientConstructorOptions};
 
-use helpers::{PlatformData, DeviceDetails, CheckMachineAuthResponse, get_machine_id};
+use helpers::{PlatformData, CheckMachineAuthResponse};
 
-use crate::enums::{EOSType, EAuthTokenPlatformType, ETokenRenewalType, EAuthSessionGuardType};
-use crate::helpers::{JwtPayload, encode_base64, get_spoofed_hostname, create_api_headers, DecodeError};
+use crate::enums::{EOSType, EAuthTokenPlatformType, ETokenRenewalType, EAuthSessionGuardType, ELanguage, WebsiteId};
+use crate::helpers::{
+    JwtPayload,
+    DecodeError,
+    get_spoofed_hostname,
+    create_api_headers,
+    STEAM_CLIENT_USER_AGENT,
+    MOBILE_USER_AGENT,
+    MOBILE_CLIENT_COOKIE,
+};
 use crate::net::ApiRequest;
 use crate::transports::Transport;
-use crate::request::{StartAuthSessionWithCredentialsRequest, MobileConfirmationRequest};
+use crate::request::{StartAuthSessionWithCredentialsRequest, MobileConfirmationRequest, RsaKey};
 use crate::proto::steammessages_auth_steamclient::{
     CAuthentication_DeviceDetails,
     CAuthentication_UpdateAuthSessionWithSteamGuardCode_Request,
@@ -33,16 +49,18 @@ use reqwest::Client;
 use steamid_ng::SteamID;
 use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT, ORIGIN, REFERER, COOKIE, CONTENT_TYPE};
 use serde::Serialize;
-use rsa::{RsaPublicKey, Pkcs1v15Encrypt, BigUint};
 
 /// A client for handling authentication requests.
+/// 
+/// The client holds no per-account state, so one client can be shared by many sessions using the 
+/// same transport and platform type, see [`LoginSessionBuilder::with_handler`](crate::login_session::LoginSessionBuilder::with_handler).
 #[derive(Debug)]
 pub struct AuthenticationClient<T> {
     transport: T,
     platform_type: EAuthTokenPlatformType,
     client: Client,
     user_agent: &'static str,
-    machine_id: Option<Vec<u8>>,
+    language: ELanguage,
 }
 
 impl<T> AuthenticationClient<T>
@@ -58,10 +76,15 @@ where
             platform_type: options.platform_type,
             client: options.client,
             user_agent: options.user_agent,
-            machine_id: options.machine_id,
+            language: options.language,
         }
     }
     
+    /// Gets the platform type.
+    pub fn platform_type(&self) -> EAuthTokenPlatformType {
+        self.platform_type
+    }
+    
     /// Encrypts `password` for `account_name`.
     pub async fn encrypt_password(
         &self,
@@ -69,18 +92,12 @@ where
         password: String,
     ) -> Result<EncryptedPassword, Error> {
         let rsa_info = self.get_rsa_key(account_name).await?;
-        let n = BigUint::parse_bytes(rsa_info.publickey_mod().as_bytes(), 16)
-            .ok_or_else(|| Error::BadUint(rsa_info.publickey_mod().into()))?;
-        let e = BigUint::parse_bytes(rsa_info.publickey_exp().as_bytes(), 16)
-            .ok_or_else(|| Error::BadUint(rsa_info.publickey_exp().into()))?;
-        let key = RsaPublicKey::new(n, e)?;
-        let encrypted_password = key.encrypt(
-            &mut rand::thread_rng(),
-            Pkcs1v15Encrypt::default(),
-            password.as_bytes(),
+        let encrypted_password = helpers::encrypt_password(
+            &password,
+            rsa_info.publickey_mod(),
+            rsa_info.publickey_exp(),
         )?;
         let key_timestamp = rsa_info.timestamp();
-        let encrypted_password = encode_base64(encrypted_password);
         
         Ok(EncryptedPassword {
             encrypted_password,
@@ -88,6 +105,23 @@ where
         })
     }
     
+    /// Encrypts `password` using a previously fetched `rsa_key`.
+    pub fn encrypt_password_with_key(
+        password: &str,
+        rsa_key: &RsaKey,
+    ) -> Result<EncryptedPassword, Error> {
+        let encrypted_password = helpers::encrypt_password(
+            password,
+            &rsa_key.modulus,
+            &rsa_key.exponent,
+        )?;
+        
+        Ok(EncryptedPassword {
+            encrypted_password,
+            key_timestamp: rsa_key.timestamp,
+        })
+    }
+    
     /// Gets RSA public key for `account_name`.
     pub async fn get_rsa_key(
         &self,
@@ -110,20 +144,21 @@ where
     ) -> Result<CAuthentication_BeginAuthSessionViaCredentials_Response, Error> {
         let mut msg: CAuthentication_BeginAuthSessionViaCredentials_Request_BinaryGuardData = CAuthentication_BeginAuthSessionViaCredentials_Request_BinaryGuardData::new();
         let platform_data = self.get_platform_data()?;
-        let mut device_details: CAuthentication_DeviceDetails = platform_data.device_details.into();
+        let mut device_details: CAuthentication_DeviceDetails = details.device_details
+            .unwrap_or(platform_data.device_details)
+            .into();
         
         msg.set_account_name(details.account_name);
         msg.set_encrypted_password(details.encrypted_password);
         msg.set_encryption_timestamp(details.encryption_timestamp);
         msg.set_remember_login(details.remember_login);
         msg.set_persistence(details.persistence);
-        msg.set_website_id(platform_data.website_id.into());
+        msg.set_website_id(details.website_id.unwrap_or_else(|| WebsiteId::for_platform(self.platform_type)).as_str().into());
+        msg.set_language(self.language.into());
         
         if details.platform_type == EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient {
-            if let Some(machine_id) = &self.machine_id {
-                device_details.set_machine_id(machine_id.clone());
-            } else {
-                device_details.set_machine_id(get_machine_id(msg.account_name()));
+            if let Some(machine_id) = details.machine_id {
+                device_details.set_machine_id(machine_id);
             }
         }
         
@@ -253,7 +288,7 @@ where
         self.send_request(msg, None).await
     }
     
-    /// Sends a request.
+    /// Sends a request, with the headers of the platform type for transports that send them.
     async fn send_request<Msg>(
         &self,
         msg: Msg,
@@ -263,14 +298,24 @@ where
         Msg: ApiRequest,
         <Msg as ApiRequest>::Response: Send,
     {
-        let response = self.transport.send_request(
+        let headers = self.get_platform_data()?.headers;
+        let response = self.transport.send_request_with_headers(
             msg,
             access_token,
+            headers,
         ).await?.await??;
         
         Ok(response)
     }
     
+    /// Gets the device details sent when logging in with the platform type, or `None` if the 
+    /// platform type isn't supported.
+    pub fn default_device_details(&self) -> Option<DeviceDetails> {
+        self.get_platform_data()
+            .ok()
+            .map(|platform_data| platform_data.device_details)
+    }
+    
     fn get_platform_data(
         &self,
     ) -> Result<PlatformData, Error> {
@@ -303,7 +348,7 @@ where
                     store_base_url: "https://store.steampowered.com/",
                     use_popups: "true",
                     dev_mode: "false",
-                    language: "english",
+                    language: self.language.api_name(),
                     platform: "windows",
                     country: "US",
                     launcher_type: "0",
@@ -312,13 +357,12 @@ where
                 let referer_qs = serde_qs::to_string(&referer_query)?;
                 let mut headers = HeaderMap::new();
                 
-                headers.append(USER_AGENT, HeaderValue::from_str("Mozilla/5.0 (Windows; U; Windows NT 10.0; en-US; Valve Steam Client/default/1665786434; ) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/85.0.4183.121 Safari/537.36")?);
+                headers.append(USER_AGENT, HeaderValue::from_str(STEAM_CLIENT_USER_AGENT)?);
                 headers.append(ORIGIN, HeaderValue::from_str("https://steamloopback.host")?);
                 headers.append(REFERER, HeaderValue::from_str(&format!("https://steamloopback.host/index.html?{}", &referer_qs))?);
                 
                 Ok(PlatformData {
-                    website_id: "Unknown",
-                    // Headers are actually not used since this is sent over a CM connection
+                    // Headers are only sent by the Web API transport
                     headers,
                     device_details: DeviceDetails {
                         device_friendly_name: local_hostname,
@@ -336,8 +380,7 @@ where
                 headers.append(REFERER, HeaderValue::from_str("https://steamcommunity.com")?);
                 
                 Ok(PlatformData {
-                    website_id: "Community",
-                    // Headers are actually not used since this is sent over a CM connection
+                    // Headers are only sent by the Web API transport
                     headers,
                     device_details: DeviceDetails {
                         device_friendly_name: self.user_agent.to_string(),
@@ -350,12 +393,11 @@ where
             EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp => {
                 let mut headers = HeaderMap::new();
                 
-                headers.append(USER_AGENT, HeaderValue::from_str("okhttp/3.12.12")?);
-                headers.append(COOKIE, HeaderValue::from_str("mobileClient=android; mobileClientVersion=777777 3.0.0")?);
+                headers.append(USER_AGENT, HeaderValue::from_str(MOBILE_USER_AGENT)?);
+                headers.append(COOKIE, HeaderValue::from_str(MOBILE_CLIENT_COOKIE)?);
                 
                 Ok(PlatformData {
-                    website_id: "Mobile",
-                    // Headers are actually not used since this is sent over a CM connection
+                    // Headers are only sent by the Web API transport
                     headers,
                     device_details: DeviceDetails {
                         device_friendly_name: String::from("Galaxy S22"),
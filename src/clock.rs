@@ -0,0 +1,86 @@
+//! Sources of the current time.
+//! 
+//! Time-sensitive checks such as login timeouts and token ages read the time from a [`Clock`] 
+//! rather than the system clock directly. This allows a clock to be injected, e.g. one adjusted 
+//! by your own Steam time offset, or a [`MockClock`] in tests.
+
+use crate::types::DateTime;
+use std::fmt::Debug;
+use std::sync::Mutex;
+use chrono::Duration;
+
+/// A source of the current time.
+pub trait Clock: Debug + Send + Sync {
+    /// Gets the current time.
+    fn now(&self) -> DateTime;
+}
+
+/// A [`Clock`] that reads the system time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime {
+        chrono::Utc::now()
+    }
+}
+
+/// A [`Clock`] that only moves when told to.
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<DateTime>,
+}
+
+impl MockClock {
+    /// Creates a new [`MockClock`] set to `now`.
+    pub fn new(now: DateTime) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+    
+    /// Sets the current time.
+    pub fn set(&self, now: DateTime) {
+        *self.now.lock().unwrap() = now;
+    }
+    
+    /// Moves the current time forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Default for MockClock {
+    /// Creates a [`MockClock`] set to the current system time.
+    fn default() -> Self {
+        Self::new(SystemClock.now())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    
+    #[test]
+    fn mock_clock_moves_only_when_told() {
+        let start = chrono::Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let clock = MockClock::new(start);
+        
+        assert_eq!(clock.now(), start);
+        
+        clock.advance(Duration::try_seconds(30).unwrap());
+        
+        assert_eq!(clock.now().timestamp(), 1_700_000_030);
+        
+        clock.set(start);
+        
+        assert_eq!(clock.now(), start);
+    }
+}
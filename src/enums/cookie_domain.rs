@@ -0,0 +1,67 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A Steam website web cookies can be obtained for.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum CookieDomain {
+    /// steamcommunity.com.
+    Community,
+    /// store.steampowered.com.
+    Store,
+    /// help.steampowered.com.
+    Help,
+}
+
+/// Returned when parsing a [`CookieDomain`] that isn't known.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Unknown cookie domain \"{}\"", .0)]
+pub struct UnknownCookieDomain(pub String);
+
+impl CookieDomain {
+    /// The domains cookies are obtained for when none are given.
+    pub const DEFAULT: [Self; 2] = [Self::Community, Self::Store];
+    
+    /// The host name of the website, e.g. `"steamcommunity.com"`.
+    pub fn host(&self) -> &'static str {
+        match self {
+            Self::Community => "steamcommunity.com",
+            Self::Store => "store.steampowered.com",
+            Self::Help => "help.steampowered.com",
+        }
+    }
+    
+    /// Gets the domain with the host name `host`, if it's known.
+    pub fn from_host(host: &str) -> Option<Self> {
+        [Self::Community, Self::Store, Self::Help]
+            .into_iter()
+            .find(|domain| domain.host().eq_ignore_ascii_case(host))
+    }
+}
+
+impl fmt::Display for CookieDomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.host())
+    }
+}
+
+impl FromStr for CookieDomain {
+    type Err = UnknownCookieDomain;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_host(s).ok_or_else(|| UnknownCookieDomain(s.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn parses_known_hosts() {
+        for domain in [CookieDomain::Community, CookieDomain::Store, CookieDomain::Help] {
+            assert_eq!(domain.host().parse::<CookieDomain>().unwrap(), domain);
+        }
+        
+        assert!("checkout.steampowered.com".parse::<CookieDomain>().is_err());
+    }
+}
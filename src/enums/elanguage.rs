@@ -0,0 +1,113 @@
+use num_enum::{TryFromPrimitive, IntoPrimitive};
+
+/// A language supported by Steam, sent with requests so error messages are localized.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, TryFromPrimitive, IntoPrimitive)]
+#[repr(u32)]
+pub enum ELanguage {
+    #[default]
+    English = 0,
+    German = 1,
+    French = 2,
+    Italian = 3,
+    Korean = 4,
+    Spanish = 5,
+    SChinese = 6,
+    TChinese = 7,
+    Russian = 8,
+    Thai = 9,
+    Japanese = 10,
+    Portuguese = 11,
+    Polish = 12,
+    Danish = 13,
+    Dutch = 14,
+    Finnish = 15,
+    Norwegian = 16,
+    Swedish = 17,
+    Hungarian = 18,
+    Czech = 19,
+    Romanian = 20,
+    Turkish = 21,
+    Brazilian = 22,
+    Bulgarian = 23,
+    Greek = 24,
+    Arabic = 25,
+    Ukrainian = 26,
+    LatAmSpanish = 27,
+    Vietnamese = 28,
+    Indonesian = 29,
+}
+
+impl ELanguage {
+    /// The name Steam uses for this language in API parameters, e.g. `"english"`.
+    pub fn api_name(&self) -> &'static str {
+        match self {
+            Self::English => "english",
+            Self::German => "german",
+            Self::French => "french",
+            Self::Italian => "italian",
+            Self::Korean => "koreana",
+            Self::Spanish => "spanish",
+            Self::SChinese => "schinese",
+            Self::TChinese => "tchinese",
+            Self::Russian => "russian",
+            Self::Thai => "thai",
+            Self::Japanese => "japanese",
+            Self::Portuguese => "portuguese",
+            Self::Polish => "polish",
+            Self::Danish => "danish",
+            Self::Dutch => "dutch",
+            Self::Finnish => "finnish",
+            Self::Norwegian => "norwegian",
+            Self::Swedish => "swedish",
+            Self::Hungarian => "hungarian",
+            Self::Czech => "czech",
+            Self::Romanian => "romanian",
+            Self::Turkish => "turkish",
+            Self::Brazilian => "brazilian",
+            Self::Bulgarian => "bulgarian",
+            Self::Greek => "greek",
+            Self::Arabic => "arabic",
+            Self::Ukrainian => "ukrainian",
+            Self::LatAmSpanish => "latam",
+            Self::Vietnamese => "vietnamese",
+            Self::Indonesian => "indonesian",
+        }
+    }
+    
+    /// The language tag for this language used in `Accept-Language` headers, e.g. `"en"`.
+    pub fn web_api_language_code(&self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::German => "de",
+            Self::French => "fr",
+            Self::Italian => "it",
+            Self::Korean => "ko",
+            Self::Spanish => "es",
+            Self::SChinese => "zh-CN",
+            Self::TChinese => "zh-TW",
+            Self::Russian => "ru",
+            Self::Thai => "th",
+            Self::Japanese => "ja",
+            Self::Portuguese => "pt",
+            Self::Polish => "pl",
+            Self::Danish => "da",
+            Self::Dutch => "nl",
+            Self::Finnish => "fi",
+            Self::Norwegian => "no",
+            Self::Swedish => "sv",
+            Self::Hungarian => "hu",
+            Self::Czech => "cs",
+            Self::Romanian => "ro",
+            Self::Turkish => "tr",
+            Self::Brazilian => "pt-BR",
+            Self::Bulgarian => "bg",
+            Self::Greek => "el",
+            Self::Arabic => "ar",
+            Self::Ukrainian => "uk",
+            Self::LatAmSpanish => "es-419",
+            Self::Vietnamese => "vi",
+            Self::Indonesian => "id",
+        }
+    }
+}
\ No newline at end of file
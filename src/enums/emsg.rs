@@ -1,7 +1,7 @@
-use num_enum::{TryFromPrimitive, IntoPrimitive};
+use num_enum::{FromPrimitive, IntoPrimitive};
 
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone, Copy, Eq, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, FromPrimitive, IntoPrimitive)]
 #[repr(u32)]
 pub enum EMsg {
     Invalid = 0,
@@ -1883,4 +1883,46 @@ pub enum EMsg {
     ChatServerGetPendingNotificationCount = 12000,
     ChatServerGetPendingNotificationCountResponse = 12001,
     ServerSecretChanged = 12100,
+    /// A value which isn't known, e.g. a message added to Steam after this list was generated. 
+    /// The raw value is kept so unrecognized messages can be passed through.
+    #[num_enum(catch_all)]
+    Unknown(u32),
+}
+
+impl EMsg {
+    /// The highest known value, [`EMsg::ServerSecretChanged`].
+    pub(super) const MAX_VALUE: u32 = 12100;
+    
+    /// Gets the variant for `value`, if it's known.
+    pub fn known(value: u32) -> Option<Self> {
+        Some(Self::from(value)).filter(|emsg| !matches!(emsg, Self::Unknown(_)))
+    }
+    
+    /// Describes a raw EMsg value for diagnostics, e.g. `"9804 (ServiceMethodCallFromClientNonAuthed)"`.
+    /// Values which aren't known are described by the nearest known values, or by whether they 
+    /// fall outside of the known range.
+    pub fn describe(value: u32) -> String {
+        if let Some(emsg) = EMsg::known(value) {
+            return format!("{value} ({emsg:?})");
+        }
+        
+        if value > Self::MAX_VALUE {
+            return format!("{value} (unknown; outside of known range 0-{})", Self::MAX_VALUE);
+        }
+        
+        let below = (0..value)
+            .rev()
+            .find_map(EMsg::known);
+        let above = (value + 1..=Self::MAX_VALUE)
+            .find_map(EMsg::known);
+        
+        match (below, above) {
+            (Some(below), Some(above)) => format!(
+                "{value} (unknown; between {below:?} ({}) and {above:?} ({}))",
+                u32::from(below),
+                u32::from(above),
+            ),
+            _ => format!("{value} (unknown)"),
+        }
+    }
 }
\ No newline at end of file
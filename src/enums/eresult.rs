@@ -1,8 +1,9 @@
 use num_enum::{TryFromPrimitive, IntoPrimitive};
-use serde::{Serialize, Deserialize};
+use std::fmt;
 
+/// A result code. Serializes as its numeric value, see [`EnumValue`](super::EnumValue).
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone, Copy, Eq, PartialEq, TryFromPrimitive, IntoPrimitive, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, TryFromPrimitive, IntoPrimitive)]
 #[repr(i32)]
 pub enum EResult {
     Invalid = 0,
@@ -132,4 +133,86 @@ pub enum EResult {
     ChargerRequired = 125,
     CachedCredentialInvalid = 126,
     PhoneNumberIsVOIP = 127,
-}
\ No newline at end of file
+}
+
+impl EResult {
+    /// Checks if a request that failed with this result may succeed if retried later, i.e. 
+    /// Steam is temporarily unavailable or busy.
+    pub fn is_retriable(&self) -> bool {
+        matches!(self, Self::ServiceUnavailable | Self::Busy | Self::TryAnotherCM)
+    }
+    
+    /// The lowest known value.
+    pub(super) const MIN_VALUE: i32 = EResult::Invalid as i32;
+    /// The highest known value.
+    pub(super) const MAX_VALUE: i32 = EResult::PhoneNumberIsVOIP as i32;
+    
+    /// Describes a raw EResult value for diagnostics, e.g. 
+    /// `"85 (AccountLogonDeniedNeedTwoFactorCode)"`. Values which aren't known are described by 
+    /// whether they fall outside of the known range.
+    pub fn describe(value: i32) -> String {
+        match EResult::try_from(value) {
+            Ok(eresult) => match eresult.description() {
+                Some(description) => format!("{value} ({eresult:?}: {description})"),
+                None => format!("{value} ({eresult:?})"),
+            },
+            Err(_) if !(Self::MIN_VALUE..=Self::MAX_VALUE).contains(&value) => {
+                format!("{value} (unknown; outside of known range {}-{})", Self::MIN_VALUE, Self::MAX_VALUE)
+            },
+            Err(_) => format!("{value} (unknown)"),
+        }
+    }
+    
+    /// A short description of what this result means, for common results during authentication.
+    pub fn description(&self) -> Option<&'static str> {
+        match self {
+            Self::Fail => Some("generic failure"),
+            Self::NoConnection => Some("no or failed network connection"),
+            Self::InvalidPassword => Some("password or ticket is invalid"),
+            Self::LoggedInElsewhere => Some("same user logged in elsewhere"),
+            Self::InvalidParam => Some("a parameter is incorrect"),
+            Self::Busy => Some("called method is busy, try again later"),
+            Self::InvalidState => Some("called object was in an invalid state"),
+            Self::AccessDenied => Some("access is denied"),
+            Self::Timeout => Some("operation timed out"),
+            Self::Banned => Some("VAC2 banned"),
+            Self::AccountNotFound => Some("account not found"),
+            Self::ServiceUnavailable => Some("the requested service is currently unavailable"),
+            Self::Expired => Some("access has expired"),
+            Self::TryAnotherCM => Some("try another CM server"),
+            Self::AccountDisabled => Some("the account is disabled"),
+            Self::AccountLogonDenied => Some("the account logon was denied, an email code is required"),
+            Self::InvalidLoginAuthCode => Some("the email code is invalid"),
+            Self::AccountLogonDeniedNoMailSent => Some("the account logon was denied and no email could be sent"),
+            Self::ExpiredLoginAuthCode => Some("the email code has expired"),
+            Self::AccountLocked => Some("the account is locked"),
+            Self::AccountLogonDeniedVerifiedEmailRequired => Some("the account requires a verified email address"),
+            Self::RateLimitExceeded => Some("too many requests, try again later"),
+            Self::AccountLogonDeniedNeedTwoFactorCode => Some("a code from the mobile authenticator is required"),
+            Self::AccountLoginDeniedThrottle => Some("too many login attempts, try again later"),
+            Self::TwoFactorCodeMismatch => Some("the mobile authenticator code is invalid"),
+            Self::TimeIsOutOfSync => Some("the time is out of sync with the server"),
+            Self::InvalidSignature => Some("the signature is invalid"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for EResult {
+    /// Formats the numeric value along with the name and description, e.g. 
+    /// `"5 (InvalidPassword: password or ticket is invalid)"`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&EResult::describe(i32::from(*self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn displays_value_and_name() {
+        assert_eq!(EResult::InvalidPassword.to_string(), "5 (InvalidPassword: password or ticket is invalid)");
+        assert_eq!(EResult::OK.to_string(), "1 (OK)");
+    }
+}
@@ -1,10 +1,18 @@
 mod eos_type;
+mod cookie_domain;
+mod elanguage;
 mod eresult;
 mod emsg;
+mod value;
+mod website_id;
 
 pub use eos_type::EOSType;
+pub use elanguage::ELanguage;
 pub use emsg::EMsg;
 pub use eresult::EResult;
+pub use value::{EnumValue, serde_value, serde_name};
+pub use website_id::{WebsiteId, UnknownWebsiteId};
+pub use cookie_domain::{CookieDomain, UnknownCookieDomain};
 
 pub use crate::proto::enums::ESessionPersistence;
 pub use crate::proto::steammessages_auth_steamclient::{
@@ -0,0 +1,281 @@
+use super::{
+    EMsg,
+    EResult,
+    EAuthTokenPlatformType,
+    EAuthSessionGuardType,
+    EAuthSessionSecurityHistory,
+    ESessionPersistence,
+    ETokenRenewalType,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use lazy_static::lazy_static;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{self, Visitor};
+
+/// An enum which is represented by a numeric value in the protocol.
+///
+/// [`EResult`] and [`EMsg`] serialize as their numeric value. The protobuf enums, such as
+/// [`EAuthTokenPlatformType`], can be serialized the same way using
+/// `#[serde(with = "steam_session::enums::serde_value")]`, or by name using
+/// `#[serde(with = "steam_session::enums::serde_name")]`.
+///
+/// When deserializing, both numeric values and names are accepted. Numeric values which aren't
+/// known deserialize into [`EnumValue::UNKNOWN`] rather than failing, as newer versions of Steam
+/// may send values this crate doesn't know about yet. [`EMsg`] keeps such values in
+/// [`EMsg::Unknown`] instead.
+pub trait EnumValue: Sized + Copy + fmt::Debug {
+    /// The value unknown numeric values deserialize into.
+    const UNKNOWN: Self;
+
+    /// Gets the numeric value.
+    fn value(self) -> i64;
+
+    /// Gets the variant for a numeric value, if it is known.
+    fn from_value(value: i64) -> Option<Self>;
+
+    /// Gets the variant for a name, e.g. `"OK"`, if it is known.
+    fn from_name(name: &str) -> Option<Self>;
+}
+
+lazy_static! {
+    static ref ERESULT_NAMES: HashMap<String, EResult> = (EResult::MIN_VALUE..=EResult::MAX_VALUE)
+        .filter_map(|value| EResult::try_from(value).ok())
+        .map(|eresult| (format!("{eresult:?}"), eresult))
+        .collect();
+    static ref EMSG_NAMES: HashMap<String, EMsg> = (0..=EMsg::MAX_VALUE)
+        .filter_map(EMsg::known)
+        .map(|emsg| (format!("{emsg:?}"), emsg))
+        .collect();
+}
+
+impl EnumValue for EResult {
+    const UNKNOWN: Self = EResult::Invalid;
+
+    fn value(self) -> i64 {
+        i32::from(self).into()
+    }
+
+    fn from_value(value: i64) -> Option<Self> {
+        EResult::try_from(i32::try_from(value).ok()?).ok()
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        ERESULT_NAMES.get(name).copied()
+    }
+}
+
+impl EnumValue for EMsg {
+    const UNKNOWN: Self = EMsg::Invalid;
+
+    fn value(self) -> i64 {
+        u32::from(self).into()
+    }
+
+    // unknown values are kept in `EMsg::Unknown` so they serialize back to the same value
+    fn from_value(value: i64) -> Option<Self> {
+        Some(EMsg::from(u32::try_from(value).ok()?))
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        EMSG_NAMES.get(name).copied()
+    }
+}
+
+macro_rules! impl_proto_enum_value {
+    ($($enum:ident => $unknown:ident),* $(,)?) => {
+        $(
+            impl EnumValue for $enum {
+                const UNKNOWN: Self = $enum::$unknown;
+
+                fn value(self) -> i64 {
+                    protobuf::Enum::value(&self).into()
+                }
+
+                fn from_value(value: i64) -> Option<Self> {
+                    <Self as protobuf::Enum>::from_i32(i32::try_from(value).ok()?)
+                }
+
+                fn from_name(name: &str) -> Option<Self> {
+                    <Self as protobuf::Enum>::from_str(name)
+                }
+            }
+        )*
+    };
+}
+
+impl_proto_enum_value!(
+    EAuthTokenPlatformType => k_EAuthTokenPlatformType_Unknown,
+    EAuthSessionGuardType => k_EAuthSessionGuardType_Unknown,
+    EAuthSessionSecurityHistory => k_EAuthSessionSecurityHistory_Invalid,
+    ESessionPersistence => k_ESessionPersistence_Invalid,
+    ETokenRenewalType => k_ETokenRenewalType_None,
+);
+
+/// Deserializes a numeric value or a name.
+struct EnumValueVisitor<T>(PhantomData<T>);
+
+impl<T> Visitor<'_> for EnumValueVisitor<T>
+where
+    T: EnumValue,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a numeric value or a name")
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        Ok(T::from_value(value).unwrap_or(T::UNKNOWN))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        Ok(i64::try_from(value).ok().and_then(T::from_value).unwrap_or(T::UNKNOWN))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        if let Ok(value) = value.parse::<i64>() {
+            return self.visit_i64(value);
+        }
+
+        T::from_name(value)
+            .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Str(value), &self))
+    }
+}
+
+fn deserialize_enum<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: EnumValue,
+{
+    deserializer.deserialize_any(EnumValueVisitor(PhantomData))
+}
+
+impl Serialize for EResult {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i32((*self).into())
+    }
+}
+
+impl<'de> Deserialize<'de> for EResult {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_enum(deserializer)
+    }
+}
+
+impl Serialize for EMsg {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u32((*self).into())
+    }
+}
+
+impl<'de> Deserialize<'de> for EMsg {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_enum(deserializer)
+    }
+}
+
+/// Serializes an [`EnumValue`] as its numeric value, for use with
+/// `#[serde(with = "steam_session::enums::serde_value")]`.
+pub mod serde_value {
+    use super::EnumValue;
+    use serde::{Serializer, Deserializer};
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: EnumValue,
+        S: Serializer,
+    {
+        serializer.serialize_i64(value.value())
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: EnumValue,
+        D: Deserializer<'de>,
+    {
+        super::deserialize_enum(deserializer)
+    }
+}
+
+/// Serializes an [`EnumValue`] as its name, for use with
+/// `#[serde(with = "steam_session::enums::serde_name")]`.
+pub mod serde_name {
+    use super::EnumValue;
+    use serde::{Serializer, Deserializer};
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: EnumValue,
+        S: Serializer,
+    {
+        serializer.collect_str(&format_args!("{value:?}"))
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: EnumValue,
+        D: Deserializer<'de>,
+    {
+        super::deserialize_enum(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct State {
+        eresult: EResult,
+        #[serde(with = "serde_value")]
+        platform_type: EAuthTokenPlatformType,
+        #[serde(with = "serde_name")]
+        guard_type: EAuthSessionGuardType,
+    }
+
+    #[test]
+    fn round_trips_enums() {
+        let state = State {
+            eresult: EResult::TryAnotherCM,
+            platform_type: EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp,
+            guard_type: EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceCode,
+        };
+        let json = serde_json::to_string(&state).unwrap();
+
+        assert_eq!(json, r#"{"eresult":48,"platform_type":3,"guard_type":"k_EAuthSessionGuardType_DeviceCode"}"#);
+        assert_eq!(serde_json::from_str::<State>(&json).unwrap(), state);
+    }
+
+    #[test]
+    fn deserializes_unknown_values_into_catch_all() {
+        assert_eq!(serde_json::from_str::<EResult>("4").unwrap(), EResult::Invalid);
+        assert_eq!(serde_json::from_str::<EMsg>("999999").unwrap(), EMsg::Unknown(999999));
+        assert_eq!(serde_json::to_string(&EMsg::Unknown(999999)).unwrap(), "999999");
+        assert_eq!(serde_json::from_str::<EMsg>("-1").unwrap(), EMsg::Invalid);
+        assert_eq!(serde_json::from_str::<EResult>(r#""TwoFactorCodeMismatch""#).unwrap(), EResult::TwoFactorCodeMismatch);
+        assert!(serde_json::from_str::<EResult>(r#""NotAResult""#).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use super::EAuthTokenPlatformType;
+use std::fmt;
+use std::str::FromStr;
+
+/// The Steam web property a login is for, sent as `website_id` when starting an auth session.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum WebsiteId {
+    /// Used by the Steam client.
+    Unknown,
+    /// steamcommunity.com.
+    Community,
+    /// store.steampowered.com.
+    Store,
+    /// help.steampowered.com.
+    Help,
+    /// The Steam mobile app.
+    Mobile,
+    /// partner.steamgames.com.
+    Partner,
+}
+
+/// Returned when parsing a [`WebsiteId`] that isn't known.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Unknown website ID \"{}\"", .0)]
+pub struct UnknownWebsiteId(pub String);
+
+impl WebsiteId {
+    /// The value sent to Steam, e.g. `"Community"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Unknown => "Unknown",
+            Self::Community => "Community",
+            Self::Store => "Store",
+            Self::Help => "Help",
+            Self::Mobile => "Mobile",
+            Self::Partner => "Partner",
+        }
+    }
+    
+    /// The website ID used by the official clients for `platform_type`.
+    pub fn for_platform(platform_type: EAuthTokenPlatformType) -> Self {
+        match platform_type {
+            EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser => Self::Community,
+            EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp => Self::Mobile,
+            EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient |
+            EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown => Self::Unknown,
+        }
+    }
+    
+    /// The origin of the website, for websites cookies can be obtained for.
+    pub fn origin(&self) -> Option<&'static str> {
+        match self {
+            Self::Community => Some("https://steamcommunity.com"),
+            Self::Store => Some("https://store.steampowered.com"),
+            Self::Help => Some("https://help.steampowered.com"),
+            Self::Partner => Some("https://partner.steamgames.com"),
+            Self::Unknown |
+            Self::Mobile => None,
+        }
+    }
+}
+
+impl fmt::Display for WebsiteId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WebsiteId {
+    type Err = UnknownWebsiteId;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Unknown" => Ok(Self::Unknown),
+            "Community" => Ok(Self::Community),
+            "Store" => Ok(Self::Store),
+            "Help" => Ok(Self::Help),
+            "Mobile" => Ok(Self::Mobile),
+            "Partner" => Ok(Self::Partner),
+            s => Err(UnknownWebsiteId(s.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn parses_known_website_ids() {
+        for website_id in [
+            WebsiteId::Unknown,
+            WebsiteId::Community,
+            WebsiteId::Store,
+            WebsiteId::Help,
+            WebsiteId::Mobile,
+            WebsiteId::Partner,
+        ] {
+            assert_eq!(website_id.as_str().parse::<WebsiteId>().unwrap(), website_id);
+        }
+        
+        assert!("Steam".parse::<WebsiteId>().is_err());
+    }
+    
+    #[test]
+    fn matches_official_clients_for_platform() {
+        assert_eq!(WebsiteId::for_platform(EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown), WebsiteId::Unknown);
+        assert_eq!(WebsiteId::for_platform(EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient), WebsiteId::Unknown);
+        assert_eq!(WebsiteId::for_platform(EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser), WebsiteId::Community);
+        assert_eq!(WebsiteId::for_platform(EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp), WebsiteId::Mobile);
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt::{Display, Formatter};
+use std::fmt::{self, Debug, Formatter};
 use std::str::FromStr;
 
 use base64::{engine::general_purpose, Engine as _};
@@ -13,6 +13,12 @@ use steamid_ng::SteamID;
 type HmacSha256 = Hmac<Sha256>;
 
 pub const DEFAULT_USER_AGENT: &str = "linux x86_64";
+/// The user agent of the Steam client's embedded browser.
+pub const STEAM_CLIENT_USER_AGENT: &str = "Mozilla/5.0 (Windows; U; Windows NT 10.0; en-US; Valve Steam Client/default/1665786434; ) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/85.0.4183.121 Safari/537.36";
+/// The user agent of the Steam mobile app.
+pub const MOBILE_USER_AGENT: &str = "okhttp/3.12.12";
+/// The cookie sent by the Steam mobile app to identify itself.
+pub const MOBILE_CLIENT_COOKIE: &str = "mobileClient=android; mobileClientVersion=777777 3.0.0";
 
 const CHARS: [char; 26] = [
     'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S',
@@ -251,6 +257,16 @@ pub fn get_spoofed_hostname() -> String {
     output
 }
 
+/// Wraps a secret value so that its [`Debug`] output does not reveal it. Used in the `Debug` 
+/// implementations of structs holding tokens, passwords or secrets.
+pub(crate) struct Redacted<T>(pub T);
+
+impl<T> Debug for Redacted<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("\"<redacted>\"")
+    }
+}
+
 pub fn create_sha1(input: &[u8]) -> Vec<u8> {
     let mut hasher = Sha1::new();
 
@@ -278,6 +294,14 @@ mod tests {
         assert_eq!(decoded.per, 1);
     }
 
+    #[test]
+    fn redacted_hides_value() {
+        let secret = "hunter2".to_string();
+        
+        assert_eq!(format!("{:?}", Redacted(&secret)), "\"<redacted>\"");
+        assert_eq!(format!("{:?}", Some(&secret).map(Redacted)), "Some(\"<redacted>\")");
+    }
+
     #[test]
     fn test_bad_jwt() {
         let jwt = "Yup, this is a bad JWT. It's not even a JWT. It's just a string. It's not even base64 encoded.";
@@ -1,6 +1,8 @@
 //! Crate for authenticating with the Steam auth server.
 
 pub mod enums;
+pub mod clock;
+pub mod steam_guard;
 pub mod net;
 pub mod login_session;
 pub mod transports;
@@ -8,9 +10,13 @@ pub mod authentication_client;
 pub mod login_approver;
 pub mod request;
 pub mod response;
+pub mod openid;
 
 mod types;
 mod serializers;
 mod helpers;
 
 pub use steam_session_proto as proto;
+
+#[cfg(not(any(feature = "native-tls", feature = "rustls-tls")))]
+compile_error!("steam-session requires a TLS backend, enable the \"native-tls\" or \"rustls-tls\" feature");
@@ -1,6 +1,6 @@
 use super::LoginApprover;
 use crate::enums::EAuthTokenPlatformType;
-use crate::helpers::DEFAULT_USER_AGENT;
+use crate::helpers::{DEFAULT_USER_AGENT, Redacted};
 
 /// Builder for creating a [`LoginApprover`].
 ///
@@ -14,16 +14,26 @@ use crate::helpers::DEFAULT_USER_AGENT;
 ///     .user_agent("Mozilla/5.0")
 ///     .build();
 /// ```
-#[derive(Debug)]
 pub struct LoginApproverBuilder {
     pub access_token: String,
     pub shared_secret: String,
     pub platform_type: EAuthTokenPlatformType,
-    pub machine_id: Option<Vec<u8>>,
     pub user_agent: &'static str,
     pub client: reqwest::Client,
 }
 
+impl std::fmt::Debug for LoginApproverBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoginApproverBuilder")
+            .field("access_token", &Redacted(&self.access_token))
+            .field("shared_secret", &Redacted(&self.shared_secret))
+            .field("platform_type", &self.platform_type)
+            .field("user_agent", &self.user_agent)
+            .field("client", &self.client)
+            .finish()
+    }
+}
+
 impl LoginApproverBuilder {
     /// Creates a new [`LoginApproverBuilder`].
     pub fn new(
@@ -34,7 +44,6 @@ impl LoginApproverBuilder {
             access_token,
             shared_secret,
             platform_type: EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp,
-            machine_id: None,
             user_agent: DEFAULT_USER_AGENT,
             client: Default::default(),
         }
@@ -59,12 +68,6 @@ impl LoginApproverBuilder {
         self
     }
     
-    /// Sets the machine id. If not set, a random machine id will be generated.
-    pub fn machine_id(mut self, machine_id: Option<Vec<u8>>) -> Self {
-        self.machine_id = machine_id;
-        self
-    }
-    
     /// Sets the user agent. If not set, the default user agent will be used.
     pub fn user_agent(mut self, user_agent: &'static str) -> Self {
         self.user_agent = user_agent;
@@ -23,7 +23,7 @@ pub use error::Error;
 pub use builder::LoginApproverBuilder;
 
 use crate::authentication_client::{AuthenticationClient, AuthenticationClientConstructorOptions};
-use crate::helpers::{JwtPayload, decode_base64, generate_hmac_signature};
+use crate::helpers::{JwtPayload, Redacted, decode_base64, generate_hmac_signature};
 use crate::request::{ApproveAuthSessionRequest, MobileConfirmationRequest};
 use crate::transports::web_api::WebApiTransport;
 use reqwest::Client;
@@ -32,13 +32,24 @@ use steamid_ng::SteamID;
 use byteorder::{WriteBytesExt, LittleEndian};
 
 /// Can be used to approve a login attempt that was started with a QR code.
-#[derive(Debug)]
+/// 
+/// The `Debug` output redacts the access token and shared secret.
 pub struct LoginApprover {
     shared_secret: String,
     access_token: String,
     handler: AuthenticationClient<WebApiTransport>,
 }
 
+impl std::fmt::Debug for LoginApprover {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoginApprover")
+            .field("shared_secret", &Redacted(&self.shared_secret))
+            .field("access_token", &Redacted(&self.access_token))
+            .field("handler", &self.handler)
+            .finish()
+    }
+}
+
 impl LoginApprover {
     pub fn builder(
         access_token: String,
@@ -128,11 +139,11 @@ impl TryFrom<LoginApproverBuilder> for LoginApprover {
     fn try_from(builder: LoginApproverBuilder) -> Result<Self, Self::Error> {
         let transport = WebApiTransport::new();
         let handler = AuthenticationClient::new(AuthenticationClientConstructorOptions {
-            machine_id: builder.machine_id,
             platform_type: builder.platform_type,
             transport,
             client: builder.client,
             user_agent: builder.user_agent,
+            language: Default::default(),
         });
         let decoded_access_token = JwtPayload::from_str(&builder.access_token)?;
         
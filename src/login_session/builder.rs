@@ -1,14 +1,33 @@
-use super::{LoginSessionError, LoginSession};
-use super::helpers::LoginSessionOptions;
+use super::{LoginSessionError, LoginSession, LoginThrottle};
+use super::helpers::{LoginSessionOptions, create_handler};
 use crate::transports::Transport;
+use crate::authentication_client::{AuthenticationClient, MachineId};
+use crate::enums::ELanguage;
+use crate::clock::{Clock, SystemClock};
+use std::sync::Arc;
 use steam_session_proto::steammessages_auth_steamclient::EAuthTokenPlatformType;
 
+/// Where a session's [`AuthenticationClient`] comes from.
+enum HandlerSource<T> {
+    /// A new client is created using the transport.
+    Transport(T),
+    /// The client is shared with other sessions.
+    Shared(Arc<AuthenticationClient<T>>),
+}
+
 pub struct LoginSessionBuilder<T> {
     platform_type: EAuthTokenPlatformType,
-    transport: T,
+    source: HandlerSource<T>,
     client: reqwest::Client,
     user_agent: Option<&'static str>,
-    machine_id: Option<Vec<u8>>,
+    machine_id: MachineId,
+    language: Option<ELanguage>,
+    retry_invalid_password: bool,
+    retry_skewed_totp_code: bool,
+    auto_mint_access_token: bool,
+    allow_platform_mismatch: bool,
+    clock: Arc<dyn Clock>,
+    throttle: Option<LoginThrottle>,
 }
 
 impl<T> LoginSessionBuilder<T>
@@ -19,12 +38,37 @@ where
         transport: T,
         platform_type: EAuthTokenPlatformType,
     ) -> Self {
+        Self::with_source(HandlerSource::Transport(transport), platform_type)
+    }
+    
+    /// Creates a builder for a session sharing `handler` with other sessions, e.g. the handler 
+    /// of another session from [`LoginSession::handler`]. This avoids creating a client and 
+    /// connection per account when working with many accounts.
+    /// 
+    /// The platform type, user agent and language are those of the handler. Setting a different 
+    /// `platform_type`, or setting `user_agent` or `language`, makes `build` fail with 
+    /// [`LoginSessionError::SharedHandlerOption`].
+    pub fn with_handler(handler: Arc<AuthenticationClient<T>>) -> Self {
+        let platform_type = handler.platform_type();
+        
+        Self::with_source(HandlerSource::Shared(handler), platform_type)
+    }
+    
+    /// Creates a builder with the default options.
+    fn with_source(source: HandlerSource<T>, platform_type: EAuthTokenPlatformType) -> Self {
         Self {
             platform_type,
-            transport,
+            source,
             client: Default::default(),
             user_agent: None,
-            machine_id: None,
+            machine_id: MachineId::default(),
+            language: None,
+            retry_invalid_password: false,
+            retry_skewed_totp_code: true,
+            auto_mint_access_token: true,
+            allow_platform_mismatch: false,
+            clock: Arc::new(SystemClock),
+            throttle: None,
         }
     }
 
@@ -38,8 +82,11 @@ where
         self
     }
     
-    pub fn machine_id(mut self, machine_id: Vec<u8>) -> Self {
-        self.machine_id = Some(machine_id);
+    /// Sets the machine ID used when logging in with 
+    /// [`EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient`]. Accepts raw bytes or a 
+    /// [`MachineId`]. Defaults to [`MachineId::Generate`].
+    pub fn machine_id(mut self, machine_id: impl Into<MachineId>) -> Self {
+        self.machine_id = machine_id.into();
         self
     }
 
@@ -48,13 +95,103 @@ where
         self
     }
     
+    /// Sets the language sent with requests. Steam uses this to localize error messages. 
+    /// Defaults to [`ELanguage::English`].
+    pub fn language(mut self, language: ELanguage) -> Self {
+        self.language = Some(language);
+        self
+    }
+    
+    /// Sets whether `start_with_credentials` retries once with a freshly fetched RSA key when 
+    /// Steam rejects the password. The RSA key is only valid for a short window, so a stale key 
+    /// is indistinguishable from a wrong password on slow connections. Defaults to `false`, as 
+    /// a wrong password is then sent twice, and both attempts count against the login rate 
+    /// limit.
+    pub fn retry_invalid_password(mut self, retry_invalid_password: bool) -> Self {
+        self.retry_invalid_password = retry_invalid_password;
+        self
+    }
+    
+    /// Sets whether a Steam Guard code generated from the `shared_secret` is retried with the 
+    /// codes for the adjacent time windows when Steam rejects it, in case the local clock has 
+    /// drifted from Steam's. Each adjacent window is tried once. Codes supplied as 
+    /// `steam_guard_code` or using `submit_steam_guard_code` are never retried. Defaults to 
+    /// `true`.
+    pub fn retry_skewed_totp_code(mut self, retry_skewed_totp_code: bool) -> Self {
+        self.retry_skewed_totp_code = retry_skewed_totp_code;
+        self
+    }
+    
+    /// Sets whether an access token is kept when logging in. If enabled, the access token Steam 
+    /// issues along with the refresh token is kept, and one is minted right away for 
+    /// [`EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp`] if Steam didn't issue one. 
+    /// If disabled, only the refresh token is kept and `get_access_token` returns `None` until 
+    /// an access token is minted on demand using `refresh_access_token` or `get_web_cookies`. 
+    /// Defaults to `true`.
+    pub fn auto_mint_access_token(mut self, auto_mint_access_token: bool) -> Self {
+        self.auto_mint_access_token = auto_mint_access_token;
+        self
+    }
+    
+    /// Sets whether `set_refresh_token` accepts a token whose audience doesn't match the platform 
+    /// type, e.g. when restoring a token saved under a different configuration. Requests made 
+    /// with a mismatched token are likely to be rejected by Steam. Defaults to `false`.
+    pub fn allow_platform_mismatch(mut self, allow_platform_mismatch: bool) -> Self {
+        self.allow_platform_mismatch = allow_platform_mismatch;
+        self
+    }
+    
+    /// Sets the clock used for time-sensitive checks such as the login timeout and the age of 
+    /// the access token. Defaults to [`SystemClock`].
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+    
+    /// Sets a [`LoginThrottle`] shared with other sessions. Login requests and polls wait for 
+    /// the throttle, so sessions sharing it are spaced out globally rather than only by each 
+    /// session's poll interval.
+    pub fn throttle(mut self, throttle: LoginThrottle) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+    
     pub fn build(self) -> Result<LoginSession<T>, LoginSessionError> {
+        let handler = match self.source {
+            HandlerSource::Transport(transport) => Arc::new(create_handler(
+                transport,
+                self.client.clone(),
+                self.platform_type,
+                self.user_agent,
+                self.language.unwrap_or_default(),
+            )?),
+            HandlerSource::Shared(handler) => {
+                if self.platform_type != handler.platform_type() {
+                    return Err(LoginSessionError::SharedHandlerOption("platform_type"));
+                }
+                
+                if self.user_agent.is_some() {
+                    return Err(LoginSessionError::SharedHandlerOption("user_agent"));
+                }
+                
+                if self.language.is_some() {
+                    return Err(LoginSessionError::SharedHandlerOption("language"));
+                }
+                
+                handler
+            },
+        };
         let session = LoginSession::new(LoginSessionOptions {
-            transport: self.transport,
+            platform_type: handler.platform_type(),
+            handler,
             client: self.client,
-            platform_type: self.platform_type,
-            user_agent: self.user_agent,
             machine_id: self.machine_id,
+            retry_invalid_password: self.retry_invalid_password,
+            retry_skewed_totp_code: self.retry_skewed_totp_code,
+            auto_mint_access_token: self.auto_mint_access_token,
+            allow_platform_mismatch: self.allow_platform_mismatch,
+            clock: self.clock,
+            throttle: self.throttle,
         })?;
         
         Ok(session)
@@ -1,6 +1,7 @@
-use steam_session_proto::steammessages_auth_steamclient::EAuthSessionGuardType;
+use steam_session_proto::steammessages_auth_steamclient::{EAuthSessionGuardType, EAuthTokenPlatformType};
 
-use crate::enums::EResult;
+use crate::enums::{EResult, WebsiteId, CookieDomain};
+use std::collections::HashMap;
 
 #[derive(Debug, thiserror::Error)]
 pub enum LoginSessionError {
@@ -14,6 +15,10 @@ pub enum LoginSessionError {
     LoginCannotUseMethodWithScheme,
     #[error("No Steam Guard code is needed for this login attempt")]
     LoginAttemptSteamGuardNotRequired,
+    #[error("A Steam Guard code was already accepted for this login attempt")]
+    GuardAlreadyProvided,
+    #[error("Steam Guard error: {}", .0)]
+    SteamGuard(#[from] crate::steam_guard::Error),
     #[error("Decode error: {}", .0)]
     Decode(#[from] crate::helpers::DecodeError),
     #[error("The provided token is a refresh token, not an access token")]
@@ -24,8 +29,10 @@ pub enum LoginSessionError {
     TokenIsForDifferentAccount,
     #[error("This token belongs to a different account from the set token")]
     TokenBelongsToOtherAccount,
+    /// An error from the authentication client. Errors carrying one of the [`EResult`] values 
+    /// covered by the variants below are converted into those variants instead.
     #[error("Authentication client error: {}", .0)]
-    AuthenticationClient(#[from] crate::authentication_client::Error),
+    AuthenticationClient(crate::authentication_client::Error),
     #[error("{}", .0)]
     InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
     #[error("A refresh token is required to get web cookies")]
@@ -34,14 +41,264 @@ pub enum LoginSessionError {
     NoAccessToken,
     #[error("Unknown auth session guard type: {:?}", .0)]
     UnknownGuardType(EAuthSessionGuardType),
-    #[error("Token platform type is different from the platform type of this LoginSession instance (required audience \"{}\"", .0)]
-    TokenPlatformDifferent(String),
+    /// The platform type isn't one that can log in, e.g. it was left as 
+    /// [`EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown`].
+    #[error("A supported platform type is required, got {:?}; use SteamClient, WebBrowser or MobileApp", .0)]
+    PlatformTypeRequired(EAuthTokenPlatformType),
+    #[error("Token audience {:?} doesn't match the platform type {:?} of this LoginSession instance", .audience, .platform_type)]
+    PlatformTokenMismatch {
+        /// The platform type of the session.
+        platform_type: EAuthTokenPlatformType,
+        /// The audience of the token.
+        audience: Vec<String>,
+    },
+    #[error("The refresh token does not allow the requested audience \"{}\"", .0)]
+    AudienceNotAllowed(String),
+    #[error("Cookies can't be obtained for website \"{}\"", .0)]
+    UnsupportedWebsiteId(WebsiteId),
+    /// The machine ID isn't a well-formed machine ID; see 
+    /// [`is_valid_machine_id`](crate::authentication_client::is_valid_machine_id).
+    #[error("Machine ID is malformed; expected a MessageObject with BB3, FF2 and 3B3 hashes")]
+    InvalidMachineId,
+    /// The device friendly name is empty or longer than 
+    /// [`MAX_DEVICE_FRIENDLY_NAME_LENGTH`](crate::authentication_client::MAX_DEVICE_FRIENDLY_NAME_LENGTH) 
+    /// characters.
+    #[error("Device friendly name must be between 1 and {} characters, got {}", crate::authentication_client::MAX_DEVICE_FRIENDLY_NAME_LENGTH, .0)]
+    InvalidDeviceFriendlyName(usize),
+    /// An option was set on a [`LoginSessionBuilder`](super::LoginSessionBuilder) sharing a 
+    /// handler, which takes the option from the handler instead.
+    #[error("\"{}\" can't be set when sharing a handler, as it's taken from the handler", .0)]
+    SharedHandlerOption(&'static str),
     #[error("Malformed response")]
     MalformedResponse,
-    #[error("Received EResult other than OK: {:?}", .0)]
+    #[error("Steam returned EResult {}", .0)]
     EResultNotOK(EResult),
+    /// The account name or password is incorrect.
+    #[error("Account name or password is incorrect (EResult {})", .0)]
+    InvalidCredentials(EResult),
+    /// The password was rejected while the local clock differs from Steam's, which can cause 
+    /// the RSA timestamp sent with the encrypted password to be rejected.
+    #[error("Account name or password was rejected (EResult {}); your system clock may be off by {} seconds", .eresult, .offset.abs())]
+    ClockSkew {
+        /// The result the password was rejected with.
+        eresult: EResult,
+        /// The number of seconds the local clock is behind Steam's servers.
+        offset: i64,
+    },
+    /// A Steam Guard code is required to log in.
+    #[error("A Steam Guard code is required (EResult {})", .0)]
+    GuardRequired(EResult),
+    /// The account's email address must be verified before it can log in. This needs action on 
+    /// the account itself, e.g. following the link in the verification email Steam sent.
+    #[error("The account's email address must be verified before logging in (EResult {}){}", .eresult, .message.as_ref().map(|message| format!(": {message}")).unwrap_or_default())]
+    EmailVerificationRequired {
+        /// The result the login was denied with.
+        eresult: EResult,
+        /// The error message Steam sent along with the result, if any.
+        message: Option<String>,
+        /// The masked email address of the account, e.g. `"j***@example.com"`, if Steam 
+        /// included it in the message.
+        email: Option<String>,
+    },
+    /// The Steam Guard code is incorrect.
+    #[error("Steam Guard code is incorrect (EResult {})", .0)]
+    GuardCodeInvalid(EResult),
+    /// Too many requests or login attempts were made recently.
+    #[error("Too many requests, try again later (EResult {})", .0)]
+    RateLimited(EResult),
+    /// The login session has expired.
+    #[error("Login session has expired (EResult {})", .0)]
+    Expired(EResult),
+    /// The account is disabled, locked or banned.
+    #[error("Account is disabled (EResult {})", .0)]
+    AccountDisabled(EResult),
+    #[error("Request was unexpectedly redirected to {}", .0)]
+    UnexpectedRedirect(String),
+    #[error("Operation was cancelled")]
+    Cancelled,
     #[error("No cookies were returned in response")]
     NoCookiesInResponse,
+    /// Cookies were obtained for some of the requested domains but not others.
+    #[error("{}", .0)]
+    PartialCookies(PartialCookieError),
     #[error("Receiver error: {}", .0)]
     RecvError(#[from] tokio::sync::oneshot::error::RecvError),
-}
\ No newline at end of file
+}
+
+/// Why the transfer of the login to a domain failed when getting web cookies.
+#[derive(Debug, thiserror::Error)]
+pub enum CookieTransferError {
+    #[error("{}", .0)]
+    Reqwest(#[from] reqwest::Error),
+    /// The transfer was redirected to another host, so its cookies would be for the wrong 
+    /// domain.
+    #[error("Transfer was unexpectedly redirected to {}", .0)]
+    UnexpectedRedirect(String),
+    /// The response didn't set a `steamLoginSecure` cookie.
+    #[error("No login cookie was returned in response")]
+    NoCookiesInResponse,
+}
+
+/// Returned when the transfers to some of the requested domains failed while others succeeded. 
+/// Carries the cookies that were obtained, so a caller which only needs some domains can still 
+/// use them.
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to get cookies for {} of {} domains", .failures.len(), .cookies.len() + .failures.len())]
+pub struct PartialCookieError {
+    /// The cookies for each domain whose transfer succeeded, including the `sessionid` cookie.
+    pub cookies: HashMap<CookieDomain, Vec<String>>,
+    /// The error for each domain whose transfer failed.
+    pub failures: HashMap<CookieDomain, CookieTransferError>,
+}
+
+impl LoginSessionError {
+    /// Gets the [`EResult`] carried by this error, if any.
+    pub fn eresult(&self) -> Option<EResult> {
+        match self {
+            Self::EResultNotOK(eresult) |
+            Self::InvalidCredentials(eresult) |
+            Self::GuardRequired(eresult) |
+            Self::GuardCodeInvalid(eresult) |
+            Self::RateLimited(eresult) |
+            Self::Expired(eresult) |
+            Self::AccountDisabled(eresult) |
+            Self::ClockSkew { eresult, .. } |
+            Self::EmailVerificationRequired { eresult, .. } => Some(*eresult),
+            Self::AuthenticationClient(error) => error.eresult(),
+            _ => None,
+        }
+    }
+    
+    /// Gets the high-level error for `eresult`, if it has one.
+    fn for_eresult(eresult: EResult) -> Option<Self> {
+        let error = match eresult {
+            EResult::InvalidPassword |
+            EResult::AccountNotFound |
+            EResult::InvalidName => Self::InvalidCredentials(eresult),
+            EResult::AccountLogonDeniedNeedTwoFactorCode => Self::GuardRequired(eresult),
+            EResult::AccountLogonDenied |
+            EResult::AccountLogonDeniedVerifiedEmailRequired => Self::EmailVerificationRequired {
+                eresult,
+                message: None,
+                email: None,
+            },
+            EResult::InvalidLoginAuthCode |
+            EResult::TwoFactorCodeMismatch => Self::GuardCodeInvalid(eresult),
+            EResult::RateLimitExceeded |
+            EResult::AccountLoginDeniedThrottle |
+            EResult::LimitExceeded => Self::RateLimited(eresult),
+            EResult::Expired => Self::Expired(eresult),
+            EResult::AccountDisabled |
+            EResult::AccountLocked |
+            EResult::Banned |
+            EResult::Suspended => Self::AccountDisabled(eresult),
+            _ => return None,
+        };
+        
+        Some(error)
+    }
+}
+
+impl From<EResult> for LoginSessionError {
+    fn from(eresult: EResult) -> Self {
+        Self::for_eresult(eresult).unwrap_or(Self::EResultNotOK(eresult))
+    }
+}
+
+impl From<crate::authentication_client::Error> for LoginSessionError {
+    fn from(error: crate::authentication_client::Error) -> Self {
+        match error.eresult().and_then(Self::for_eresult) {
+            Some(Self::EmailVerificationRequired { eresult, .. }) => {
+                let message = error.extended_error_message().map(String::from);
+                
+                Self::EmailVerificationRequired {
+                    eresult,
+                    email: message.as_deref().and_then(masked_email),
+                    message,
+                }
+            },
+            Some(high_level_error) => high_level_error,
+            None => Self::AuthenticationClient(error),
+        }
+    }
+}
+
+/// Finds the masked email address in `message`, e.g. `"j***@example.com"`.
+fn masked_email(message: &str) -> Option<String> {
+    message
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !(c.is_alphanumeric() || c == '*')))
+        .find(|word| {
+            word.split_once('@')
+                .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.'))
+        })
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authentication_client::Error as AuthenticationClientError;
+    use crate::transports::web_api::Error as WebApiError;
+    
+    #[test]
+    fn maps_eresults_to_high_level_errors() {
+        let error = LoginSessionError::from(AuthenticationClientError::WebAPI(WebApiError::EResultNotOK(EResult::TwoFactorCodeMismatch)));
+        
+        assert!(matches!(error, LoginSessionError::GuardCodeInvalid(EResult::TwoFactorCodeMismatch)));
+        assert_eq!(error.eresult(), Some(EResult::TwoFactorCodeMismatch));
+        assert!(matches!(LoginSessionError::from(EResult::RateLimitExceeded), LoginSessionError::RateLimited(_)));
+        assert!(matches!(
+            LoginSessionError::from(AuthenticationClientError::EResultNotOK(EResult::Busy)),
+            LoginSessionError::AuthenticationClient(_),
+        ));
+    }
+    
+    #[test]
+    fn maps_email_verification_required() {
+        let error = LoginSessionError::from(AuthenticationClientError::WebAPI(WebApiError::EResultWithMessage {
+            eresult: EResult::AccountLogonDeniedVerifiedEmailRequired,
+            message: String::from("Verify the email address j***@example.com to log in."),
+        }));
+        
+        assert!(matches!(
+            &error,
+            LoginSessionError::EmailVerificationRequired {
+                eresult: EResult::AccountLogonDeniedVerifiedEmailRequired,
+                message: Some(_),
+                email: Some(email),
+            } if email == "j***@example.com",
+        ));
+        assert!(matches!(
+            LoginSessionError::from(AuthenticationClientError::EResultNotOK(EResult::AccountLogonDenied)),
+            LoginSessionError::EmailVerificationRequired { message: None, email: None, .. },
+        ));
+    }
+    
+    #[test]
+    fn partial_cookies_carry_successes() {
+        let error = LoginSessionError::PartialCookies(PartialCookieError {
+            cookies: HashMap::from([(CookieDomain::Community, vec![String::from("steamLoginSecure=1")])]),
+            failures: HashMap::from([(CookieDomain::Help, CookieTransferError::NoCookiesInResponse)]),
+        });
+        
+        assert_eq!(error.to_string(), "Failed to get cookies for 1 of 2 domains");
+        
+        let LoginSessionError::PartialCookies(partial) = error else {
+            unreachable!();
+        };
+        
+        assert!(partial.cookies.contains_key(&CookieDomain::Community));
+    }
+    
+    #[test]
+    fn clock_skew_hints_at_offset() {
+        let error = LoginSessionError::ClockSkew {
+            eresult: EResult::InvalidPassword,
+            offset: -120,
+        };
+        
+        assert_eq!(error.eresult(), Some(EResult::InvalidPassword));
+        assert!(error.to_string().contains("your system clock may be off by 120 seconds"));
+    }
+}
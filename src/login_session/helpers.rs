@@ -1,24 +1,31 @@
-use super::LoginSessionError;
-use crate::authentication_client::{AuthenticationClient, AuthenticationClientConstructorOptions};
+use super::{LoginSessionError, LoginThrottle};
+use std::sync::Arc;
+use crate::authentication_client::{AuthenticationClient, AuthenticationClientConstructorOptions, MachineId};
 use crate::helpers::DEFAULT_USER_AGENT;
+use crate::clock::Clock;
 use crate::transports::Transport;
-use crate::enums::EAuthTokenPlatformType;
+use crate::enums::{EAuthTokenPlatformType, ELanguage};
 
 #[derive(Debug)]
 pub struct LoginSessionOptions<T> {
-    pub transport: T,
+    pub handler: Arc<AuthenticationClient<T>>,
     pub client: reqwest::Client,
     pub platform_type: EAuthTokenPlatformType,
-    pub user_agent: Option<&'static str>,
-    pub machine_id: Option<Vec<u8>>,
+    pub machine_id: MachineId,
+    pub retry_invalid_password: bool,
+    pub retry_skewed_totp_code: bool,
+    pub auto_mint_access_token: bool,
+    pub allow_platform_mismatch: bool,
+    pub clock: Arc<dyn Clock>,
+    pub throttle: Option<LoginThrottle>,
 }
 
 pub fn create_handler<T>(
     transport: T,
     client: reqwest::Client,
     platform_type: EAuthTokenPlatformType,
-    machine_id: Option<Vec<u8>>,
     user_agent: Option<&'static str>,
+    language: ELanguage,
 ) -> Result<AuthenticationClient<T>, LoginSessionError>
 where
     T: Transport,
@@ -27,7 +34,40 @@ where
         platform_type,
         transport,
         client,
-        machine_id,
         user_agent: user_agent.unwrap_or(DEFAULT_USER_AGENT),
+        language,
     }))
+}
+
+/// Checks that `platform_type` is one that can log in.
+pub fn verify_platform_type(platform_type: EAuthTokenPlatformType) -> Result<(), LoginSessionError> {
+    match platform_type {
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient |
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser |
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp => Ok(()),
+        platform_type => Err(LoginSessionError::PlatformTypeRequired(platform_type)),
+    }
+}
+
+/// Gets the token audience for `platform_type`.
+pub fn platform_audience(platform_type: EAuthTokenPlatformType) -> &'static str {
+    match platform_type {
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient => "client",
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp => "mobile",
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser => "web",
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown => "unknown",
+    }
+}
+
+/// Gets the platform type a token with the audience `audience` was issued for. Tokens for the 
+/// Steam client and mobile app also have the `web` audience, so their own audiences take 
+/// precedence.
+pub fn audience_platform(audience: &[String]) -> Option<EAuthTokenPlatformType> {
+    [
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient,
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp,
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser,
+    ]
+        .into_iter()
+        .find(|platform_type| audience.iter().any(|aud| aud == platform_audience(*platform_type)))
 }
\ No newline at end of file
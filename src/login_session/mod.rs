@@ -1,27 +1,68 @@
+//! Logging in to Steam using a [`LoginSession`].
+//! 
+//! # Platform types
+//! 
+//! The platform type given to [`LoginSessionBuilder::new`] determines which client the session 
+//! imitates and the audience of the tokens it receives. Behavior differs per platform type:
+//! 
+//! - **SteamClient** - Imitates the Steam client. Requests use the client's embedded browser user 
+//!   agent with a `steamloopback.host` origin, device details use a spoofed Windows hostname and 
+//!   the machine ID is sent when logging in. Tokens have the `client` audience. Web cookies are 
+//!   created from the access token rather than from `finalizelogin`.
+//! - **WebBrowser** - Imitates logging in on steamcommunity.com. Requests use the configured user 
+//!   agent (see [`LoginSessionBuilder::user_agent`]) with a `steamcommunity.com` origin, and the 
+//!   user agent is used as the device name. Tokens have the `web` audience. Web cookies are 
+//!   obtained through `finalizelogin` and the transfer URLs it returns.
+//! - **MobileApp** - Imitates the Steam mobile app. Requests use the app's `okhttp` user agent and 
+//!   `mobileClient` cookie and device details describe an Android phone. Tokens have the 
+//!   `mobile` audience, which is needed for mobile confirmations. Web cookies are created from 
+//!   the access token rather than from `finalizelogin`. Configuring a user agent has no effect.
+//! 
+//! The headers are sent with each request by the Web API transport and in the handshake by the 
+//! websocket transport when connecting with [`WebSocketCMTransport::handshake_headers`].
+
 mod error;
 mod builder;
 mod helpers;
+mod state;
+mod throttle;
 
+use std::fmt;
 use std::str::FromStr;
+use std::collections::HashMap;
+use std::sync::Arc;
 
-pub use error::LoginSessionError;
+pub use error::{LoginSessionError, PartialCookieError, CookieTransferError};
 pub use builder::LoginSessionBuilder;
+pub use state::LoginSessionState;
+pub use throttle::LoginThrottle;
 
-use helpers::LoginSessionOptions;
+use helpers::{LoginSessionOptions, platform_audience, audience_platform, verify_platform_type};
 
 use crate::enums::EResult;
-use crate::response::{StartSessionResponseValidAction, StartSessionResponse};
+use crate::response::{StartSessionResponseValidAction, StartSessionResponse, PollResult, PollFailure, WebCookie, SteamGuardMachineToken};
 use crate::request::{
     StartLoginSessionWithCredentialsDetails,
     StartAuthSessionWithCredentialsRequest,
+    GenerateAccessTokenOptions,
+    WebCookiesOptions,
+    RsaKey,
 };
 use crate::serializers::from_number_or_string_option;
 use crate::transports::web_api::WebApiTransport;
 use crate::transports::{Transport, WebSocketCMTransport};
 use crate::types::DateTime;
-use crate::authentication_client::{AuthenticationClient, Error as AuthenticationClientError};
-use crate::helpers::{JwtPayload, generate_sessionid, create_api_headers, value_to_multipart};
-use crate::enums::{ESessionPersistence, EAuthTokenPlatformType, EAuthSessionGuardType};
+use crate::clock::Clock;
+use crate::authentication_client::{
+    AuthenticationClient,
+    MachineId,
+    DeviceDetails,
+    Error as AuthenticationClientError,
+    MAX_DEVICE_FRIENDLY_NAME_LENGTH,
+    is_valid_machine_id,
+};
+use crate::helpers::{JwtPayload, Redacted, generate_sessionid, create_api_headers, value_to_multipart};
+use crate::enums::{ESessionPersistence, EAuthTokenPlatformType, EAuthSessionGuardType, WebsiteId, CookieDomain};
 
 use cookie::Cookie;
 use futures::StreamExt;
@@ -29,16 +70,30 @@ use futures::stream::FuturesOrdered;
 use reqwest::header::SET_COOKIE;
 use serde::Deserialize;
 use serde_json::Value;
-use chrono::{Utc, Duration};
+use chrono::Duration;
 use http::HeaderValue;
 use reqwest::{Client, RequestBuilder};
 use steam_session_proto::steammessages_auth_steamclient::CAuthentication_BeginAuthSessionViaCredentials_Response;
 use steamid_ng::SteamID;
 use url::form_urlencoded;
+use tokio_util::sync::CancellationToken;
 
 const LOGIN_TIMEOUT_SECONDS: i64 = 30;
+/// How long a pre-fetched RSA key is considered fresh.
+const RSA_KEY_MAX_AGE_SECONDS: i64 = 60;
+/// How far the local clock can be off from Steam's before a rejected password is reported as 
+/// [`LoginSessionError::ClockSkew`].
+const CLOCK_SKEW_THRESHOLD_SECONDS: i64 = 60;
+/// The length of the time window of a TOTP code.
+const TOTP_WINDOW_SECONDS: i64 = 30;
+const FINALIZE_LOGIN_URL: &str = "https://login.steampowered.com/jwt/finalizelogin";
 
-#[derive(Debug)]
+/// A session for logging in to Steam.
+/// 
+/// Every [`Transport`] is `Send + Sync`, so a [`LoginSession`] and the futures returned by its 
+/// methods can be held across `.await` points and shared using `Arc`, e.g. in web handlers.
+/// 
+/// The `Debug` output redacts tokens and Steam Guard codes so sessions can be logged safely.
 pub struct LoginSession<T> {
     login_timeout: Duration,
     account_name: Option<String>,
@@ -47,15 +102,34 @@ pub struct LoginSession<T> {
     access_token_set_at: Option<DateTime>,
     platform_type: EAuthTokenPlatformType,
     client: Client,
-    handler: AuthenticationClient<T>,
+    handler: Arc<AuthenticationClient<T>>,
+    machine_id: MachineId,
+    device_details: Option<DeviceDetails>,
     steam_guard_code: Option<String>,
+    steam_guard_code_accepted: bool,
+    shared_secret: Option<String>,
+    time_offset: i64,
     steam_guard_machine_token: Option<Vec<u8>>,
+    new_steam_guard_machine_token: Option<SteamGuardMachineToken>,
     start_session_response: Option<CAuthentication_BeginAuthSessionViaCredentials_Response>,
+    session_started_at: Option<DateTime>,
+    last_poll_result: Option<PollResult>,
+    last_poll_failure: Option<PollFailure>,
+    last_polled_at: Option<DateTime>,
+    retry_invalid_password: bool,
+    retry_skewed_totp_code: bool,
+    auto_mint_access_token: bool,
+    allow_platform_mismatch: bool,
+    state: LoginSessionState,
+    clock: Arc<dyn Clock>,
+    last_rsa_key: Option<RsaKey>,
+    throttle: Option<LoginThrottle>,
 }
 
 pub async fn connect_ws() -> Result<LoginSession<WebSocketCMTransport>, LoginSessionError> {
     let platform_type = EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp;
-    let transport = WebSocketCMTransport::connect().await
+    let headers = WebSocketCMTransport::handshake_headers(platform_type);
+    let transport = WebSocketCMTransport::connect_with_headers(headers).await
         .map_err(AuthenticationClientError::WebSocketCM)?;
     
     LoginSessionBuilder::new(transport, platform_type)
@@ -70,6 +144,72 @@ pub async fn connect_webapi() -> Result<LoginSession<WebApiTransport>, LoginSess
         .build()
 }
 
+/// Refreshes the access tokens of `sessions` concurrently, with at most `concurrency` requests 
+/// in flight at once. Returns a result for each session in the same order, so one failure 
+/// doesn't abort the rest of the batch.
+#[must_use = "each result tells whether the session was refreshed"]
+pub async fn refresh_access_tokens<T>(
+    sessions: &mut [LoginSession<T>],
+    concurrency: usize,
+) -> Vec<Result<(), LoginSessionError>>
+where
+    T: Transport,
+{
+    futures::stream::iter(sessions.iter_mut().map(|session| session.refresh_access_token()))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// The cookies obtained by `fetch_web_cookies`.
+struct FetchedWebCookies {
+    sessionid: String,
+    /// The cookies from each successful transfer other than `sessionid`. The domain is `None` 
+    /// for cookies created from the access token.
+    transfers: Vec<(Option<CookieDomain>, Vec<String>)>,
+    /// The error for each failed transfer.
+    failures: Vec<(Option<CookieDomain>, CookieTransferError)>,
+}
+
+impl<T> fmt::Debug for LoginSession<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoginSession")
+            .field("login_timeout", &self.login_timeout)
+            .field("account_name", &self.account_name)
+            .field("refresh_token", &self.refresh_token.as_ref().map(Redacted))
+            .field("access_token", &self.access_token.as_ref().map(Redacted))
+            .field("access_token_set_at", &self.access_token_set_at)
+            .field("platform_type", &self.platform_type)
+            .field("client", &self.client)
+            .field("handler", &self.handler)
+            .field("machine_id", &self.machine_id)
+            .field("device_details", &self.device_details)
+            .field("steam_guard_code", &self.steam_guard_code.as_ref().map(Redacted))
+            .field("steam_guard_code_accepted", &self.steam_guard_code_accepted)
+            .field("shared_secret", &self.shared_secret.as_ref().map(Redacted))
+            .field("time_offset", &self.time_offset)
+            .field("steam_guard_machine_token", &self.steam_guard_machine_token.as_ref().map(Redacted))
+            .field("new_steam_guard_machine_token", &self.new_steam_guard_machine_token)
+            .field("start_session_response", &self.start_session_response.as_ref().map(Redacted))
+            .field("session_started_at", &self.session_started_at)
+            .field("last_poll_result", &self.last_poll_result)
+            .field("last_poll_failure", &self.last_poll_failure)
+            .field("last_polled_at", &self.last_polled_at)
+            .field("retry_invalid_password", &self.retry_invalid_password)
+            .field("retry_skewed_totp_code", &self.retry_skewed_totp_code)
+            .field("auto_mint_access_token", &self.auto_mint_access_token)
+            .field("allow_platform_mismatch", &self.allow_platform_mismatch)
+            .field("state", &self.state)
+            .field("clock", &self.clock)
+            .field("last_rsa_key", &self.last_rsa_key)
+            .field("throttle", &self.throttle)
+            .finish()
+    }
+}
+
 impl<T> LoginSession<T>
 where
     T: Transport,
@@ -87,13 +227,14 @@ where
         options: LoginSessionOptions<T>,
     ) -> Result<Self, LoginSessionError> {
         let platform_type = options.platform_type;
-        let handler = helpers::create_handler(
-            options.transport,
-            options.client.clone(),
-            platform_type,
-            options.machine_id,
-            options.user_agent
-        )?;
+        let handler = options.handler;
+        
+        if !options.machine_id.is_valid() {
+            return Err(LoginSessionError::InvalidMachineId);
+        }
+        
+        // resolved once so that the device is listed under the same name for every login attempt
+        let device_details = handler.default_device_details();
         
         Ok(Self {
             login_timeout: Duration::try_seconds(LOGIN_TIMEOUT_SECONDS).unwrap(),
@@ -104,9 +245,27 @@ where
             platform_type,
             client: options.client,
             handler,
+            machine_id: options.machine_id,
+            device_details,
             steam_guard_code: None,
+            steam_guard_code_accepted: false,
+            shared_secret: None,
+            time_offset: 0,
             steam_guard_machine_token: None,
+            new_steam_guard_machine_token: None,
             start_session_response: None,
+            session_started_at: None,
+            last_poll_result: None,
+            last_poll_failure: None,
+            last_polled_at: None,
+            retry_invalid_password: options.retry_invalid_password,
+            retry_skewed_totp_code: options.retry_skewed_totp_code,
+            auto_mint_access_token: options.auto_mint_access_token,
+            allow_platform_mismatch: options.allow_platform_mismatch,
+            state: LoginSessionState::Idle,
+            clock: options.clock,
+            last_rsa_key: None,
+            throttle: options.throttle,
         })
     }
     
@@ -125,6 +284,29 @@ where
     /// 
     /// On success returns a [`StartSessionResponse`]. Check `allowed_confirmations` for how to 
     /// respond to the response.
+    /// 
+    /// Steam can signal that a code from your mobile authenticator is required in two ways:
+    /// 
+    /// - The modern flow lists [`EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceCode`] in 
+    ///   the session's `allowed_confirmations`. The session is started and the code can be supplied 
+    ///   using `submit_steam_guard_code`.
+    /// - Older flows reject the request outright with 
+    ///   [`EResult::AccountLogonDeniedNeedTwoFactorCode`]. No session is started in this case, so 
+    ///   the code must be supplied to the `steam_guard_code` property when calling 
+    ///   `start_with_credentials` again.
+    /// 
+    /// Both are returned as [`StartSessionResponse::ActionRequired`] containing a 
+    /// [`EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceCode`] action, so 
+    /// [`StartSessionResponse::requires_device_code`] is true for either.
+    /// 
+    /// An expired RSA key is reported with [`EResult::InvalidPassword`], the same way as a wrong 
+    /// password. Enable [`LoginSessionBuilder::retry_invalid_password`] to retry once with a 
+    /// freshly fetched RSA key when this happens.
+    /// 
+    /// If `rsa_key` is supplied and was fetched within the last minute, it is used instead of 
+    /// fetching a new key, saving a round-trip. The key used for the last attempt is available 
+    /// from `last_rsa_key`.
+    #[must_use = "the response tells whether a Steam Guard code or confirmation is needed"]
     pub async fn start_with_credentials(
         &mut self,
         details: StartLoginSessionWithCredentialsDetails,
@@ -133,38 +315,231 @@ where
             account_name,
             password,
             steam_guard_code,
+            shared_secret,
             steam_guard_machine_token,
             platform_type,
             persistence,
+            rsa_key,
+            website_id,
+            machine_id,
             ..
         } = details;
         
+        // checked up front as Steam rejects an unknown platform type with a vague error
+        verify_platform_type(self.platform_type)?;
+        verify_platform_type(platform_type)?;
+        
+        let machine_id = match machine_id {
+            Some(machine_id) if !is_valid_machine_id(&machine_id) => {
+                return Err(LoginSessionError::InvalidMachineId);
+            },
+            Some(machine_id) => Some(machine_id),
+            None => self.machine_id.resolve(&account_name),
+        };
+        
         self.steam_guard_code = steam_guard_code;
+        self.steam_guard_code_accepted = false;
+        self.shared_secret = shared_secret;
         
-        let encrypted_password = self.handler.encrypt_password(
-            account_name.clone(),
-            password.clone(),
-        ).await?;
-        let start_session_response = self.handler.start_session_with_credentials(StartAuthSessionWithCredentialsRequest {
-            account_name,
-            encrypted_password: encrypted_password.encrypted_password,
-            encryption_timestamp: encrypted_password.key_timestamp,
-            remember_login: true,
-            platform_type,
-            persistence: persistence.unwrap_or(ESessionPersistence::k_ESessionPersistence_Persistent),
-            steam_guard_machine_token: steam_guard_machine_token.clone(),
-        }).await?;
+        let persistence = persistence.unwrap_or(ESessionPersistence::k_ESessionPersistence_Persistent);
+        let mut retry_invalid_password = self.retry_invalid_password;
+        let mut rsa_key = rsa_key.filter(|rsa_key| self.rsa_key_is_fresh(rsa_key));
+        let result = loop {
+            // A retry always fetches a new RSA key.
+            let rsa_key = match rsa_key.take() {
+                Some(rsa_key) => rsa_key,
+                None => self.fetch_rsa_key(account_name.clone()).await?,
+            };
+            let encrypted_password = AuthenticationClient::<T>::encrypt_password_with_key(
+                &password,
+                &rsa_key,
+            )?;
+            
+            self.last_rsa_key = Some(rsa_key);
+            self.wait_for_throttle().await;
+            
+            let result = self.handler.start_session_with_credentials(StartAuthSessionWithCredentialsRequest {
+                account_name: account_name.clone(),
+                encrypted_password: encrypted_password.encrypted_password,
+                encryption_timestamp: encrypted_password.key_timestamp,
+                remember_login: persistence == ESessionPersistence::k_ESessionPersistence_Persistent,
+                platform_type,
+                persistence,
+                steam_guard_machine_token: steam_guard_machine_token.clone(),
+                website_id,
+                machine_id: machine_id.clone(),
+                device_details: self.device_details.clone(),
+            }).await;
+            
+            match result {
+                // The key may have expired before the password was submitted.
+                Err(error) if retry_invalid_password && error.eresult() == Some(EResult::InvalidPassword) => {
+                    log::debug!("Password was rejected, retrying with a fresh RSA key");
+                    retry_invalid_password = false;
+                },
+                result => break result,
+            }
+        };
+        let start_session_response = match result {
+            Ok(start_session_response) => start_session_response,
+            Err(error) if error.eresult() == Some(EResult::AccountLogonDeniedNeedTwoFactorCode) => {
+                // Legacy flow - Steam rejected the attempt rather than listing the device code in 
+                // allowed_confirmations. No session was started.
+                self.start_session_response = None;
+                self.session_started_at = None;
+                
+                let response = StartSessionResponse::ActionRequired(vec![
+                    StartSessionResponseValidAction {
+                        r#type: EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceCode,
+                        detail: None,
+                    },
+                ]);
+                
+                self.state = LoginSessionState::from(&response);
+                return Ok(response);
+            },
+            Err(error) if error.eresult() == Some(EResult::InvalidPassword) => {
+                return Err(self.check_clock_skew(error.into()).await);
+            },
+            Err(error) => return Err(error.into()),
+        };
         
         self.start_session_response = Some(start_session_response);
+        self.session_started_at = Some(self.clock.now());
+        self.state = LoginSessionState::Pending;
         
         let response = self.process_start_session_response().await?;
         
+        // polling may have already authenticated the session
+        if !self.state.is_authenticated() {
+            self.state = LoginSessionState::from(&response);
+        }
+        
         Ok(response)
     }
     
+    /// Checks whether the local clock is off from Steam's after the password was rejected with 
+    /// `error`. If it is, [`LoginSessionError::ClockSkew`] is returned instead, since the 
+    /// password may be correct. Otherwise, or if the time can't be fetched, `error` is returned.
+    async fn check_clock_skew(&self, error: LoginSessionError) -> LoginSessionError {
+        let offset = crate::steam_guard::fetch_server_time_offset_with_clock(
+            &self.client,
+            self.clock.as_ref(),
+        ).await;
+        
+        match (error.eresult(), offset) {
+            (Some(eresult), Ok(offset)) if offset.abs() >= CLOCK_SKEW_THRESHOLD_SECONDS => {
+                log::debug!("Password was rejected with the local clock {offset} seconds behind Steam");
+                LoginSessionError::ClockSkew {
+                    eresult,
+                    offset,
+                }
+            },
+            _ => error,
+        }
+    }
+    
+    /// Gets the RSA key used to encrypt the password in the last call to 
+    /// `start_with_credentials`. This can be supplied to a later attempt to skip fetching a new 
+    /// key.
+    pub fn last_rsa_key(&self) -> Option<&RsaKey> {
+        self.last_rsa_key.as_ref()
+    }
+    
+    /// Fetches the RSA key for `account_name`.
+    async fn fetch_rsa_key(&self, account_name: String) -> Result<RsaKey, LoginSessionError> {
+        let response = self.handler.get_rsa_key(account_name).await?;
+        
+        Ok(RsaKey {
+            modulus: response.publickey_mod().into(),
+            exponent: response.publickey_exp().into(),
+            timestamp: response.timestamp(),
+            fetched_at: self.clock.now(),
+        })
+    }
+    
+    /// Checks if `rsa_key` was fetched recently enough to be used.
+    fn rsa_key_is_fresh(&self, rsa_key: &RsaKey) -> bool {
+        let age = self.clock.now() - rsa_key.fetched_at;
+        
+        age >= Duration::zero() && age < Duration::try_seconds(RSA_KEY_MAX_AGE_SECONDS).unwrap()
+    }
+    
+    /// Gets the handler used to send authentication requests. It can be shared with other 
+    /// sessions using [`LoginSessionBuilder::with_handler`].
+    pub fn handler(&self) -> &Arc<AuthenticationClient<T>> {
+        &self.handler
+    }
+    
+    /// Gets the current state of the session. See [`LoginSessionState`] for how each method 
+    /// advances it.
+    pub fn state(&self) -> &LoginSessionState {
+        &self.state
+    }
+    
+    /// Clears all per-account state so the session can be reused to log in to a different 
+    /// account. Tokens, the account name, Steam Guard details, the pending auth session and the 
+    /// last poll result are cleared and the state returns to [`LoginSessionState::Idle`].
+    /// 
+    /// The handler, client, platform type, machine ID setting, throttle, clock and time offset 
+    /// are kept. Polling borrows the session mutably, so no poll can be in flight while 
+    /// resetting; dropping a `poll` future abandons its request.
+    pub fn reset(&mut self) {
+        self.account_name = None;
+        self.refresh_token = None;
+        self.access_token = None;
+        self.access_token_set_at = None;
+        self.steam_guard_code = None;
+        self.steam_guard_code_accepted = false;
+        self.shared_secret = None;
+        self.steam_guard_machine_token = None;
+        self.new_steam_guard_machine_token = None;
+        self.start_session_response = None;
+        self.session_started_at = None;
+        self.last_poll_result = None;
+        self.last_poll_failure = None;
+        self.last_polled_at = None;
+        self.state = LoginSessionState::Idle;
+        self.last_rsa_key = None;
+    }
+    
+    /// Gets the time remaining until the pending auth session times out, e.g. for showing a 
+    /// countdown. This is the deadline `poll` gives up at. Returns `None` if no auth session is 
+    /// pending.
+    pub fn time_remaining(&self) -> Option<Duration> {
+        match self.state {
+            LoginSessionState::Idle |
+            LoginSessionState::Authenticated |
+            LoginSessionState::Expired => return None,
+            _ => {},
+        }
+        
+        let elapsed = self.clock.now() - self.session_started_at?;
+        
+        Some((self.login_timeout - elapsed).max(Duration::zero()))
+    }
+    
+    /// Gets the Steam Guard machine token Steam issued for this device after the login was 
+    /// verified, e.g. using an email code, bound to the account it was issued for. Once received 
+    /// it's also used as the session's machine token.
+    /// 
+    /// This is only returned once per device, so it must be persisted and supplied as 
+    /// `steam_guard_machine_token` on the next login to skip Steam Guard; see 
+    /// [`SteamGuardMachineToken`].
+    pub fn steam_guard_machine_token(&self) -> Option<&SteamGuardMachineToken> {
+        self.new_steam_guard_machine_token.as_ref()
+    }
+    
+    /// Checks if the session is authenticated i.e. it holds a refresh token.
+    pub fn is_authenticated(&self) -> bool {
+        self.state.is_authenticated()
+    }
+    
+    /// Gets the SteamID of the account. This is known as soon as the login is started, 
+    /// otherwise it's taken from the tokens once they're issued.
     pub fn steamid(&self) -> Option<SteamID> {
-        if let Some(start_session_response) = &self.start_session_response {
-            return Some(SteamID::from(start_session_response.steamid()));
+        if let Some(steamid) = self.started_steamid() {
+            return Some(SteamID::from(steamid));
         }
         
         let token = if let Some(access_token) = &self.access_token {
@@ -179,6 +554,13 @@ where
         Some(decoded.sub)
     }
     
+    /// Gets the SteamID Steam gave when the login was started, if it gave one.
+    fn started_steamid(&self) -> Option<u64> {
+        self.start_session_response.as_ref()
+            .map(|start_session_response| start_session_response.steamid())
+            .filter(|steamid| *steamid != 0)
+    }
+    
     /// Gets the account name.
     pub fn get_account_name(&self) -> Option<&String> {
         self.account_name.as_ref()
@@ -195,6 +577,16 @@ where
         self.access_token.as_ref()
     }
     
+    /// Gets the platform type the access token was issued for, decoded from its `aud` claim, 
+    /// e.g. to check it before using it for a platform-restricted operation. Steam may issue a 
+    /// token for a different platform than the session's in some cases. Returns `None` if no 
+    /// access token is set, or if it can't be decoded or has no platform audience.
+    pub fn access_token_platform(&self) -> Option<EAuthTokenPlatformType> {
+        let decoded = JwtPayload::from_str(self.access_token.as_ref()?).ok()?;
+        
+        audience_platform(&decoded.aud)
+    }
+    
     /// Sets the access token. Will return an error if:
     /// 
     /// - You set it to a token that isn't well-formed, or
@@ -226,8 +618,8 @@ where
             return Err(LoginSessionError::ExpectedAccessToken);
         }
         
-        if let Some(start_session_response) = &self.start_session_response {
-            if start_session_response.steamid() != u64::from(decoded.sub) {
+        if let Some(steamid) = self.started_steamid() {
+            if steamid != u64::from(decoded.sub) {
                 return Err(LoginSessionError::TokenIsForDifferentAccount);
             }
         }
@@ -242,7 +634,7 @@ where
         
         // Everything checks out
         self.access_token = Some(token);
-        self.access_token_set_at = Some(Utc::now());
+        self.access_token_set_at = Some(self.clock.now());
         
         Ok(())
     }
@@ -253,10 +645,22 @@ where
         self.access_token.as_ref()
     }
     
-    /// Sets the refresh token. Will return an error if:
+    /// Gets when the refresh token expires, decoded from its `exp` claim. This depends on the 
+    /// `persistence` requested when logging in. Returns `None` if no refresh token is set.
+    pub fn refresh_token_expires_at(&self) -> Option<DateTime> {
+        let decoded = JwtPayload::from_str(self.refresh_token.as_ref()?).ok()?;
+        
+        chrono::DateTime::from_timestamp(decoded.exp as i64, 0)
+    }
+    
+    /// Sets the refresh token, e.g. to restore a previously saved session. Will return an error 
+    /// if:
     ///
     /// - You set it to a token that isn't well-formed, or
     /// - You set it to an access token rather than a refresh token, or
+    /// - You set it to a token for a different platform type, which returns
+    ///   [`LoginSessionError::PlatformTokenMismatch`] unless allowed using
+    ///   [`LoginSessionBuilder::allow_platform_mismatch`], or
     /// - You have already called `start_with_credentials` and you set it to a token that doesn't 
     /// belong to the same account, or
     /// - You have already set an `access_token` and you set this to a token that doesn't belong 
@@ -273,19 +677,21 @@ where
             return Err(LoginSessionError::ExpectedRefreshToken);
         }
         
-        let required_audience = match self.platform_type {
-            EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient => "client",
-            EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp => "mobile",
-            EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser => "web",
-            EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown => "unknown",
-        };
+        let required_audience = platform_audience(self.platform_type);
         
         if !decoded.aud.iter().any(|a| a == required_audience) {
-            return Err(LoginSessionError::TokenPlatformDifferent(required_audience.into()));
+            if !self.allow_platform_mismatch {
+                return Err(LoginSessionError::PlatformTokenMismatch {
+                    platform_type: self.platform_type,
+                    audience: decoded.aud,
+                });
+            }
+            
+            log::warn!("Refresh token audience {:?} doesn't match platform type {:?}", decoded.aud, self.platform_type);
         }
         
-        if let Some(start_session_response) = &self.start_session_response {
-            if start_session_response.steamid() != u64::from(decoded.sub) {
+        if let Some(steamid) = self.started_steamid() {
+            if steamid != u64::from(decoded.sub) {
                 return Err(LoginSessionError::TokenIsForDifferentAccount);
             }
         }
@@ -300,6 +706,7 @@ where
         
         // Everything checks out
         self.refresh_token = Some(token);
+        self.state = LoginSessionState::Authenticated;
         
         Ok(())
     }
@@ -375,13 +782,14 @@ where
     }
     
     /// Attempts steam guard code.
+    #[must_use = "the result tells whether the code was accepted"]
     pub async fn attempt_steam_guard_code(&mut self) -> Result<bool, LoginSessionError> {
         if let Some(steam_guard_code) = &self.steam_guard_code {
             match self.submit_steam_guard_code(steam_guard_code.clone()).await {
                 Ok(_) => {
                     return Ok(true);
                 },
-                Err(LoginSessionError::AuthenticationClient(AuthenticationClientError::EResultNotOK(EResult::TwoFactorCodeMismatch))) => {
+                Err(LoginSessionError::GuardCodeInvalid(EResult::TwoFactorCodeMismatch)) => {
                     // nothing
                 },
                 Err(error) => {
@@ -422,23 +830,115 @@ where
         Ok(false)
     }
     
-    /// Attemps TOTP code authentication.
+    /// Attemps TOTP code authentication, generating a code from the `shared_secret` if one was 
+    /// supplied instead of a code. A rejected generated code is retried with the codes for the 
+    /// adjacent time windows, see [`LoginSessionBuilder::retry_skewed_totp_code`].
     async fn attempt_totp_code_auth(&mut self) -> Result<bool, LoginSessionError> {
-        self.attempt_steam_guard_code().await
+        if self.steam_guard_code.is_some() {
+            return self.attempt_steam_guard_code().await;
+        }
+        
+        self.steam_guard_code = self.generate_totp_code(0)?;
+        
+        if self.attempt_steam_guard_code().await? {
+            return Ok(true);
+        }
+        
+        if self.steam_guard_code.is_none() || !self.retry_skewed_totp_code {
+            return Ok(false);
+        }
+        
+        for skew in [-TOTP_WINDOW_SECONDS, TOTP_WINDOW_SECONDS] {
+            let code = self.generate_totp_code(skew)?;
+            
+            if code == self.steam_guard_code {
+                continue;
+            }
+            
+            self.steam_guard_code = code;
+            
+            if self.attempt_steam_guard_code().await? {
+                return Ok(true);
+            }
+        }
+        
+        Ok(false)
+    }
+    
+    /// Generates a TOTP code from the `shared_secret` using the stored time offset shifted by 
+    /// `skew` seconds, if a `shared_secret` was supplied.
+    fn generate_totp_code(&self, skew: i64) -> Result<Option<String>, LoginSessionError> {
+        let Some(shared_secret) = &self.shared_secret else {
+            return Ok(None);
+        };
+        let code = crate::steam_guard::generate_auth_code_with_clock(
+            shared_secret,
+            self.time_offset + skew,
+            self.clock.as_ref(),
+        )?;
+        
+        Ok(Some(code))
+    }
+    
+    /// Sets the number of seconds the local clock is behind Steam's servers, e.g. as fetched 
+    /// using [`crate::steam_guard::fetch_server_time_offset`]. It's used when generating codes 
+    /// from the `shared_secret` supplied to `start_with_credentials`, for this and future login 
+    /// attempts with this session. Defaults to 0.
+    pub fn set_time_offset(&mut self, time_offset: i64) {
+        self.time_offset = time_offset;
+    }
+    
+    /// Gets the time offset set using [`LoginSession::set_time_offset`].
+    pub fn time_offset(&self) -> i64 {
+        self.time_offset
+    }
+    
+    /// Gets the device details sent when logging in, which determine how the device is listed in 
+    /// the account's authorized devices. Defaults to the details for the session's platform type, 
+    /// e.g. a spoofed Windows hostname for 
+    /// [`EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient`]. `None` if the platform 
+    /// type isn't supported.
+    pub fn device_details(&self) -> Option<&DeviceDetails> {
+        self.device_details.as_ref()
+    }
+    
+    /// Sets the device details sent when logging in with this session. The `platform_type` of 
+    /// `device_details` is replaced with the session's platform type.
+    /// 
+    /// Fails with [`LoginSessionError::InvalidDeviceFriendlyName`] if the friendly name is empty 
+    /// or longer than [`MAX_DEVICE_FRIENDLY_NAME_LENGTH`] characters.
+    pub fn set_device_details(&mut self, mut device_details: DeviceDetails) -> Result<(), LoginSessionError> {
+        let length = device_details.device_friendly_name.chars().count();
+        
+        if length == 0 || length > MAX_DEVICE_FRIENDLY_NAME_LENGTH {
+            return Err(LoginSessionError::InvalidDeviceFriendlyName(length));
+        }
+        
+        device_details.platform_type = self.platform_type;
+        self.device_details = Some(device_details);
+        Ok(())
     }
     
     /// Submits a Steam Guard code. If a Steam Guard code is needed, you can supply it using this 
     /// method.
     /// 
-    /// Note that an incorrect email code will fail with EResult value 
-    /// [`EResult::InvalidLoginAuthCode`] (65), and an incorrect TOTP code will fail with EResult 
-    /// value [`EResult::TwoFactorCodeMismatch`] (88).
+    /// Note that an incorrect code fails with [`LoginSessionError::GuardCodeInvalid`], carrying 
+    /// [`EResult::InvalidLoginAuthCode`] (65) for an email code or 
+    /// [`EResult::TwoFactorCodeMismatch`] (88) for a TOTP code.
+    /// 
+    /// Once a code has been accepted, submitting another code for the same login attempt fails 
+    /// with [`LoginSessionError::GuardAlreadyProvided`] without contacting Steam. A code can be 
+    /// submitted again after an incorrect code.
     pub async fn submit_steam_guard_code(
         &mut self,
         auth_code: String,
     ) -> Result<(), LoginSessionError> {
         self.verify_started(true)?;
         
+        if self.steam_guard_code_accepted {
+            return Err(LoginSessionError::GuardAlreadyProvided);
+        }
+        
         let start_session_response = self.start_session_response.as_ref()
             .ok_or(LoginSessionError::LoginSessionHasNotStarted)?;
         let needs_email_code = start_session_response.allowed_confirmations
@@ -470,8 +970,11 @@ where
             auth_code,
             code_type
         ).await?;
+        self.steam_guard_code_accepted = true;
         // should authenticate
-        self.do_poll().await?;
+        if !self.do_poll().await?.is_authenticated() {
+            self.state = LoginSessionState::Pending;
+        }
         
         Ok(())
     }
@@ -482,9 +985,137 @@ where
     /// 
     /// Returns an array of strings. Each string contains a cookie, e.g.
     /// `"steamLoginSecure=blahblahblahblah; Path=/; Secure; HttpOnly; SameSite=None; Domain=steamcommunity.com"`.
+    /// 
+    /// For [`EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser`], a redirected 
+    /// `finalizelogin` request results in [`LoginSessionError::UnexpectedRedirect`], and cookies 
+    /// from a transfer request that was redirected to another host are ignored. Transfers that 
+    /// fail are skipped, as long as one succeeds. Use `get_web_cookies_by_domain` to find out 
+    /// which domains failed.
+    #[must_use = "getting cookies has no effect other than returning them"]
     pub async fn get_web_cookies(
         &mut self,
     ) -> Result<Vec<String>, LoginSessionError> {
+        self.get_web_cookies_with_options(WebCookiesOptions::default()).await
+    }
+    
+    /// Same as `get_web_cookies`, but parses each cookie into a [`WebCookie`] so it can be 
+    /// inserted into a cookie jar. A [`WebCookie`] can be converted into a [`cookie::Cookie`].
+    /// Cookies that can't be parsed are skipped with a warning.
+    #[must_use = "getting cookies has no effect other than returning them"]
+    pub async fn get_parsed_web_cookies(
+        &mut self,
+    ) -> Result<Vec<WebCookie>, LoginSessionError> {
+        let cookies = self.get_web_cookies().await?
+            .iter()
+            .filter_map(|cookie| match WebCookie::parse(cookie) {
+                Ok(cookie) => Some(cookie),
+                Err(error) => {
+                    // the value is left out as it may contain a token
+                    let name = cookie.split_once('=').map_or(cookie.as_str(), |(name, _value)| name);
+                    
+                    log::warn!("Failed to parse cookie {name:?}: {error}");
+                    None
+                },
+            })
+            .collect();
+        
+        Ok(cookies)
+    }
+    
+    /// Same as `get_web_cookies`, using the given options.
+    #[must_use = "getting cookies has no effect other than returning them"]
+    pub async fn get_web_cookies_with_options(
+        &mut self,
+        options: WebCookiesOptions,
+    ) -> Result<Vec<String>, LoginSessionError> {
+        let FetchedWebCookies {
+            sessionid,
+            transfers,
+            failures,
+        } = self.fetch_web_cookies(options).await?;
+        
+        for (domain, error) in failures {
+            log::warn!("Failed to get cookies for {domain:?}: {error}");
+        }
+        
+        let mut cookies = transfers
+            .into_iter()
+            .flat_map(|(_domain, cookies)| cookies)
+            .collect::<Vec<_>>();
+        
+        cookies.push(format!("sessionid={sessionid}"));
+        
+        Ok(cookies)
+    }
+    
+    /// Same as `get_web_cookies_with_options`, but groups the cookies by domain. Only the 
+    /// transfers for `options.domains` are performed, defaulting to [`CookieDomain::DEFAULT`], 
+    /// which avoids round-trips for domains that aren't needed. Each domain's cookies include 
+    /// the `sessionid` cookie.
+    /// 
+    /// For platform types other than 
+    /// [`EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser`], the cookies are created 
+    /// from the access token and are the same for every domain.
+    /// 
+    /// If the transfers to some domains fail while others succeed, this fails with 
+    /// [`LoginSessionError::PartialCookies`], which carries the cookies for the domains that 
+    /// succeeded along with the error for each domain that failed. It's up to the caller whether 
+    /// a partial result is acceptable, e.g. if only the community cookies are needed. If every 
+    /// transfer fails, this fails with [`LoginSessionError::NoCookiesInResponse`].
+    #[must_use = "getting cookies has no effect other than returning them"]
+    pub async fn get_web_cookies_by_domain(
+        &mut self,
+        mut options: WebCookiesOptions,
+    ) -> Result<HashMap<CookieDomain, Vec<String>>, LoginSessionError> {
+        let domains = options.domains
+            .get_or_insert_with(|| CookieDomain::DEFAULT.to_vec())
+            .clone();
+        let FetchedWebCookies {
+            sessionid,
+            transfers,
+            failures,
+        } = self.fetch_web_cookies(options).await?;
+        let mut cookies = HashMap::<CookieDomain, Vec<String>>::new();
+        
+        for (domain, transfer_cookies) in transfers {
+            // Cookies created from the access token aren't tied to a transfer.
+            let transfer_domains = match domain {
+                Some(domain) => vec![domain],
+                None => domains.clone(),
+            };
+            
+            for domain in transfer_domains {
+                cookies.entry(domain).or_default().extend(transfer_cookies.iter().cloned());
+            }
+        }
+        
+        for domain_cookies in cookies.values_mut() {
+            domain_cookies.push(format!("sessionid={sessionid}"));
+        }
+        
+        // Only transfers to requested domains are performed, so every failure has a domain.
+        let failures = failures
+            .into_iter()
+            .filter_map(|(domain, error)| Some((domain?, error)))
+            .collect::<HashMap<_, _>>();
+        
+        if !failures.is_empty() {
+            return Err(LoginSessionError::PartialCookies(PartialCookieError {
+                cookies,
+                failures,
+            }));
+        }
+        
+        Ok(cookies)
+    }
+    
+    /// Gets web cookies, returning the session ID along with the cookies from each transfer 
+    /// other than `sessionid` and the error for each transfer that failed. Fails if no transfer 
+    /// succeeded.
+    async fn fetch_web_cookies(
+        &mut self,
+        options: WebCookiesOptions,
+    ) -> Result<FetchedWebCookies, LoginSessionError> {
         #[derive(Debug, Deserialize)]
         struct TransferInfo {
             url: String,
@@ -502,8 +1133,18 @@ where
             transfer_info: Option<Vec<TransferInfo>>,
         }
         
-        async fn get_cookies(request: RequestBuilder) -> Option<Vec<String>> {
-            let response = request.send().await.ok()?;
+        async fn get_cookies(request: RequestBuilder, url: &str) -> Result<Vec<String>, CookieTransferError> {
+            let response = request.send().await?;
+            let host = url::Url::parse(url).ok()
+                .and_then(|url| url.host_str().map(String::from));
+            
+            // Cookies are attributed to the domain of the response, so a transfer that was 
+            // redirected to another host would produce cookies for the wrong domain.
+            if response.url().host_str() != host.as_deref() {
+                log::warn!("Transfer to {url} was redirected to {}; ignoring its cookies", response.url());
+                return Err(CookieTransferError::UnexpectedRedirect(response.url().to_string()));
+            }
+            
             let headers = response.headers();
             let set_cookie = headers.get_all(SET_COOKIE);
             let cookies = set_cookie
@@ -521,15 +1162,11 @@ where
                 })
                 .collect::<Vec<String>>();
             
-            if cookies.is_empty() {
-                return None;
-            }
-            
             if !cookies.iter().any(|cookie| cookie.contains("steamLoginSecure=")) {
-                return None;
+                return Err(CookieTransferError::NoCookiesInResponse);
             }
             
-            Some(cookies)
+            Ok(cookies)
         }
         
         let refresh_token = self.refresh_token.as_ref()
@@ -548,10 +1185,12 @@ where
             // when it expires (or was issued), but let's try to minimize how much we depend on 
             // the access token being a JWT (as Valve may change it at any point).
             if self.access_token.is_none() ||
-            self.access_token_set_at
-                .map(|datetime| Utc::now() - datetime > Duration::try_minutes(10).unwrap())
-                .unwrap_or(false) {
-                self.refresh_access_token().await?;
+            self.access_token_is_stale() ||
+            !self.access_token_has_audience(options.access_token_audience.as_deref()) {
+                self.refresh_access_token_with_options(GenerateAccessTokenOptions {
+                    audience: options.access_token_audience,
+                    renew_refresh: false,
+                }).await?;
             }
             
             let access_token = self.access_token.as_ref()
@@ -562,30 +1201,48 @@ where
             let encoded_cookie_value = form_urlencoded::byte_serialize(cookie_value.as_bytes())
                 .collect::<String>();
             
-            return Ok(vec![
-                format!("steamLoginSecure={}", encoded_cookie_value),
-                format!("sessionid={}", sessionid),
-            ]);
+            return Ok(FetchedWebCookies {
+                sessionid,
+                transfers: vec![
+                    (None, vec![format!("steamLoginSecure={}", encoded_cookie_value)]),
+                ],
+                failures: Vec::new(),
+            });
         }
         
+        let website_id = options.website_id.unwrap_or(WebsiteId::Community);
+        let origin = website_id.origin()
+            .ok_or(LoginSessionError::UnsupportedWebsiteId(website_id))?;
+        let redir = match website_id {
+            WebsiteId::Community => format!("{origin}/login/home/?goto="),
+            _ => format!("{origin}/"),
+        };
         let mut headers = create_api_headers()?;
-        headers.insert("Origin", HeaderValue::from_str("https://steamcommunity.com")?);
-        headers.insert("Referer", HeaderValue::from_str("https://steamcommunity.com/")?);
+        headers.insert("Origin", HeaderValue::from_str(origin)?);
+        headers.insert("Referer", HeaderValue::from_str(&format!("{origin}/"))?);
         let form = reqwest::multipart::Form::new()
             .text("nonce", refresh_token.clone())
             .text("sessionid", sessionid.clone())
-            .text("redir", "https://steamcommunity.com/login/home/?goto=");
-        let response = self.client.post("https://login.steampowered.com/jwt/finalizelogin")
+            .text("redir", redir);
+        let response = self.client.post(FINALIZE_LOGIN_URL)
             .headers(headers)
             .multipart(form)
             .send()
-            .await?
+            .await?;
+        
+        // A redirected POST is retried as a GET without its body, which can't produce a valid 
+        // response.
+        if response.status().is_redirection() || response.url().as_str() != FINALIZE_LOGIN_URL {
+            return Err(LoginSessionError::UnexpectedRedirect(response.url().to_string()));
+        }
+        
+        let response = response
             .json::<Response>()
             .await?;
         
         if let Some(eresult) = response.result {
             if eresult != EResult::OK {
-                return Err(LoginSessionError::EResultNotOK(eresult));
+                return Err(eresult.into());
             }
         }
         
@@ -593,20 +1250,41 @@ where
             .ok_or(LoginSessionError::MalformedResponse)?
             .into_iter()
             .map(|transfer_info| {
+                let domain = url::Url::parse(&transfer_info.url).ok()
+                    .and_then(|url| url.host_str().and_then(CookieDomain::from_host));
+                
+                (domain, transfer_info)
+            })
+            // Skip transfers to domains that weren't requested.
+            .filter(|(domain, _transfer_info)| options.domains.as_ref().is_none_or(|domains| {
+                domain.is_some_and(|domain| domains.contains(&domain))
+            }))
+            .map(|(domain, transfer_info)| {
                 let form = value_to_multipart(transfer_info.params)
                     .text("steamID", u64::from(steamid).to_string());
                 let request = self.client.post(&transfer_info.url).multipart(form);
                 
                 // send a request that will return cookies if it contains cookies
                 log::debug!("POST {}", transfer_info.url);
-                get_cookies(request)
+                async move {
+                    (domain, get_cookies(request, &transfer_info.url).await)
+                }
             })
             .collect::<FuturesOrdered<_>>();
         let mut cookies = Vec::new();
+        let mut failures = Vec::new();
         
-        while let Some(transfer) = transfers.next().await {
-            if let Some(mut domain_cookies) = transfer {
-                cookies.append(&mut domain_cookies);
+        while let Some((domain, transfer)) = transfers.next().await {
+            match transfer {
+                Ok(domain_cookies) => {
+                    let domain_cookies = domain_cookies
+                        .into_iter()
+                        .filter(|cookie| !cookie.contains("sessionid="))
+                        .collect();
+                
+                    cookies.push((domain, domain_cookies));
+                },
+                Err(error) => failures.push((domain, error)),
             }
         }
         
@@ -614,32 +1292,82 @@ where
             return Err(LoginSessionError::NoCookiesInResponse);
         }
         
-        let mut cookies = cookies
-            .into_iter()
-            .filter(|cookie| !cookie.contains("sessionid="))
-            .collect::<Vec<_>>();
-        
-        cookies.push(format!("sessionid={sessionid}"));
-        
-        Ok(cookies)
+        Ok(FetchedWebCookies {
+            sessionid,
+            transfers: cookies,
+            failures,
+        })
     }
     
     /// Refreshes the access token. As long as a `refresh_token` is set, you can call this method 
     /// to obtain a new access token. 
     pub async fn refresh_access_token(&mut self) -> Result<(), LoginSessionError> {
+        self.refresh_access_token_with_options(GenerateAccessTokenOptions::default()).await
+    }
+    
+    /// Refreshes the access token using the given options.
+    /// 
+    /// Steam derives the audience of an access token from the refresh token it was minted from, 
+    /// so a narrower audience can't be requested outright. Instead a requested `audience` is 
+    /// checked against the refresh token before the request is made, and against the minted 
+    /// access token afterwards. Returns [`LoginSessionError::AudienceNotAllowed`] if either 
+    /// doesn't allow it. Without an `audience` the access token has the refresh token's own 
+    /// audience, which may differ from this session's platform type if 
+    /// [`LoginSessionBuilder::allow_platform_mismatch`] is set.
+    pub async fn refresh_access_token_with_options(
+        &mut self,
+        options: GenerateAccessTokenOptions,
+    ) -> Result<(), LoginSessionError> {
         let refresh_token = self.refresh_token.as_ref()
             .ok_or_else(|| LoginSessionError::NoRefreshToken)?;
-        let access_token = self.handler.generate_access_token_for_app(
+        
+        if let Some(audience) = &options.audience {
+            if !JwtPayload::from_str(refresh_token)?.aud.contains(audience) {
+                return Err(LoginSessionError::AudienceNotAllowed(audience.clone()));
+            }
+        }
+        
+        let response = self.handler.generate_access_token_for_app(
             refresh_token.clone(),
-            false,
+            options.renew_refresh,
         ).await?;
-        let access_token = access_token.access_token().to_string();
+        let access_token = response.access_token().to_string();
+        
+        if let Some(audience) = options.audience {
+            if !JwtPayload::from_str(&access_token)?.aud.contains(&audience) {
+                return Err(LoginSessionError::AudienceNotAllowed(audience));
+            }
+        }
         
         self.set_access_token(access_token)?;
         
+        if !response.refresh_token().is_empty() {
+            self.set_refresh_token(response.refresh_token().to_owned())?;
+        }
+        
         Ok(())
     }
     
+    /// Checks if the access token was set more than 10 minutes ago.
+    fn access_token_is_stale(&self) -> bool {
+        self.access_token_set_at
+            .map(|datetime| self.clock.now() - datetime > Duration::try_minutes(10).unwrap())
+            .unwrap_or(false)
+    }
+    
+    /// Checks whether the current access token is valid for `audience`. If `audience` is `None` 
+    /// any access token is accepted.
+    fn access_token_has_audience(&self, audience: Option<&str>) -> bool {
+        let Some(audience) = audience else {
+            return true;
+        };
+        
+        self.access_token.as_ref()
+            .and_then(|access_token| JwtPayload::from_str(access_token).ok())
+            .map(|decoded| decoded.aud.iter().any(|a| a == audience))
+            .unwrap_or(false)
+    }
+    
     /// Does the same thing as `refresh_access_token`, while also attempting to renew your refresh 
     /// token.
     ///
@@ -662,53 +1390,204 @@ where
         Ok(!access_token.is_empty())
     }
     
+    /// Same as `start_with_credentials`, but returns [`LoginSessionError::Cancelled`] as soon as 
+    /// `cancellation_token` is cancelled. Any request that is in flight is abandoned and the 
+    /// session can be started again.
+    #[must_use = "the response tells whether a Steam Guard code or confirmation is needed"]
+    pub async fn start_with_credentials_with_cancellation(
+        &mut self,
+        details: StartLoginSessionWithCredentialsDetails,
+        cancellation_token: CancellationToken,
+    ) -> Result<StartSessionResponse, LoginSessionError> {
+        tokio::select! {
+            result = self.start_with_credentials(details) => result,
+            _ = cancellation_token.cancelled() => Err(LoginSessionError::Cancelled),
+        }
+    }
+    
+    /// Same as `submit_steam_guard_code`, but returns [`LoginSessionError::Cancelled`] as soon 
+    /// as `cancellation_token` is cancelled. Any request that is in flight is abandoned.
+    pub async fn submit_steam_guard_code_with_cancellation(
+        &mut self,
+        auth_code: String,
+        cancellation_token: CancellationToken,
+    ) -> Result<(), LoginSessionError> {
+        tokio::select! {
+            result = self.submit_steam_guard_code(auth_code) => result,
+            _ = cancellation_token.cancelled() => Err(LoginSessionError::Cancelled),
+        }
+    }
+    
+    /// Same as `poll`, but returns [`LoginSessionError::Cancelled`] as soon as 
+    /// `cancellation_token` is cancelled. Any request that is in flight is abandoned.
+    pub async fn poll_with_cancellation(
+        &mut self,
+        cancellation_token: CancellationToken,
+    ) -> Result<(), LoginSessionError> {
+        tokio::select! {
+            result = self.poll() => result,
+            _ = cancellation_token.cancelled() => Err(LoginSessionError::Cancelled),
+        }
+    }
+    
+    /// Polls the auth session status until the login is confirmed or times out. The timeout is 
+    /// counted from when the auth session was started, see `time_remaining`. Polling a session 
+    /// which is already authenticated or expired returns immediately without changing it.
+    /// 
+    /// Some accounts must accept updated agreements before the login can complete. This can't be 
+    /// done automatically, so polling stops early and the state becomes 
+    /// [`LoginSessionState::AgreementRequired`] with the URL the user needs to visit. Call `poll` 
+    /// again once the agreement has been accepted to resume; the timeout then starts over.
     pub async fn poll(&mut self) -> Result<(), LoginSessionError> {
-        let polling_started_time = Utc::now();
-        let poll_interval = self.start_session_response.as_ref()
-            .ok_or(LoginSessionError::LoginSessionHasNotStarted)?
-            .interval();
+        self.verify_started(false)?;
+        
+        match self.state {
+            LoginSessionState::Authenticated |
+            LoginSessionState::Expired => return Ok(()),
+            LoginSessionState::AgreementRequired(_) => {
+                // the user may have taken a while to accept the agreement
+                self.session_started_at = Some(self.clock.now());
+                self.state = LoginSessionState::Pending;
+            },
+            _ => {},
+        }
         
         loop {
-            let total_polling_time = Utc::now() - polling_started_time;
+            let timed_out = self.time_remaining()
+                .is_some_and(|time_remaining| time_remaining <= Duration::zero());
             
-            if total_polling_time >= self.login_timeout {
+            if timed_out {
+                self.state = LoginSessionState::Expired;
                 return Ok(());
             }
             
-            if self.do_poll().await? {
+            self.wait_for_poll_interval().await;
+            
+            let response = self.do_poll().await?;
+            
+            if response.is_authenticated() || response.agreement_session_url.is_some() {
                 return Ok(());
             }
-            
-            // poll again
-            async_std::task::sleep(std::time::Duration::from_secs(poll_interval as u64)).await;
         }
     }
     
-    /// Performs a poll. Returns true if complete.
-    async fn do_poll(&mut self) -> Result<bool, LoginSessionError> {
+    /// Performs a single poll of the auth session status. Once the login has been confirmed, the 
+    /// returned [`PollResult`] contains the issued tokens and this session is authenticated.
+    /// 
+    /// If the last poll was less than the interval given by Steam ago, this waits for the rest of 
+    /// the interval first.
+    #[must_use = "the result tells whether the login has been confirmed"]
+    pub async fn poll_status(&mut self) -> Result<PollResult, LoginSessionError> {
+        self.verify_started(false)?;
+        self.wait_for_poll_interval().await;
+        self.do_poll().await
+    }
+    
+    /// Same as `poll_status`, but polls immediately regardless of the interval given by Steam, 
+    /// e.g. when the user presses a button. A [`LoginThrottle`] shared with other sessions still 
+    /// applies.
+    /// 
+    /// Polling more often than the interval may get requests rate limited, so avoid calling this 
+    /// in a loop. `poll` and `poll_status` count the interval from the last poll, including polls 
+    /// made using this method.
+    #[must_use = "the result tells whether the login has been confirmed"]
+    pub async fn poll_once_now(&mut self) -> Result<PollResult, LoginSessionError> {
+        self.do_poll().await
+    }
+    
+    /// Waits until the interval given by Steam has passed since the last poll.
+    async fn wait_for_poll_interval(&self) {
+        let (Some(last_polled_at), Some(interval)) = (self.last_polled_at, self.poll_interval()) else {
+            return;
+        };
+        let remaining = last_polled_at + interval - self.clock.now();
+        
+        if let Ok(remaining) = remaining.to_std() {
+            async_std::task::sleep(remaining).await;
+        }
+    }
+    
+    /// Gets the interval Steam gave for polling, if the login has started.
+    fn poll_interval(&self) -> Option<Duration> {
+        let start_session_response = self.start_session_response.as_ref()?;
+        
+        Some(Duration::milliseconds((start_session_response.interval() * 1000.0) as i64))
+    }
+    
+    /// Waits for the next free slot of the shared [`LoginThrottle`], if any.
+    async fn wait_for_throttle(&self) {
+        if let Some(throttle) = &self.throttle {
+            throttle.wait().await;
+        }
+    }
+    
+    /// Gets the result of the most recent poll, if any.
+    pub fn last_poll_result(&self) -> Option<&PollResult> {
+        self.last_poll_result.as_ref()
+    }
+    
+    /// Gets the details of the most recent poll if it failed, including Steam's explanation of 
+    /// the failure and how long to wait before polling again. Cleared by a successful poll.
+    pub fn last_poll_failure(&self) -> Option<&PollFailure> {
+        self.last_poll_failure.as_ref()
+    }
+    
+    /// Performs a poll.
+    async fn do_poll(&mut self) -> Result<PollResult, LoginSessionError> {
         let start_session_response = self.start_session_response.as_ref()
             .ok_or(LoginSessionError::LoginSessionHasNotStarted)?;
         let clientid = start_session_response.client_id();
         let request_id = start_session_response.request_id();
-        let response = self.handler.poll_login_status(
+        
+        self.wait_for_throttle().await;
+        self.last_polled_at = Some(self.clock.now());
+        
+        let response = match self.handler.poll_login_status(
             clientid,
             request_id.into(),
-        ).await?;
+        ).await {
+            Ok(response) => PollResult {
+                interval: self.poll_interval().and_then(|interval| interval.to_std().ok()),
+                ..PollResult::from(response)
+            },
+            Err(error) => {
+                if error.eresult() == Some(EResult::Expired) {
+                    self.state = LoginSessionState::Expired;
+                }
+                
+                self.last_poll_failure = Some(self.poll_failure(&error));
+                return Err(error.into());
+            },
+        };
         
-        if response.had_remote_interaction() {
-            
+        self.last_poll_failure = None;
+        
+        if !self.state.is_authenticated() {
+            if let Some(agreement_session_url) = &response.agreement_session_url {
+                self.state = LoginSessionState::AgreementRequired(agreement_session_url.clone());
+            } else if response.had_remote_interaction {
+                self.state = LoginSessionState::RemoteInteraction;
+            }
         }
         
-        if !response.refresh_token().is_empty() {
-            let client_id = response.new_client_id();
-            
+        if let Some(client_id) = response.new_client_id {
             if let Some(start_session_response) = self.start_session_response.as_mut() {
                 start_session_response.set_client_id(client_id);
             }
+        }
+        
+        if let Some(account_name) = &response.account_name {
+            self.account_name = Some(account_name.clone());
+        }
+        
+        self.store_new_guard_data(&response);
+        
+        if let Some(refresh_token) = &response.refresh_token {
+            if self.auto_mint_access_token {
+                self.set_access_token(response.access_token.clone().unwrap_or_default())?;
+            }
             
-            self.access_token = Some(response.access_token().to_owned());
-            self.set_access_token(response.access_token().to_owned())?;
-            self.set_refresh_token(response.refresh_token().to_owned())?;
+            self.set_refresh_token(refresh_token.clone())?;
             
             // On 2023-09-12, Steam stopped issuing access tokens alongside refresh tokens 
             // for newly authenticated sessions. This won't affect any consumer apps that 
@@ -720,15 +1599,55 @@ where
             // `get_web_cookies` should be used instead. However, the access token is also 
             // used as a WebAPI key for MobileApp, so we should probably ensure that we 
             // have one for that platform.
-            if self.refresh_token.is_none() && 
+            if self.auto_mint_access_token && 
+            self.access_token.is_none() && 
             self.platform_type == EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp {
                 self.refresh_access_token().await?;
             }
-            
-            return Ok(true);
         }
         
-        Ok(false)
+        // completed from the session so the result of the login holds everything needed to 
+        // persist it; the SteamID is confirmed against the subject of the tokens by now
+        let response = PollResult {
+            access_token: if response.is_authenticated() {
+                self.access_token.clone()
+            } else {
+                response.access_token
+            },
+            account_name: response.account_name.or_else(|| self.account_name.clone()),
+            steamid: self.steamid(),
+            steam_guard_machine_token: response.new_guard_data
+                .as_ref()
+                .and(self.new_steam_guard_machine_token.clone()),
+            ..response
+        };
+        
+        self.last_poll_result = Some(response.clone());
+        
+        Ok(response)
+    }
+    
+    /// Gets the details of a poll which failed with `error`.
+    fn poll_failure(&self, error: &AuthenticationClientError) -> PollFailure {
+        PollFailure {
+            eresult: error.eresult(),
+            extended_error_message: error.extended_error_message().map(String::from),
+            interval: self.poll_interval().and_then(|interval| interval.to_std().ok()),
+        }
+    }
+    
+    /// Stores the guard data issued in `response` as the session's machine token, bound to the 
+    /// account it was issued for.
+    fn store_new_guard_data(&mut self, response: &PollResult) {
+        let (Some(guard_data), Some(account_name)) = (&response.new_guard_data, &self.account_name) else {
+            return;
+        };
+        
+        self.steam_guard_machine_token = Some(guard_data.clone().into_bytes());
+        self.new_steam_guard_machine_token = Some(SteamGuardMachineToken {
+            account_name: account_name.clone(),
+            guard_data: guard_data.clone(),
+        });
     }
     
     fn verify_started(&self, must_have_steamid: bool) -> Result<(), LoginSessionError> {
@@ -743,3 +1662,640 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::ApiRequest;
+    use crate::enums::{ETokenRenewalType, ELanguage};
+    use crate::transports::{ReplayTransport, RecordedExchange};
+    use crate::proto::steammessages_auth_steamclient::{
+        CAuthentication_AccessToken_GenerateForApp_Request,
+        CAuthentication_AccessToken_GenerateForApp_Response,
+        CAuthentication_AllowedConfirmation,
+        CAuthentication_PollAuthSessionStatus_Request,
+        CAuthentication_PollAuthSessionStatus_Response,
+        CAuthentication_UpdateAuthSessionWithSteamGuardCode_Request,
+    };
+    use protobuf::Message;
+    
+    fn assert_send_sync<T: Send + Sync>() {}
+    
+    fn assert_send<T: Send>(_: &T) {}
+    
+    /// Creates a session for `platform_type` using `transport`, with the default options.
+    fn build_session<T: Transport>(transport: T, platform_type: EAuthTokenPlatformType) -> LoginSession<T> {
+        LoginSession::builder(transport, platform_type)
+            .build()
+            .unwrap()
+    }
+    
+    #[test]
+    fn login_session_is_send_sync() {
+        assert_send_sync::<LoginSession<WebApiTransport>>();
+        assert_send_sync::<LoginSession<WebSocketCMTransport>>();
+        assert_send_sync::<LoginSessionError>();
+        assert_send_sync::<crate::login_approver::LoginApprover>();
+    }
+    
+    #[test]
+    fn login_session_futures_are_send() {
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser);
+        
+        assert_send(&session.start_with_credentials(StartLoginSessionWithCredentialsDetails::default()));
+        assert_send(&session.submit_steam_guard_code(String::new()));
+        assert_send(&session.poll());
+        assert_send(&session.get_web_cookies());
+        assert_send(&session.get_web_cookies_by_domain(WebCookiesOptions::default()));
+        assert_send(&session.refresh_access_token());
+    }
+    
+    #[test]
+    fn does_not_retry_invalid_password_by_default() {
+        let session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser);
+        
+        assert!(!session.retry_invalid_password);
+    }
+    
+    #[test]
+    fn debug_redacts_secrets() {
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser);
+        
+        session.access_token = Some("secret-access-token".into());
+        session.refresh_token = Some("secret-refresh-token".into());
+        session.steam_guard_code = Some("ABCDE".into());
+        
+        let debug = format!("{session:?}");
+        
+        assert!(!debug.contains("secret-access-token"));
+        assert!(!debug.contains("secret-refresh-token"));
+        assert!(!debug.contains("ABCDE"));
+        assert!(debug.contains("<redacted>"));
+    }
+    
+    #[test]
+    fn generates_totp_code_using_time_offset() {
+        let clock = Arc::new(crate::clock::MockClock::default());
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser);
+        let shared_secret = "AAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+        
+        session.clock = clock.clone();
+        
+        assert_eq!(session.generate_totp_code(0).unwrap(), None);
+        
+        session.shared_secret = Some(shared_secret.into());
+        session.set_time_offset(90);
+        
+        let expected = crate::steam_guard::generate_auth_code_for_time(
+            shared_secret,
+            clock.now().timestamp() + 90,
+        ).unwrap();
+        
+        assert_eq!(session.generate_totp_code(0).unwrap(), Some(expected));
+    }
+    
+    /// A transport whose requests never complete.
+    #[derive(Debug)]
+    struct StalledTransport;
+    
+    #[async_trait::async_trait]
+    impl Transport for StalledTransport {
+        async fn send_request<Msg>(
+            &self,
+            _msg: Msg,
+            _access_token: Option<String>,
+        ) -> Result<crate::transports::ResponseReceiver<Msg::Response>, crate::authentication_client::Error>
+        where
+            Msg: ApiRequest,
+            <Msg as ApiRequest>::Response: Send,
+        {
+            std::future::pending().await
+        }
+    }
+    
+    /// Cancels `cancellation_token` once the request in flight has had a chance to be sent.
+    fn cancel_soon(cancellation_token: &CancellationToken) {
+        let cancellation_token = cancellation_token.clone();
+        
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            cancellation_token.cancel();
+        });
+    }
+    
+    #[tokio::test]
+    async fn cancels_start_with_credentials_mid_request() {
+        let mut session = build_session(StalledTransport, EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser);
+        let cancellation_token = CancellationToken::new();
+        let details = StartLoginSessionWithCredentialsDetails {
+            account_name: "gabe".into(),
+            password: "hunter2".into(),
+            ..StartLoginSessionWithCredentialsDetails::default()
+        };
+        
+        cancel_soon(&cancellation_token);
+        
+        let result = session.start_with_credentials_with_cancellation(details, cancellation_token).await;
+        
+        assert!(matches!(result, Err(LoginSessionError::Cancelled)));
+        assert!(session.start_session_response.is_none());
+    }
+    
+    #[tokio::test]
+    async fn cancels_submit_steam_guard_code_mid_request() {
+        let mut session = device_code_session(StalledTransport);
+        let cancellation_token = CancellationToken::new();
+        
+        cancel_soon(&cancellation_token);
+        
+        let result = session.submit_steam_guard_code_with_cancellation("ABCDE".into(), cancellation_token).await;
+        
+        assert!(matches!(result, Err(LoginSessionError::Cancelled)));
+        assert!(!session.steam_guard_code_accepted);
+    }
+    
+    #[tokio::test]
+    async fn cancels_poll_mid_request() {
+        let mut session = device_code_session(StalledTransport);
+        let cancellation_token = CancellationToken::new();
+        
+        session.session_started_at = Some(session.clock.now());
+        cancel_soon(&cancellation_token);
+        
+        let result = session.poll_with_cancellation(cancellation_token).await;
+        
+        assert!(matches!(result, Err(LoginSessionError::Cancelled)));
+    }
+    
+    /// Creates a session using `transport` which needs a device code for a login which was 
+    /// started with a `shared_secret`.
+    fn device_code_session<T: Transport>(transport: T) -> LoginSession<T> {
+        let mut session = build_session(transport, EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser);
+        let mut start_session_response = CAuthentication_BeginAuthSessionViaCredentials_Response::new();
+        let mut confirmation = CAuthentication_AllowedConfirmation::new();
+        
+        confirmation.set_confirmation_type(EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceCode);
+        start_session_response.set_client_id(1);
+        start_session_response.set_steamid(76561197960287930);
+        start_session_response.allowed_confirmations.push(confirmation);
+        session.start_session_response = Some(start_session_response);
+        session.shared_secret = Some("AAAAAAAAAAAAAAAAAAAAAAAAAAA=".into());
+        session.clock = Arc::new(crate::clock::MockClock::default());
+        session
+    }
+    
+    /// Records the submission of `code`, rejecting it unless `accepted`.
+    fn code_exchange(code: &str, accepted: bool) -> RecordedExchange {
+        let mut request = CAuthentication_UpdateAuthSessionWithSteamGuardCode_Request::new();
+        
+        request.set_client_id(1);
+        request.set_steamid(76561197960287930);
+        request.set_code(code.into());
+        request.set_code_type(EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceCode);
+        
+        RecordedExchange {
+            name: <CAuthentication_UpdateAuthSessionWithSteamGuardCode_Request as ApiRequest>::NAME.into(),
+            request: crate::helpers::encode_base64(request.write_to_bytes().unwrap()),
+            response: accepted.then(String::new),
+            eresult: (!accepted).then_some(EResult::TwoFactorCodeMismatch as i32),
+            error: None,
+        }
+    }
+    
+    /// Records a poll of the session started by `device_code_session`, answered with `response`.
+    fn poll_exchange(response: &CAuthentication_PollAuthSessionStatus_Response) -> RecordedExchange {
+        let mut request = CAuthentication_PollAuthSessionStatus_Request::new();
+        
+        request.set_client_id(1);
+        request.set_request_id(Vec::new());
+        
+        RecordedExchange {
+            name: <CAuthentication_PollAuthSessionStatus_Request as ApiRequest>::NAME.into(),
+            request: crate::helpers::encode_base64(request.write_to_bytes().unwrap()),
+            response: Some(crate::helpers::encode_base64(response.write_to_bytes().unwrap())),
+            eresult: None,
+            error: None,
+        }
+    }
+    
+    #[tokio::test]
+    async fn retries_generated_totp_code_for_adjacent_windows() {
+        let codes = device_code_session(WebApiTransport::new());
+        let code = |skew| codes.generate_totp_code(skew).unwrap().unwrap();
+        let mut session = device_code_session(ReplayTransport::new(vec![
+            code_exchange(&code(0), false),
+            code_exchange(&code(-TOTP_WINDOW_SECONDS), false),
+            code_exchange(&code(TOTP_WINDOW_SECONDS), true),
+            poll_exchange(&CAuthentication_PollAuthSessionStatus_Response::new()),
+        ]));
+        
+        assert!(session.attempt_totp_code_auth().await.unwrap());
+        assert_eq!(session.steam_guard_code, Some(code(TOTP_WINDOW_SECONDS)));
+        
+        let mut session = device_code_session(ReplayTransport::new(vec![
+            code_exchange(&code(0), false),
+        ]));
+        
+        session.retry_skewed_totp_code = false;
+        
+        assert!(!session.attempt_totp_code_auth().await.unwrap());
+        
+        // codes which weren't generated aren't retried
+        let mut session = device_code_session(ReplayTransport::new(vec![
+            code_exchange("ABCDE", false),
+        ]));
+        
+        session.steam_guard_code = Some("ABCDE".into());
+        
+        assert!(!session.attempt_totp_code_auth().await.unwrap());
+    }
+    
+    #[test]
+    fn time_remaining_counts_down_from_session_start() {
+        let clock = Arc::new(crate::clock::MockClock::default());
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser);
+        
+        session.clock = clock.clone();
+        
+        assert_eq!(session.time_remaining(), None);
+        
+        session.start_session_response = Some(Default::default());
+        session.session_started_at = Some(clock.now());
+        session.state = LoginSessionState::Pending;
+        clock.advance(Duration::seconds(10));
+        
+        assert_eq!(session.time_remaining(), Some(Duration::seconds(LOGIN_TIMEOUT_SECONDS - 10)));
+        
+        clock.advance(Duration::seconds(LOGIN_TIMEOUT_SECONDS));
+        
+        assert_eq!(session.time_remaining(), Some(Duration::zero()));
+        
+        session.state = LoginSessionState::Authenticated;
+        
+        assert_eq!(session.time_remaining(), None);
+    }
+    
+    #[tokio::test]
+    async fn poll_keeps_authenticated_session() {
+        let mut session = device_code_session(ReplayTransport::new(Vec::new()));
+        
+        session.set_refresh_token(refresh_token(&["web", "renew", "derive"], 0)).unwrap();
+        session.poll().await.unwrap();
+        
+        assert!(session.is_authenticated());
+        assert!(session.refresh_token.is_some());
+    }
+    
+    #[tokio::test]
+    async fn poll_resumes_after_agreement_with_new_deadline() {
+        let mut response = CAuthentication_PollAuthSessionStatus_Response::new();
+        
+        response.set_refresh_token(refresh_token(&["web", "renew", "derive"], 0));
+        
+        let clock = Arc::new(crate::clock::MockClock::default());
+        let mut session = device_code_session(ReplayTransport::new(vec![
+            poll_exchange(&response),
+        ]));
+        
+        session.clock = clock.clone();
+        session.session_started_at = Some(clock.now());
+        session.state = LoginSessionState::AgreementRequired("https://store.steampowered.com/agreement".into());
+        clock.advance(Duration::seconds(LOGIN_TIMEOUT_SECONDS * 2));
+        session.poll().await.unwrap();
+        
+        assert!(session.is_authenticated());
+    }
+    
+    #[tokio::test]
+    async fn rejects_unknown_platform_type() {
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown);
+        
+        assert!(matches!(
+            session.start_with_credentials(StartLoginSessionWithCredentialsDetails::default()).await,
+            Err(LoginSessionError::PlatformTypeRequired(EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown)),
+        ));
+        
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp);
+        
+        assert!(matches!(
+            session.start_with_credentials(StartLoginSessionWithCredentialsDetails {
+                platform_type: EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown,
+                ..Default::default()
+            }).await,
+            Err(LoginSessionError::PlatformTypeRequired(EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown)),
+        ));
+    }
+    
+    #[test]
+    fn sets_device_details() {
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp);
+        
+        assert_eq!(session.device_details().unwrap().device_friendly_name, "Galaxy S22");
+        
+        let device_details = DeviceDetails {
+            device_friendly_name: String::from("Pixel 8"),
+            platform_type: EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown,
+            ..session.device_details().unwrap().clone()
+        };
+        
+        session.set_device_details(device_details.clone()).unwrap();
+        
+        assert_eq!(session.device_details(), Some(&DeviceDetails {
+            platform_type: EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp,
+            ..device_details.clone()
+        }));
+        
+        for device_friendly_name in [String::new(), "a".repeat(MAX_DEVICE_FRIENDLY_NAME_LENGTH + 1)] {
+            assert!(matches!(
+                session.set_device_details(DeviceDetails {
+                    device_friendly_name,
+                    ..device_details.clone()
+                }),
+                Err(LoginSessionError::InvalidDeviceFriendlyName(_)),
+            ));
+        }
+        
+        assert_eq!(session.device_details().unwrap().device_friendly_name, "Pixel 8");
+    }
+    
+    #[test]
+    fn rejects_malformed_machine_id() {
+        let builder = || LoginSession::builder(
+            WebApiTransport::new(),
+            EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient,
+        );
+        
+        assert!(matches!(
+            builder().machine_id(b"not a machine id".as_slice()).build(),
+            Err(LoginSessionError::InvalidMachineId),
+        ));
+        assert!(builder().machine_id(MachineId::Generate.resolve("gaben")).build().is_ok());
+    }
+    
+    #[test]
+    fn sessions_share_handler() {
+        let first = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp);
+        let second = LoginSessionBuilder::with_handler(first.handler().clone())
+            .build()
+            .unwrap();
+        
+        assert!(Arc::ptr_eq(first.handler(), second.handler()));
+        assert_eq!(second.platform_type, EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp);
+        assert!(matches!(
+            LoginSessionBuilder::with_handler(first.handler().clone())
+                .platform_type(EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser)
+                .build(),
+            Err(LoginSessionError::SharedHandlerOption("platform_type")),
+        ));
+        assert!(matches!(
+            LoginSessionBuilder::with_handler(first.handler().clone())
+                .language(ELanguage::German)
+                .build(),
+            Err(LoginSessionError::SharedHandlerOption("language")),
+        ));
+    }
+    
+    #[test]
+    fn reset_clears_account_state() {
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp);
+        let handler = session.handler().clone();
+        
+        session.set_refresh_token(refresh_token(&["mobile", "renew", "derive"], 0)).unwrap();
+        session.set_time_offset(5);
+        session.reset();
+        
+        assert_eq!(session.state(), &LoginSessionState::Idle);
+        assert!(session.refresh_token.is_none());
+        assert!(session.account_name.is_none());
+        assert!(Arc::ptr_eq(session.handler(), &handler));
+        assert_eq!(session.time_offset(), 5);
+    }
+    
+    #[test]
+    fn stores_new_guard_data_for_account() {
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser);
+        let response = PollResult {
+            new_guard_data: Some("guard-data".into()),
+            ..Default::default()
+        };
+        
+        session.account_name = Some("gaben".into());
+        session.store_new_guard_data(&response);
+        
+        let token = session.steam_guard_machine_token().unwrap();
+        
+        assert_eq!(token.account_name, "gaben");
+        assert_eq!(token.for_account("Gaben"), Some(b"guard-data".to_vec()));
+        assert_eq!(token.for_account("someone-else"), None);
+        assert_eq!(session.steam_guard_machine_token.as_deref(), Some(b"guard-data".as_slice()));
+        assert!(!format!("{session:?}").contains("guard-data"));
+    }
+    
+    #[test]
+    fn keeps_details_of_failed_poll() {
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser);
+        let mut start_session_response = CAuthentication_BeginAuthSessionViaCredentials_Response::new();
+        
+        start_session_response.set_interval(5.0);
+        session.start_session_response = Some(start_session_response);
+        
+        let error = AuthenticationClientError::WebAPI(crate::transports::web_api::Error::EResultWithMessage {
+            eresult: EResult::RateLimitExceeded,
+            message: "Too many attempts".into(),
+        });
+        
+        assert_eq!(session.poll_failure(&error), PollFailure {
+            eresult: Some(EResult::RateLimitExceeded),
+            extended_error_message: Some("Too many attempts".into()),
+            interval: Some(std::time::Duration::from_secs(5)),
+        });
+        assert!(matches!(LoginSessionError::from(error), LoginSessionError::RateLimited(EResult::RateLimitExceeded)));
+    }
+    
+    /// Creates an unsigned refresh token for `audience` expiring at `exp`.
+    fn refresh_token(audience: &[&str], exp: u64) -> String {
+        let payload = serde_json::json!({
+            "iss": "steam",
+            "sub": "76561197960287930",
+            "aud": audience,
+            "exp": exp,
+            "iat": 0,
+            "nbf": 0,
+            "oat": 0,
+            "jti": "0",
+            "per": 1,
+            "ip_subject": "127.0.0.1",
+            "ip_confirmer": "127.0.0.1",
+        });
+        
+        format!("e30.{}.sig", crate::helpers::encode_base64(payload.to_string()))
+    }
+    
+    #[test]
+    fn rejects_refresh_token_for_other_platform() {
+        let token = refresh_token(&["web", "renew", "derive"], 0);
+        let builder = || LoginSession::builder(
+            WebApiTransport::new(),
+            EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp,
+        );
+        let mut session = builder().build().unwrap();
+        
+        assert!(matches!(
+            session.set_refresh_token(token.clone()),
+            Err(LoginSessionError::PlatformTokenMismatch { audience, .. }) if audience.contains(&"web".to_string())
+        ));
+        
+        let mut session = builder().allow_platform_mismatch(true).build().unwrap();
+        
+        assert!(session.set_refresh_token(token).is_ok());
+    }
+    
+    #[tokio::test]
+    async fn refreshes_access_token_for_mismatched_platform() {
+        let token = refresh_token(&["mobile", "renew", "derive"], 0);
+        let mut request = CAuthentication_AccessToken_GenerateForApp_Request::new();
+        let mut response = CAuthentication_AccessToken_GenerateForApp_Response::new();
+        
+        request.set_refresh_token(token.clone());
+        request.set_steamid(76561197960287930);
+        request.set_renewal_type(ETokenRenewalType::k_ETokenRenewalType_None);
+        response.set_access_token(refresh_token(&["mobile"], 0));
+        
+        let transport = ReplayTransport::new(vec![RecordedExchange {
+            name: <CAuthentication_AccessToken_GenerateForApp_Request as ApiRequest>::NAME.into(),
+            request: crate::helpers::encode_base64(request.write_to_bytes().unwrap()),
+            response: Some(crate::helpers::encode_base64(response.write_to_bytes().unwrap())),
+            eresult: None,
+            error: None,
+        }]);
+        let mut session = LoginSession::builder(transport, EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser)
+            .allow_platform_mismatch(true)
+            .build()
+            .unwrap();
+        
+        session.set_refresh_token(token).unwrap();
+        session.refresh_access_token().await.unwrap();
+        
+        assert_eq!(session.get_access_token(), Some(&refresh_token(&["mobile"], 0)));
+    }
+    
+    #[test]
+    fn confirms_steamid_against_token_subject() {
+        let token = refresh_token(&["web", "renew", "derive"], 0);
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser);
+        let mut start_session_response = CAuthentication_BeginAuthSessionViaCredentials_Response::new();
+        
+        start_session_response.set_steamid(76561197960287931);
+        session.start_session_response = Some(start_session_response.clone());
+        
+        assert_eq!(session.steamid(), Some(SteamID::from(76561197960287931)));
+        assert!(matches!(
+            session.set_refresh_token(token.clone()),
+            Err(LoginSessionError::TokenIsForDifferentAccount),
+        ));
+        
+        // an unknown SteamID is taken from the token instead
+        start_session_response.set_steamid(0);
+        session.start_session_response = Some(start_session_response);
+        
+        assert_eq!(session.steamid(), None);
+        
+        session.set_refresh_token(token).unwrap();
+        
+        assert_eq!(session.steamid(), Some(SteamID::from(76561197960287930)));
+    }
+    
+    #[tokio::test]
+    async fn completes_authenticated_poll_result() {
+        let mut response = CAuthentication_PollAuthSessionStatus_Response::new();
+        
+        response.set_refresh_token(refresh_token(&["web", "renew", "derive"], 0));
+        response.set_access_token(refresh_token(&["web"], 0));
+        response.set_new_guard_data("guard-data".into());
+        
+        let mut session = device_code_session(ReplayTransport::new(vec![
+            poll_exchange(&response),
+        ]));
+        
+        session.account_name = Some("gaben".into());
+        
+        let result = session.do_poll().await.unwrap();
+        
+        assert!(result.is_authenticated());
+        assert_eq!(result.access_token, Some(refresh_token(&["web"], 0)));
+        assert_eq!(result.account_name.as_deref(), Some("gaben"));
+        assert_eq!(result.steamid, Some(SteamID::from(76561197960287930)));
+        assert_eq!(result.steam_guard_machine_token, Some(SteamGuardMachineToken {
+            account_name: "gaben".into(),
+            guard_data: "guard-data".into(),
+        }));
+    }
+    
+    #[tokio::test]
+    async fn keeps_only_refresh_token_without_auto_mint() {
+        let mut response = CAuthentication_PollAuthSessionStatus_Response::new();
+        
+        response.set_refresh_token(refresh_token(&["web", "renew", "derive"], 0));
+        response.set_access_token(refresh_token(&["web"], 0));
+        
+        let mut session = device_code_session(ReplayTransport::new(vec![
+            poll_exchange(&response),
+        ]));
+        
+        session.auto_mint_access_token = false;
+        
+        let result = session.do_poll().await.unwrap();
+        
+        assert!(result.is_authenticated());
+        assert_eq!(result.access_token, None);
+        assert_eq!(session.get_access_token(), None);
+        assert!(session.refresh_token.is_some());
+    }
+    
+    #[tokio::test]
+    async fn groups_access_token_cookies_by_domain() {
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp);
+        
+        session.set_refresh_token(refresh_token(&["mobile", "renew", "derive"], 0)).unwrap();
+        session.set_access_token(refresh_token(&["mobile"], 0)).unwrap();
+        
+        let cookies = session.get_web_cookies_by_domain(WebCookiesOptions::default()).await.unwrap();
+        
+        assert_eq!(cookies.len(), CookieDomain::DEFAULT.len());
+        
+        for domain in CookieDomain::DEFAULT {
+            assert!(cookies[&domain][0].starts_with("steamLoginSecure=76561197960287930%7C%7C"));
+            assert!(cookies[&domain][1].starts_with("sessionid="));
+        }
+    }
+    
+    #[test]
+    fn decodes_refresh_token_expiry() {
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp);
+        
+        assert!(session.refresh_token_expires_at().is_none());
+        
+        session.set_refresh_token(refresh_token(&["mobile", "renew", "derive"], 1717000000)).unwrap();
+        
+        assert_eq!(session.refresh_token_expires_at().unwrap().timestamp(), 1717000000);
+    }
+    
+    #[test]
+    fn decodes_access_token_platform() {
+        let mut session = build_session(WebApiTransport::new(), EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp);
+        
+        assert_eq!(session.access_token_platform(), None);
+        
+        session.set_access_token(refresh_token(&["web", "mobile"], 0)).unwrap();
+        
+        assert_eq!(session.access_token_platform(), Some(EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp));
+        
+        session.access_token = Some(refresh_token(&["web"], 0));
+        
+        assert_eq!(session.access_token_platform(), Some(EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser));
+        
+        session.access_token = Some(String::from("not a token"));
+        
+        assert_eq!(session.access_token_platform(), None);
+    }
+}
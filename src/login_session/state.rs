@@ -0,0 +1,98 @@
+use crate::enums::EAuthSessionGuardType;
+use crate::response::StartSessionResponse;
+
+/// The state of a [`super::LoginSession`].
+/// 
+/// A session starts as [`LoginSessionState::Idle`] and moves through the states as follows:
+/// 
+/// - `start_with_credentials` moves to [`LoginSessionState::Authenticated`] if no action is 
+///   needed, [`LoginSessionState::AwaitingGuard`] if Steam Guard actions are required, or 
+///   [`LoginSessionState::Pending`] otherwise.
+/// - `submit_steam_guard_code` moves to [`LoginSessionState::Authenticated`] once the code is 
+///   accepted and Steam issues tokens, or [`LoginSessionState::Pending`] if it has not yet.
+/// - `poll` and `poll_status` move to [`LoginSessionState::RemoteInteraction`] once a confirmer 
+///   has interacted with the session, [`LoginSessionState::AgreementRequired`] if an agreement 
+///   must be accepted first, [`LoginSessionState::Authenticated`] once tokens are issued, and 
+///   [`LoginSessionState::Expired`] when the login times out or Steam reports the session as 
+///   expired.
+/// - `set_refresh_token` moves to [`LoginSessionState::Authenticated`] when given a valid token.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LoginSessionState {
+    /// No login attempt has been started.
+    #[default]
+    Idle,
+    /// A login attempt has been started and is waiting to be confirmed.
+    Pending,
+    /// A login attempt has been started and is waiting for one of these Steam Guard actions.
+    AwaitingGuard(Vec<EAuthSessionGuardType>),
+    /// A confirmer has interacted with the session, e.g. the QR code was scanned, but the login 
+    /// has not been approved yet.
+    RemoteInteraction,
+    /// The user must accept an agreement at this URL before the login can complete. The crate 
+    /// can't accept it on the user's behalf; poll again once it has been accepted.
+    AgreementRequired(String),
+    /// The session holds a refresh token.
+    Authenticated,
+    /// The login attempt expired before it was confirmed.
+    Expired,
+}
+
+impl LoginSessionState {
+    /// Checks if the session is authenticated.
+    pub fn is_authenticated(&self) -> bool {
+        matches!(self, Self::Authenticated)
+    }
+}
+
+impl From<&StartSessionResponse> for LoginSessionState {
+    fn from(response: &StartSessionResponse) -> Self {
+        match response {
+            StartSessionResponse::Authenticated => Self::Authenticated,
+            StartSessionResponse::ActionRequired(actions) if !actions.is_empty() => {
+                Self::AwaitingGuard(actions
+                    .iter()
+                    .map(|action| action.r#type)
+                    .collect())
+            },
+            _ => Self::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::StartSessionResponseValidAction;
+    
+    #[test]
+    fn state_from_start_session_response() {
+        assert_eq!(
+            LoginSessionState::from(&StartSessionResponse::Authenticated),
+            LoginSessionState::Authenticated,
+        );
+        assert_eq!(
+            LoginSessionState::from(&StartSessionResponse::ActionRequired(Vec::new())),
+            LoginSessionState::Pending,
+        );
+        assert_eq!(
+            LoginSessionState::from(&StartSessionResponse::QrChallenge("https://s.team/q/1/1".into())),
+            LoginSessionState::Pending,
+        );
+        assert_eq!(
+            LoginSessionState::from(&StartSessionResponse::ActionRequired(vec![
+                StartSessionResponseValidAction {
+                    r#type: EAuthSessionGuardType::k_EAuthSessionGuardType_EmailCode,
+                    detail: Some("gmail.com".into()),
+                },
+                StartSessionResponseValidAction {
+                    r#type: EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceConfirmation,
+                    detail: None,
+                },
+            ])),
+            LoginSessionState::AwaitingGuard(vec![
+                EAuthSessionGuardType::k_EAuthSessionGuardType_EmailCode,
+                EAuthSessionGuardType::k_EAuthSessionGuardType_DeviceConfirmation,
+            ]),
+        );
+    }
+}
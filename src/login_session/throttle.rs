@@ -0,0 +1,64 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Spaces out requests made by every [`super::LoginSession`] sharing it, e.g. to stay under 
+/// Steam's per-IP rate limits when many accounts log in through the same transport.
+/// 
+/// Clones share the same schedule. Each request is given the next free slot, so concurrent 
+/// requests are sent at least `min_interval` apart regardless of which session makes them. 
+/// This is in addition to the poll interval given by Steam for each session.
+#[derive(Debug, Clone)]
+pub struct LoginThrottle {
+    min_interval: Duration,
+    next_at: Arc<Mutex<Option<Instant>>>,
+}
+
+impl LoginThrottle {
+    /// Creates a new [`LoginThrottle`] which sends requests at least `min_interval` apart.
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            next_at: Default::default(),
+        }
+    }
+    
+    /// Gets the minimum interval between requests.
+    pub fn min_interval(&self) -> Duration {
+        self.min_interval
+    }
+    
+    /// Waits for the next free slot to send a request.
+    pub async fn wait(&self) {
+        let now = Instant::now();
+        let slot = {
+            let mut next_at = self.next_at.lock().unwrap();
+            let slot = next_at.map_or(now, |next_at| next_at.max(now));
+            
+            *next_at = Some(slot + self.min_interval);
+            slot
+        };
+        
+        if slot > now {
+            async_std::task::sleep(slot - now).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[tokio::test]
+    async fn spaces_out_concurrent_requests() {
+        let throttle = LoginThrottle::new(Duration::from_millis(50));
+        let started_at = Instant::now();
+        
+        futures::future::join_all((0..3).map(|_| {
+            let throttle = throttle.clone();
+            
+            async move { throttle.wait().await }
+        })).await;
+        
+        assert!(started_at.elapsed() >= Duration::from_millis(100));
+    }
+}
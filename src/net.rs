@@ -1,4 +1,5 @@
 use crate::enums::EMsg;
+use crate::transports::web_api::HttpMethod;
 use crate::proto::steammessages_clientserver_login::CMsgClientHello;
 use crate::proto::custom::CAuthentication_BeginAuthSessionViaCredentials_Request_BinaryGuardData;
 use crate::proto::steammessages_auth_steamclient::{
@@ -24,27 +25,91 @@ pub trait ApiRequest: Sized + protobuf::Message + protobuf::MessageFull {
     const METHOD: &'static str;
     const VERSION: u32;
     const NAME: &'static str;
+    /// The HTTP method used to send the request using the Web API.
+    const HTTP_METHOD: HttpMethod = HttpMethod::Post;
     type Response: ApiResponse;
 }
 
+/// A service method implemented by the crate, as listed in [`SUPPORTED_METHODS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ApiMethod {
+    /// The service, e.g. `"Authentication"` for `IAuthenticationService`.
+    pub service: &'static str,
+    /// The method, e.g. `"PollAuthSessionStatus"`.
+    pub method: &'static str,
+    /// The version of the method.
+    pub version: u32,
+    /// The HTTP method used to send the request using the Web API.
+    pub http_method: HttpMethod,
+}
+
+impl ApiMethod {
+    /// Gets the [`ApiMethod`] sent for requests of type `Msg`.
+    pub const fn of<Msg: ApiRequest>() -> Self {
+        Self {
+            service: Msg::INTERFACE,
+            method: Msg::METHOD,
+            version: Msg::VERSION,
+            http_method: Msg::HTTP_METHOD,
+        }
+    }
+    
+    /// Gets the Web API path of the method, e.g. 
+    /// `"IAuthenticationService/PollAuthSessionStatus/v1"`.
+    pub fn pathname(&self) -> String {
+        format!("I{}Service/{}/v{}", self.service, self.method, self.version)
+    }
+}
+
+/// The `IAuthenticationService` methods implemented by the crate, i.e. those with an 
+/// [`ApiRequest`] type. The `ClientHello` which starts a websocket connection isn't included as 
+/// it isn't a service method.
+pub const SUPPORTED_METHODS: &[ApiMethod] = &[
+    ApiMethod::of::<CAuthentication_AccessToken_GenerateForApp_Request>(),
+    ApiMethod::of::<CAuthentication_BeginAuthSessionViaCredentials_Request_BinaryGuardData>(),
+    ApiMethod::of::<CAuthentication_UpdateAuthSessionWithSteamGuardCode_Request>(),
+    ApiMethod::of::<CAuthentication_UpdateAuthSessionWithMobileConfirmation_Request>(),
+    ApiMethod::of::<CAuthentication_GetAuthSessionInfo_Request>(),
+    ApiMethod::of::<CAuthentication_GetPasswordRSAPublicKey_Request>(),
+    ApiMethod::of::<CAuthentication_PollAuthSessionStatus_Request>(),
+];
+
 pub trait ApiResponse: Sized {
     fn parse_from_reader(reader: &mut dyn Read) -> protobuf::Result<Self>;
+    
+    /// Encodes the response to its protobuf-encoded body.
+    fn to_bytes(&self) -> protobuf::Result<Vec<u8>>;
+}
+
+/// A typed response along with its protobuf-encoded body, for reading fields the typed response 
+/// doesn't cover. See [`Transport::send_request_raw`](crate::transports::Transport::send_request_raw).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawResponse<R> {
+    /// The typed response.
+    pub response: R,
+    /// The protobuf-encoded body of the response.
+    pub body: Vec<u8>,
 }
 
 impl ApiResponse for () {
     fn parse_from_reader(_reader: &mut dyn Read) -> protobuf::Result<Self> {
         Ok(())
     }
+    
+    fn to_bytes(&self) -> protobuf::Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
 }
 
 macro_rules! api_method {
-    (($interface:literal, $method:literal, $version:expr) => $req:path, $res:path) => {
+    (($interface:literal, $method:literal, $version:expr $(, $http_method:ident)?) => $req:path, $res:path) => {
         impl ApiRequest for $req {
             const KIND: EMsg = EMsg::ServiceMethodCallFromClientNonAuthed;
             const INTERFACE: &'static str = $interface;
             const METHOD: &'static str = $method;
             const VERSION: u32 = $version;
             const NAME: &'static str = concat!($interface, ".", $method, "#", $version);
+            $(const HTTP_METHOD: HttpMethod = HttpMethod::$http_method;)?
             type Response = $res;
         }
         
@@ -52,6 +117,10 @@ macro_rules! api_method {
             fn parse_from_reader(reader: &mut dyn Read) -> protobuf::Result<Self> {
                 <Self as protobuf::Message>::parse_from_reader(reader)
             }
+            
+            fn to_bytes(&self) -> protobuf::Result<Vec<u8>> {
+                <Self as protobuf::Message>::write_to_bytes(self)
+            }
         }
     };
     (($interface:literal, $method:literal, $version:expr) => $req:path) => {
@@ -72,6 +141,27 @@ api_method!(("Authentication", "BeginAuthSessionViaCredentials", 1) => CAuthenti
 api_method!(("Authentication", "UpdateAuthSessionWithSteamGuardCode", 1) => CAuthentication_UpdateAuthSessionWithSteamGuardCode_Request, CAuthentication_UpdateAuthSessionWithSteamGuardCode_Response);
 api_method!(("Authentication", "UpdateAuthSessionWithMobileConfirmation", 1) => CAuthentication_UpdateAuthSessionWithMobileConfirmation_Request, CAuthentication_UpdateAuthSessionWithMobileConfirmation_Response);
 api_method!(("Authentication", "GetAuthSessionInfo", 1) => CAuthentication_GetAuthSessionInfo_Request, CAuthentication_GetAuthSessionInfo_Response);
-api_method!(("Authentication", "GetPasswordRSAPublicKey", 1) => CAuthentication_GetPasswordRSAPublicKey_Request, CAuthentication_GetPasswordRSAPublicKey_Response);
+api_method!(("Authentication", "GetPasswordRSAPublicKey", 1, Get) => CAuthentication_GetPasswordRSAPublicKey_Request, CAuthentication_GetPasswordRSAPublicKey_Response);
 api_method!(("Authentication", "PollAuthSessionStatus", 1) => CAuthentication_PollAuthSessionStatus_Request, CAuthentication_PollAuthSessionStatus_Response);
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn lists_supported_methods() {
+        let method = SUPPORTED_METHODS
+            .iter()
+            .find(|method| method.method == "GetPasswordRSAPublicKey")
+            .unwrap();
+        
+        assert_eq!(method.pathname(), "IAuthenticationService/GetPasswordRSAPublicKey/v1");
+        assert_eq!(method.http_method, HttpMethod::Get);
+        assert!(SUPPORTED_METHODS.contains(&ApiMethod::of::<CAuthentication_PollAuthSessionStatus_Request>()));
+        assert!(SUPPORTED_METHODS
+            .iter()
+            .filter(|method| method.method != "GetPasswordRSAPublicKey")
+            .all(|method| method.service == "Authentication" && method.http_method == HttpMethod::Post));
+    }
+}
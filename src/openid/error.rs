@@ -0,0 +1,22 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("reqwest error: {}", .0)]
+    Reqwest(#[from] reqwest::Error),
+    #[error("OpenID assertion is missing \"{}\"", .0)]
+    MissingParameter(&'static str),
+    #[error("Unexpected OpenID mode \"{}\"; the login may have been cancelled", .0)]
+    UnexpectedMode(String),
+    #[error("OpenID assertion is from unexpected endpoint \"{}\"", .0)]
+    UnexpectedEndpoint(String),
+    #[error("OpenID assertion was returned to \"{}\" instead of \"{}\"", .actual, .expected)]
+    ReturnToMismatch {
+        /// The return URL the login was started with.
+        expected: String,
+        /// The return URL in the assertion.
+        actual: String,
+    },
+    #[error("OpenID assertion has invalid claimed ID \"{}\"", .0)]
+    InvalidClaimedId(String),
+    #[error("Steam rejected the OpenID assertion")]
+    InvalidAssertion,
+}
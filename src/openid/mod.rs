@@ -0,0 +1,224 @@
+//! # Sign in through Steam
+//! 
+//! Steam's OpenID 2.0 login, for websites that redirect users to Steam's hosted login page 
+//! rather than logging in on their behalf. This is separate from [`LoginSession`], which 
+//! authenticates using `IAuthenticationService`: no tokens are issued, Steam only asserts which 
+//! account the user logged in to.
+//! 
+//! Redirect the user to [`login_url`]. After logging in, Steam redirects them back to 
+//! `return_to` with the assertion in the query string, which is checked using 
+//! [`verify_assertion`].
+//! 
+//! ## Examples
+//! ```no_run
+//! use steam_session::openid;
+//! 
+//! # async fn example(callback_query: &str) -> Result<(), openid::Error> {
+//! let return_to = "https://example.com/auth/steam/callback"; 
+//! let redirect_to = openid::login_url(return_to, "https://example.com/"); 
+//! // ...after the user is redirected back to `return_to` 
+//! let steamid = openid::verify_assertion(&reqwest::Client::new(), callback_query, return_to).await?;
+//! # Ok(())
+//! # }
+//! ```
+//! 
+//! [`LoginSession`]: crate::login_session::LoginSession
+
+mod error;
+
+pub use error::Error;
+
+use std::collections::HashMap;
+use reqwest::Client;
+use steamid_ng::SteamID;
+use url::{Url, form_urlencoded};
+
+/// The endpoint of Steam's OpenID provider.
+pub const OPENID_LOGIN_URL: &str = "https://steamcommunity.com/openid/login";
+
+const OPENID_NS: &str = "http://specs.openid.net/auth/2.0";
+const OPENID_IDENTIFIER_SELECT: &str = "http://specs.openid.net/auth/2.0/identifier_select";
+const CLAIMED_ID_PREFIX: &str = "https://steamcommunity.com/openid/id/";
+
+/// Builds the URL of Steam's login page for the user to be redirected to. Once logged in, the 
+/// user is redirected to `return_to`, which must be within `realm`, e.g. 
+/// `"https://example.com/"`. The realm is shown to the user on the login page.
+pub fn login_url(return_to: &str, realm: &str) -> String {
+    let mut url = Url::parse(OPENID_LOGIN_URL).expect("OPENID_LOGIN_URL is a valid URL");
+    
+    url.query_pairs_mut()
+        .append_pair("openid.ns", OPENID_NS)
+        .append_pair("openid.mode", "checkid_setup")
+        .append_pair("openid.return_to", return_to)
+        .append_pair("openid.realm", realm)
+        .append_pair("openid.identity", OPENID_IDENTIFIER_SELECT)
+        .append_pair("openid.claimed_id", OPENID_IDENTIFIER_SELECT);
+    
+    url.into()
+}
+
+/// A positive OpenID assertion which has been checked locally but not yet confirmed by Steam.
+#[derive(Debug, Clone)]
+pub struct Assertion {
+    /// The SteamID of the account the user logged in to.
+    pub steamid: SteamID,
+    params: HashMap<String, String>,
+}
+
+/// Parses the assertion in the query string Steam redirected the user back with. Checks that it 
+/// is a positive assertion from Steam for `return_to`, the URL passed to [`login_url`].
+/// 
+/// This doesn't check the signature, so the SteamID can't be trusted until the assertion is 
+/// confirmed using [`verify_assertion`].
+pub fn parse_assertion(query: &str, return_to: &str) -> Result<Assertion, Error> {
+    let params = form_urlencoded::parse(query.trim_start_matches('?').as_bytes())
+        .into_owned()
+        .collect::<HashMap<_, _>>();
+    let param = |name: &'static str| params
+        .get(name)
+        .map(String::as_str)
+        .ok_or(Error::MissingParameter(name));
+    let mode = param("openid.mode")?;
+    
+    if mode != "id_res" {
+        return Err(Error::UnexpectedMode(mode.into()));
+    }
+    
+    let op_endpoint = param("openid.op_endpoint")?;
+    
+    if op_endpoint != OPENID_LOGIN_URL {
+        return Err(Error::UnexpectedEndpoint(op_endpoint.into()));
+    }
+    
+    let actual_return_to = param("openid.return_to")?;
+    
+    if actual_return_to != return_to {
+        return Err(Error::ReturnToMismatch {
+            expected: return_to.into(),
+            actual: actual_return_to.into(),
+        });
+    }
+    
+    let claimed_id = param("openid.claimed_id")?;
+    
+    if param("openid.identity")? != claimed_id {
+        return Err(Error::InvalidClaimedId(claimed_id.into()));
+    }
+    
+    let steamid = claimed_id
+        .strip_prefix(CLAIMED_ID_PREFIX)
+        .and_then(|steamid| steamid.parse::<u64>().ok())
+        .map(SteamID::from)
+        .ok_or_else(|| Error::InvalidClaimedId(claimed_id.into()))?;
+    
+    param("openid.sig")?;
+    param("openid.signed")?;
+    
+    Ok(Assertion {
+        steamid,
+        params,
+    })
+}
+
+/// Verifies the assertion in the query string Steam redirected the user back with, returning 
+/// the SteamID of the account the user logged in to. The assertion is parsed using 
+/// [`parse_assertion`] and then confirmed by Steam.
+pub async fn verify_assertion(
+    client: &Client,
+    query: &str,
+    return_to: &str,
+) -> Result<SteamID, Error> {
+    let Assertion {
+        steamid,
+        mut params,
+    } = parse_assertion(query, return_to)?;
+    
+    params.insert("openid.mode".into(), "check_authentication".into());
+    
+    let body = client.post(OPENID_LOGIN_URL)
+        .form(&params)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    
+    if !is_valid(&body) {
+        return Err(Error::InvalidAssertion);
+    }
+    
+    Ok(steamid)
+}
+
+/// Checks if a `check_authentication` response, in key-value form, says the assertion is valid.
+fn is_valid(body: &str) -> bool {
+    body
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .any(|(key, value)| key.trim() == "is_valid" && value.trim() == "true")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const RETURN_TO: &str = "https://example.com/auth/steam/callback";
+    
+    fn assertion_query(claimed_id: &str) -> String {
+        form_urlencoded::Serializer::new(String::new())
+            .append_pair("openid.ns", OPENID_NS)
+            .append_pair("openid.mode", "id_res")
+            .append_pair("openid.op_endpoint", OPENID_LOGIN_URL)
+            .append_pair("openid.claimed_id", claimed_id)
+            .append_pair("openid.identity", claimed_id)
+            .append_pair("openid.return_to", RETURN_TO)
+            .append_pair("openid.response_nonce", "2024-01-01T00:00:00Zabc")
+            .append_pair("openid.assoc_handle", "1234567890")
+            .append_pair("openid.signed", "signed,op_endpoint,claimed_id,identity,return_to,response_nonce,assoc_handle")
+            .append_pair("openid.sig", "c2ln")
+            .finish()
+    }
+    
+    #[test]
+    fn builds_login_url() {
+        let url = Url::parse(&login_url(RETURN_TO, "https://example.com/")).unwrap();
+        let params = url.query_pairs().into_owned().collect::<HashMap<_, _>>();
+        
+        assert_eq!(url.host_str(), Some("steamcommunity.com"));
+        assert_eq!(params["openid.mode"], "checkid_setup");
+        assert_eq!(params["openid.return_to"], RETURN_TO);
+        assert_eq!(params["openid.realm"], "https://example.com/");
+    }
+    
+    #[test]
+    fn parses_assertion() {
+        let query = assertion_query("https://steamcommunity.com/openid/id/76561197960287930");
+        let assertion = parse_assertion(&format!("?{query}"), RETURN_TO).unwrap();
+        
+        assert_eq!(u64::from(assertion.steamid), 76561197960287930);
+    }
+    
+    #[test]
+    fn rejects_mismatched_assertion() {
+        let query = assertion_query("https://steamcommunity.com/openid/id/76561197960287930");
+        
+        assert!(matches!(
+            parse_assertion(&query, "https://example.com/other"),
+            Err(Error::ReturnToMismatch { .. }),
+        ));
+        
+        let query = assertion_query("https://example.com/openid/id/76561197960287930");
+        
+        assert!(matches!(parse_assertion(&query, RETURN_TO), Err(Error::InvalidClaimedId(_))));
+        assert!(matches!(
+            parse_assertion("openid.mode=cancel", RETURN_TO),
+            Err(Error::UnexpectedMode(mode)) if mode == "cancel",
+        ));
+    }
+    
+    #[test]
+    fn reads_check_authentication_response() {
+        assert!(is_valid("ns:http://specs.openid.net/auth/2.0\nis_valid:true\n"));
+        assert!(!is_valid("ns:http://specs.openid.net/auth/2.0\nis_valid:false\n"));
+    }
+}
@@ -1,15 +1,53 @@
-use crate::enums::{ESessionPersistence, EAuthTokenPlatformType};
+use crate::enums::{ESessionPersistence, EAuthTokenPlatformType, WebsiteId, CookieDomain};
+use crate::helpers::Redacted;
+use crate::authentication_client::DeviceDetails;
 
-#[derive(Debug, Clone)]
+/// The details for starting a login session with credentials. The `Debug` output redacts the 
+/// password, Steam Guard code and machine token.
+#[derive(Clone)]
 pub struct StartLoginSessionWithCredentialsDetails {
     pub account_name: String,
     pub password: String,
     pub platform_type: EAuthTokenPlatformType,
+    /// Whether to request a long-lived refresh token, like "Remember me" when signing in. 
+    /// [`ESessionPersistence::k_ESessionPersistence_Persistent`] tokens are valid for around 200 
+    /// days, while [`ESessionPersistence::k_ESessionPersistence_Ephemeral`] tokens expire much 
+    /// sooner. Defaults to persistent. The expiry of the token received can be checked using 
+    /// [`LoginSession::refresh_token_expires_at`](crate::login_session::LoginSession::refresh_token_expires_at).
     pub persistence: Option<ESessionPersistence>,
     pub steam_guard_machine_token: Option<Vec<u8>>,
     pub steam_guard_code: Option<String>,
+    /// The base64-encoded `shared_secret` of the account's mobile authenticator. If Steam asks 
+    /// for a code from the authenticator and no `steam_guard_code` was supplied, one is generated 
+    /// using the session's time offset, see 
+    /// [`LoginSession::set_time_offset`](crate::login_session::LoginSession::set_time_offset).
+    pub shared_secret: Option<String>,
     pub machine_id: Option<Vec<u8>>,
     pub user_agent: Option<&'static str>,
+    /// A previously fetched RSA key for this account. If it is still fresh it is used to encrypt 
+    /// the password instead of fetching a new key.
+    pub rsa_key: Option<RsaKey>,
+    /// The website the login is for. Defaults to the website of the platform type, see 
+    /// [`WebsiteId::for_platform`].
+    pub website_id: Option<WebsiteId>,
+}
+
+impl std::fmt::Debug for StartLoginSessionWithCredentialsDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StartLoginSessionWithCredentialsDetails")
+            .field("account_name", &self.account_name)
+            .field("password", &Redacted(&self.password))
+            .field("platform_type", &self.platform_type)
+            .field("persistence", &self.persistence)
+            .field("steam_guard_machine_token", &self.steam_guard_machine_token.as_ref().map(Redacted))
+            .field("steam_guard_code", &self.steam_guard_code.as_ref().map(Redacted))
+            .field("shared_secret", &self.shared_secret.as_ref().map(Redacted))
+            .field("machine_id", &self.machine_id)
+            .field("user_agent", &self.user_agent)
+            .field("rsa_key", &self.rsa_key)
+            .field("website_id", &self.website_id)
+            .finish()
+    }
 }
 
 impl Default for StartLoginSessionWithCredentialsDetails {
@@ -21,12 +59,32 @@ impl Default for StartLoginSessionWithCredentialsDetails {
             persistence: None,
             steam_guard_machine_token: None,
             steam_guard_code: None,
+            shared_secret: None,
             machine_id: None,
             user_agent: None,
+            rsa_key: None,
+            website_id: None,
         }
     }
 }
 
+/// An RSA public key used to encrypt an account's password, as returned by 
+/// `GetPasswordRSAPublicKey`.
+/// 
+/// Steam's `timestamp` identifies the key rather than when it was issued, so freshness is judged 
+/// by `fetched_at`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RsaKey {
+    /// The hex-encoded modulus.
+    pub modulus: String,
+    /// The hex-encoded exponent.
+    pub exponent: String,
+    /// The key timestamp, sent along with the encrypted password.
+    pub timestamp: u64,
+    /// When the key was fetched.
+    pub fetched_at: chrono::DateTime<chrono::Utc>,
+}
+
 #[derive(Debug, Clone)]
 pub struct StartAuthSessionWithCredentialsRequest {
     pub account_name: String,
@@ -36,6 +94,12 @@ pub struct StartAuthSessionWithCredentialsRequest {
     pub platform_type: EAuthTokenPlatformType,
     pub persistence: ESessionPersistence,
     pub steam_guard_machine_token: Option<Vec<u8>>,
+    pub website_id: Option<WebsiteId>,
+    /// The machine ID to send when logging in with 
+    /// [`EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient`].
+    pub machine_id: Option<Vec<u8>>,
+    /// The device details to send in place of the platform type's defaults.
+    pub device_details: Option<DeviceDetails>,
 }
 
 #[derive(Debug, Clone)]
@@ -55,4 +119,31 @@ pub struct ApproveAuthSessionRequest {
     pub steamid: u64,
     pub approve: bool,
     pub persistence: ESessionPersistence,
+}
+
+/// Options for minting an access token from a refresh token.
+#[derive(Debug, Clone, Default)]
+pub struct GenerateAccessTokenOptions {
+    /// The audience the access token is required to be valid for, e.g. `"web"` or `"mobile"`. 
+    /// If not set, the audience of the refresh token is accepted as is.
+    pub audience: Option<String>,
+    /// Whether to also request a renewed refresh token.
+    pub renew_refresh: bool,
+}
+
+/// Options for getting web cookies.
+#[derive(Debug, Clone, Default)]
+pub struct WebCookiesOptions {
+    /// The audience required of the access token used for the cookies. Defaults to the audience 
+    /// of the session's platform type.
+    pub access_token_audience: Option<String>,
+    /// The website to get cookies for when logging in with 
+    /// [`EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser`]. Must be a website with 
+    /// an origin, see [`WebsiteId::origin`]. Defaults to [`WebsiteId::Community`].
+    pub website_id: Option<WebsiteId>,
+    /// The domains to get cookies for when logging in with 
+    /// [`EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser`]. Transfers to other 
+    /// domains are skipped. Defaults to every domain Steam returns for `get_web_cookies`, and to 
+    /// [`CookieDomain::DEFAULT`] for `get_web_cookies_by_domain`.
+    pub domains: Option<Vec<CookieDomain>>,
 }
\ No newline at end of file
@@ -1,4 +1,11 @@
-use steam_session_proto::steammessages_auth_steamclient::EAuthSessionGuardType;
+use crate::enums::EResult;
+use crate::helpers::Redacted;
+use std::time::Duration;
+use steamid_ng::SteamID;
+use steam_session_proto::steammessages_auth_steamclient::{
+    EAuthSessionGuardType,
+    CAuthentication_PollAuthSessionStatus_Response,
+};
 
 #[derive(Debug, Clone)]
 pub struct StartSessionResponseValidAction {
@@ -47,4 +54,244 @@ impl StartSessionResponse {
             _ => false,
         }
     }
-}
\ No newline at end of file
+}
+
+/// The result of polling the status of an auth session. The `Debug` output redacts tokens and 
+/// guard data.
+/// 
+/// When returned by [`LoginSession`](crate::login_session::LoginSession), the result of the 
+/// poll which completes the login holds everything needed to persist it: the tokens, the 
+/// SteamID, the account name and the machine token if one was issued.
+#[derive(Clone, Default)]
+pub struct PollResult {
+    /// If the challenge is old, this is the new client ID.
+    pub new_client_id: Option<u64>,
+    /// If the challenge is old, this is the new challenge URL to re-render for mobile 
+    /// confirmation.
+    pub new_challenge_url: Option<String>,
+    /// If the login has been confirmed, this is the new refresh token.
+    pub refresh_token: Option<String>,
+    /// If the login has been confirmed, this is a new access token subordinate to the refresh 
+    /// token. If Steam didn't issue one alongside the refresh token, this is the access token 
+    /// [`LoginSession`](crate::login_session::LoginSession) fetched instead, if any.
+    pub access_token: Option<String>,
+    /// Whether the auth session appears to have had remote interaction from a potential 
+    /// confirmer, e.g. the QR code was scanned.
+    pub had_remote_interaction: bool,
+    /// The account name of the authenticating account. If Steam didn't include it, this is the 
+    /// account name the login was started with, set by 
+    /// [`LoginSession`](crate::login_session::LoginSession).
+    pub account_name: Option<String>,
+    /// If the login has been confirmed, this may contain a machine token (guard data) which can 
+    /// be supplied as `steam_guard_machine_token` on future logins. The session also keeps it, 
+    /// see [`LoginSession::steam_guard_machine_token`](crate::login_session::LoginSession::steam_guard_machine_token).
+    pub new_guard_data: Option<String>,
+    /// A URL to an agreement the user needs to accept before the login can complete.
+    pub agreement_session_url: Option<String>,
+    /// How long to wait before polling again. Poll responses don't include an interval, so this 
+    /// is the interval Steam gave when the login was started, set by 
+    /// [`LoginSession`](crate::login_session::LoginSession).
+    pub interval: Option<Duration>,
+    /// The SteamID of the authenticating account. Poll responses don't include a SteamID, so 
+    /// this is the SteamID Steam gave when the login was started, or the subject of the tokens 
+    /// once they're issued, set by [`LoginSession`](crate::login_session::LoginSession).
+    pub steamid: Option<SteamID>,
+    /// The guard data issued by this poll bound to the account it was issued for, set by 
+    /// [`LoginSession`](crate::login_session::LoginSession) if the account name is known.
+    pub steam_guard_machine_token: Option<SteamGuardMachineToken>,
+}
+
+impl std::fmt::Debug for PollResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PollResult")
+            .field("new_client_id", &self.new_client_id)
+            .field("new_challenge_url", &self.new_challenge_url)
+            .field("refresh_token", &self.refresh_token.as_ref().map(Redacted))
+            .field("access_token", &self.access_token.as_ref().map(Redacted))
+            .field("had_remote_interaction", &self.had_remote_interaction)
+            .field("account_name", &self.account_name)
+            .field("new_guard_data", &self.new_guard_data.as_ref().map(Redacted))
+            .field("agreement_session_url", &self.agreement_session_url)
+            .field("interval", &self.interval)
+            .field("steamid", &self.steamid)
+            .field("steam_guard_machine_token", &self.steam_guard_machine_token)
+            .finish()
+    }
+}
+
+impl PollResult {
+    /// Checks if the login has been confirmed and tokens were issued.
+    pub fn is_authenticated(&self) -> bool {
+        self.refresh_token.is_some()
+    }
+}
+
+impl From<CAuthentication_PollAuthSessionStatus_Response> for PollResult {
+    fn from(response: CAuthentication_PollAuthSessionStatus_Response) -> Self {
+        fn non_empty(value: Option<String>) -> Option<String> {
+            value.filter(|value| !value.is_empty())
+        }
+        
+        Self {
+            new_client_id: response.new_client_id.filter(|client_id| *client_id != 0),
+            new_challenge_url: non_empty(response.new_challenge_url),
+            refresh_token: non_empty(response.refresh_token),
+            access_token: non_empty(response.access_token),
+            had_remote_interaction: response.had_remote_interaction.unwrap_or(false),
+            account_name: non_empty(response.account_name),
+            new_guard_data: non_empty(response.new_guard_data),
+            agreement_session_url: non_empty(response.agreement_session_url),
+            interval: None,
+            steamid: None,
+            steam_guard_machine_token: None,
+        }
+    }
+}
+
+/// Details of a failed poll, as returned by 
+/// [`LoginSession::last_poll_failure`](crate::login_session::LoginSession::last_poll_failure). 
+/// These are kept even if the returned error is converted into a variant which doesn't carry 
+/// them, such as [`LoginSessionError::RateLimited`](crate::login_session::LoginSessionError::RateLimited).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollFailure {
+    /// The result the poll failed with, if any.
+    pub eresult: Option<EResult>,
+    /// Steam's explanation of the failure, if it sent one, e.g. for displaying to the user.
+    pub extended_error_message: Option<String>,
+    /// How long to wait before polling again.
+    pub interval: Option<Duration>,
+}
+
+/// A Steam Guard machine token issued after verifying a login from a new device, bound to the 
+/// account it was issued for. The `Debug` output redacts the guard data.
+/// 
+/// Steam only issues this once per device. Persist it, e.g. alongside the refresh token, and 
+/// supply [`SteamGuardMachineToken::guard_data`] as `steam_guard_machine_token` the next time 
+/// the same account logs in so that Steam Guard isn't asked for again. It has no effect for other 
+/// accounts.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SteamGuardMachineToken {
+    /// The account name of the account the token was issued for.
+    pub account_name: String,
+    /// The guard data.
+    pub guard_data: String,
+}
+
+impl SteamGuardMachineToken {
+    /// Gets the guard data if the token was issued for `account_name`, for supplying as 
+    /// `steam_guard_machine_token`.
+    pub fn for_account(&self, account_name: &str) -> Option<Vec<u8>> {
+        if self.account_name.eq_ignore_ascii_case(account_name) {
+            Some(self.guard_data.clone().into_bytes())
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Debug for SteamGuardMachineToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SteamGuardMachineToken")
+            .field("account_name", &self.account_name)
+            .field("guard_data", &Redacted(&self.guard_data))
+            .finish()
+    }
+}
+
+/// A cookie for use on the Steam websites, as returned by `get_web_cookies`. The `Debug` output 
+/// redacts the value, as cookies such as `steamLoginSecure` contain an access token.
+#[derive(Clone, PartialEq, Eq)]
+pub struct WebCookie {
+    /// The name of the cookie, e.g. `steamLoginSecure`.
+    pub name: String,
+    /// The value of the cookie.
+    pub value: String,
+    /// The domain the cookie is for, if set.
+    pub domain: Option<String>,
+    /// The path the cookie is for, if set.
+    pub path: Option<String>,
+    /// Whether the cookie is only sent over HTTPS.
+    pub secure: bool,
+    /// Whether the cookie is hidden from scripts.
+    pub http_only: bool,
+}
+
+impl WebCookie {
+    /// Parses a cookie from a `Set-Cookie` style string, e.g. 
+    /// `"sessionid=abc; Path=/; Secure; Domain=steamcommunity.com"`.
+    pub fn parse(cookie: &str) -> Result<Self, cookie::ParseError> {
+        let cookie = cookie::Cookie::parse(cookie)?;
+        
+        Ok(Self {
+            name: cookie.name().into(),
+            value: cookie.value().into(),
+            domain: cookie.domain().map(String::from),
+            path: cookie.path().map(String::from),
+            secure: cookie.secure().unwrap_or(false),
+            http_only: cookie.http_only().unwrap_or(false),
+        })
+    }
+}
+
+impl std::fmt::Debug for WebCookie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebCookie")
+            .field("name", &self.name)
+            .field("value", &Redacted(&self.value))
+            .field("domain", &self.domain)
+            .field("path", &self.path)
+            .field("secure", &self.secure)
+            .field("http_only", &self.http_only)
+            .finish()
+    }
+}
+
+impl std::str::FromStr for WebCookie {
+    type Err = cookie::ParseError;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl From<WebCookie> for cookie::Cookie<'static> {
+    fn from(web_cookie: WebCookie) -> Self {
+        let mut builder = cookie::Cookie::build((web_cookie.name, web_cookie.value))
+            .secure(web_cookie.secure)
+            .http_only(web_cookie.http_only);
+        
+        if let Some(domain) = web_cookie.domain {
+            builder = builder.domain(domain);
+        }
+        
+        if let Some(path) = web_cookie.path {
+            builder = builder.path(path);
+        }
+        
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn parses_web_cookie() {
+        let cookie = WebCookie::parse("steamLoginSecure=76561197960287930%7C%7Ctoken; Path=/; Secure; HttpOnly; SameSite=None; Domain=steamcommunity.com").unwrap();
+        
+        assert_eq!(cookie, WebCookie {
+            name: "steamLoginSecure".into(),
+            value: "76561197960287930%7C%7Ctoken".into(),
+            domain: Some("steamcommunity.com".into()),
+            path: Some("/".into()),
+            secure: true,
+            http_only: true,
+        });
+        
+        let cookie = WebCookie::parse("sessionid=abc").unwrap();
+        
+        assert_eq!(cookie.domain, None);
+        assert!(!cookie.secure);
+    }
+}
@@ -3,6 +3,8 @@ use serde::{Deserialize, Deserializer};
 use serde::de;
 use serde_json::Value;
 
+/// Deserializes an enum from its numeric value, given as a number or a string. `null` gives 
+/// `None`.
 pub fn from_number_or_string_option<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
 where
     T: TryFromPrimitive + TryFrom<i32>,
@@ -26,6 +28,7 @@ where
     }
 }
 
+/// Deserializes an enum from its numeric value, given as a number or a string.
 pub fn from_number_or_string<'de, D, T>(deserializer: D) -> Result<T, D::Error>
 where
     T: TryFromPrimitive + TryFrom<i32>,
@@ -42,4 +45,16 @@ where
     }?;
     
     T::try_from(number).map_err(|_| de::Error::custom("failed to convert from primitive"))
-}
\ No newline at end of file
+}
+
+/// Deserializes an `i64` given as a number or a string, e.g. a timestamp.
+pub fn from_i64_number_or_string<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: Deserializer<'de>
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(s) => s.parse::<i64>().map_err(de::Error::custom),
+        Value::Number(num) => num.as_i64().ok_or_else(|| de::Error::custom("invalid number")),
+        _ => Err(de::Error::custom("not a number")),
+    }
+}
@@ -0,0 +1,18 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("reqwest error: {}", .0)]
+    Reqwest(#[from] reqwest::Error),
+    #[error("Decode error: {}", .0)]
+    Decode(#[from] crate::helpers::DecodeError),
+    #[error("Shared secret is empty")]
+    EmptySecret,
+    // boxed so the error stays small for code generation
+    #[error("Login approver error: {}", .0)]
+    LoginApprover(Box<crate::login_approver::Error>),
+}
+
+impl From<crate::login_approver::Error> for Error {
+    fn from(error: crate::login_approver::Error) -> Self {
+        Self::LoginApprover(Box::new(error))
+    }
+}
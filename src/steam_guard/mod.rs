@@ -0,0 +1,274 @@
+//! # Steam Guard
+//! 
+//! Generates Steam Guard codes from a mobile authenticator's `shared_secret`. Codes are only 
+//! accepted within a short window of Steam's server time, so on machines with clock drift the 
+//! difference between local and Steam time should be supplied as a time offset. This can be 
+//! fetched using [`fetch_server_time_offset`], or managed automatically using 
+//! [`TimeOffsetCache`].
+//! 
+//! ## Examples
+//! ```no_run
+//! use steam_session::steam_guard::TimeOffsetCache;
+//! 
+//! # async fn example() -> Result<(), steam_session::steam_guard::Error> {
+//! let time_offsets = TimeOffsetCache::new(reqwest::Client::new());
+//! let code = time_offsets.generate_auth_code("000000000000000000000000000=").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod error;
+
+pub use error::Error;
+
+use crate::clock::{Clock, SystemClock};
+use crate::enums::ESessionPersistence;
+use crate::login_approver::LoginApprover;
+use crate::request::ApproveAuthSessionRequest;
+use crate::helpers::decode_base64;
+use crate::serializers::from_i64_number_or_string;
+use crate::types::DateTime;
+use std::sync::{Arc, Mutex};
+use chrono::Duration;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::Deserialize;
+use sha1::Sha1;
+
+type HmacSha1 = Hmac<Sha1>;
+
+const QUERY_TIME_URL: &str = "https://api.steampowered.com/ITwoFactorService/QueryTime/v1/";
+/// The version of the mobile confirmation signature.
+const MOBILE_CONFIRMATION_VERSION: u16 = 1;
+/// The characters used in Steam Guard codes.
+const CHARS: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
+/// How long a fetched time offset is cached by default.
+const DEFAULT_TIME_OFFSET_TTL_MINUTES: i64 = 60;
+
+#[derive(Debug, Deserialize)]
+struct QueryTimeResponse {
+    response: QueryTimeResponseBody,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryTimeResponseBody {
+    #[serde(deserialize_with = "from_i64_number_or_string")]
+    server_time: i64,
+}
+
+/// Fetches the number of seconds the local clock is **behind** Steam's servers using 
+/// `ITwoFactorService/QueryTime`. Pass the result as the `time_offset` of 
+/// [`generate_auth_code`].
+pub async fn fetch_server_time_offset(client: &Client) -> Result<i64, Error> {
+    fetch_server_time_offset_with_clock(client, &SystemClock).await
+}
+
+/// Same as [`fetch_server_time_offset`], measuring the offset against `clock`.
+pub async fn fetch_server_time_offset_with_clock(
+    client: &Client,
+    clock: &dyn Clock,
+) -> Result<i64, Error> {
+    let response = client.post(QUERY_TIME_URL)
+        .header(reqwest::header::CONTENT_LENGTH, 0)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<QueryTimeResponse>()
+        .await?;
+    
+    Ok(response.response.server_time - clock.now().timestamp())
+}
+
+/// Generates a Steam Guard code from a base64-encoded `shared_secret`. `time_offset` is the 
+/// number of seconds the local clock is behind Steam's servers.
+pub fn generate_auth_code(shared_secret: &str, time_offset: i64) -> Result<String, Error> {
+    generate_auth_code_with_clock(shared_secret, time_offset, &SystemClock)
+}
+
+/// Same as [`generate_auth_code`], reading the local time from `clock`.
+pub fn generate_auth_code_with_clock(
+    shared_secret: &str,
+    time_offset: i64,
+    clock: &dyn Clock,
+) -> Result<String, Error> {
+    generate_auth_code_for_time(shared_secret, clock.now().timestamp() + time_offset)
+}
+
+/// Generates a Steam Guard code for the Steam server time `timestamp`.
+pub fn generate_auth_code_for_time(shared_secret: &str, timestamp: i64) -> Result<String, Error> {
+    // decode_base64 does not accept padding
+    let shared_secret = decode_base64(shared_secret.trim_end_matches('='))?;
+    
+    if shared_secret.is_empty() {
+        return Err(Error::EmptySecret);
+    }
+    
+    let mut mac = HmacSha1::new_from_slice(&shared_secret)
+        .map_err(|_error| Error::EmptySecret)?;
+    
+    mac.update(&(timestamp / 30).to_be_bytes());
+    
+    let hash = mac.finalize().into_bytes();
+    let start = (hash[19] & 0x0F) as usize;
+    let mut full_code = u32::from_be_bytes([
+        hash[start],
+        hash[start + 1],
+        hash[start + 2],
+        hash[start + 3],
+    ]) & 0x7FFFFFFF;
+    let chars_len = CHARS.len() as u32;
+    
+    Ok((0..5)
+        .map(|_| {
+            let ch = CHARS[(full_code % chars_len) as usize] as char;
+            
+            full_code /= chars_len;
+            ch
+        })
+        .collect())
+}
+
+/// Approves a pending login for `steamid` as the mobile authenticator would, without a phone. 
+/// `client_id` is the client ID of the auth session to approve, and `access_token` must be a 
+/// [`EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp`] access token for the same 
+/// account.
+/// 
+/// The approval is signed with an HMAC of the session using `shared_secret`; the 
+/// `identity_secret` is only used for trade and market confirmations and isn't needed.
+/// 
+/// # Security
+/// 
+/// Anything holding the `shared_secret` and a mobile access token can approve logins to the 
+/// account, which defeats the purpose of Steam Guard if either is leaked. Only approve auth 
+/// sessions you started yourself, store the secrets encrypted, and never approve a `client_id` 
+/// received from an untrusted source.
+/// 
+/// [`EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp`]: crate::enums::EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp
+pub async fn approve_login(
+    access_token: String,
+    shared_secret: String,
+    client_id: u64,
+    steamid: u64,
+    persistence: ESessionPersistence,
+) -> Result<(), Error> {
+    let login_approver = LoginApprover::builder(access_token, shared_secret).build()?;
+    
+    login_approver.approve_auth_session(ApproveAuthSessionRequest {
+        version: MOBILE_CONFIRMATION_VERSION,
+        client_id,
+        steamid,
+        approve: true,
+        persistence,
+    }).await?;
+    
+    Ok(())
+}
+
+/// Caches the Steam server time offset, fetching it again once it is older than the TTL.
+#[derive(Debug)]
+pub struct TimeOffsetCache {
+    client: Client,
+    clock: Arc<dyn Clock>,
+    ttl: Duration,
+    cached: Mutex<Option<(i64, DateTime)>>,
+}
+
+impl TimeOffsetCache {
+    /// Creates a new [`TimeOffsetCache`] which caches the offset for an hour.
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            clock: Arc::new(SystemClock),
+            ttl: Duration::try_minutes(DEFAULT_TIME_OFFSET_TTL_MINUTES).unwrap(),
+            cached: Mutex::new(None),
+        }
+    }
+    
+    /// Sets how long a fetched offset is cached.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+    
+    /// Sets the clock the offset is measured against.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+    
+    /// Gets the time offset, fetching it if it has not been fetched or has expired.
+    pub async fn get(&self) -> Result<i64, Error> {
+        if let Some(time_offset) = self.get_cached() {
+            return Ok(time_offset);
+        }
+        
+        let time_offset = fetch_server_time_offset_with_clock(&self.client, self.clock.as_ref()).await?;
+        
+        *self.cached.lock().unwrap() = Some((time_offset, self.clock.now()));
+        Ok(time_offset)
+    }
+    
+    /// Gets the cached time offset if it has not expired.
+    pub fn get_cached(&self) -> Option<i64> {
+        let cached = *self.cached.lock().unwrap();
+        
+        cached
+            .filter(|(_, fetched_at)| self.clock.now() - *fetched_at < self.ttl)
+            .map(|(time_offset, _)| time_offset)
+    }
+    
+    /// Sets the time offset, e.g. one you maintain yourself.
+    pub fn set(&self, time_offset: i64) {
+        *self.cached.lock().unwrap() = Some((time_offset, self.clock.now()));
+    }
+    
+    /// Generates a Steam Guard code from `shared_secret`, applying the time offset.
+    pub async fn generate_auth_code(&self, shared_secret: &str) -> Result<String, Error> {
+        let time_offset = self.get().await?;
+        
+        generate_auth_code_with_clock(shared_secret, time_offset, self.clock.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use chrono::TimeZone;
+    
+    const SHARED_SECRET: &str = "zvIayp3JPvtvX/QGHqsqKBk/44s=";
+    
+    #[test]
+    fn generates_auth_code_for_time() {
+        assert_eq!(generate_auth_code_for_time(SHARED_SECRET, 1792048072).unwrap(), "Q88PK");
+    }
+    
+    #[test]
+    fn applies_time_offset() {
+        let clock = MockClock::new(chrono::Utc.timestamp_opt(1792048000, 0).unwrap());
+        let code = generate_auth_code_with_clock(SHARED_SECRET, 72, &clock).unwrap();
+        
+        assert_eq!(code, "Q88PK");
+    }
+    
+    #[test]
+    fn cached_time_offset_expires() {
+        let clock = Arc::new(MockClock::default());
+        let cache = TimeOffsetCache::new(Client::new())
+            .ttl(Duration::try_minutes(10).unwrap());
+        let cache = TimeOffsetCache {
+            clock: clock.clone(),
+            ..cache
+        };
+        
+        assert_eq!(cache.get_cached(), None);
+        
+        cache.set(-5);
+        
+        assert_eq!(cache.get_cached(), Some(-5));
+        
+        clock.advance(Duration::try_minutes(10).unwrap());
+        
+        assert_eq!(cache.get_cached(), None);
+    }
+}
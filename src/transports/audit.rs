@@ -0,0 +1,284 @@
+//! A transport layer for auditing what's sent to Steam. [`AuditLayer`] reports each request to an 
+//! [`AuditObserver`] along with the [`EResult`] it resulted in, e.g. for compliance logging.
+//! 
+//! Passwords, tokens, Steam Guard codes and other secrets are redacted from the request before 
+//! the observer sees it.
+
+use super::{Transport, TransportLayer, ResponseReceiver};
+use crate::authentication_client::Error as AuthenticationClientError;
+use crate::enums::EResult;
+use crate::net::ApiRequest;
+use std::fmt;
+use std::sync::Arc;
+use async_trait::async_trait;
+use protobuf::MessageDyn;
+use protobuf::reflect::{ReflectValueBox, RuntimeFieldType, RuntimeType};
+use reqwest::header::HeaderMap;
+
+/// The value redacted fields are replaced with.
+const REDACTED: &str = "[redacted]";
+
+/// A request sent to Steam, as reported to an [`AuditObserver`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// The name of the service method, e.g. `"Authentication.PollAuthSessionStatus#1"`.
+    pub name: &'static str,
+    /// The parameters of the request in protobuf text format, with secrets redacted.
+    pub parameters: String,
+    /// The result of the request. [`EResult::OK`] if it succeeded, and `None` if it failed 
+    /// without a result, e.g. because Steam couldn't be reached.
+    pub eresult: Option<EResult>,
+}
+
+/// Observes the requests sent by an [`AuditLayer`]. Implemented for closures taking an 
+/// [`AuditRecord`].
+pub trait AuditObserver: Send + Sync {
+    /// Called once the result of a request is known.
+    fn observe(&self, record: &AuditRecord);
+}
+
+impl<F> AuditObserver for F
+where
+    F: Fn(&AuditRecord) + Send + Sync,
+{
+    fn observe(&self, record: &AuditRecord) {
+        self(record)
+    }
+}
+
+/// Reports each request sent using the wrapped transport to an [`AuditObserver`], with secrets 
+/// redacted. Works with any transport, including both 
+/// [`WebApiTransport`](super::web_api::WebApiTransport) and 
+/// [`WebSocketCMTransport`](super::WebSocketCMTransport).
+/// 
+/// Requests are passed straight through until an observer is set using 
+/// [`AuditLayer::observer`].
+pub struct AuditLayer<T> {
+    inner: T,
+    observer: Option<Arc<dyn AuditObserver>>,
+}
+
+impl<T> fmt::Debug for AuditLayer<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditLayer")
+            .field("inner", &self.inner)
+            .field("observer", &self.observer.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl<T> AuditLayer<T>
+where
+    T: Transport,
+{
+    /// Creates a new [`AuditLayer`] wrapping `inner`, without an observer.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            observer: None,
+        }
+    }
+    
+    /// Sets the observer requests are reported to.
+    pub fn observer(mut self, observer: impl AuditObserver + 'static) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+    
+    /// Unwraps the wrapped transport.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+    
+    /// Reports the request with `parameters` to `observer` once its response is received.
+    fn observe<R>(
+        observer: Arc<dyn AuditObserver>,
+        name: &'static str,
+        parameters: String,
+        result: Result<ResponseReceiver<R>, AuthenticationClientError>,
+    ) -> Result<ResponseReceiver<R>, AuthenticationClientError>
+    where
+        R: Send + 'static,
+    {
+        let receiver = match result {
+            Ok(receiver) => receiver,
+            Err(error) => {
+                observer.observe(&AuditRecord {
+                    name,
+                    parameters,
+                    eresult: error.eresult(),
+                });
+                return Err(error);
+            },
+        };
+        
+        Ok(receiver.inspect(move |response| {
+            let eresult = match response {
+                Ok(_) => Some(EResult::OK),
+                Err(error) => error.eresult(),
+            };
+            
+            observer.observe(&AuditRecord {
+                name,
+                parameters,
+                eresult,
+            });
+        }))
+    }
+}
+
+impl<T> TransportLayer for AuditLayer<T>
+where
+    T: Transport,
+{
+    type Inner = T;
+    
+    fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<T> Transport for AuditLayer<T>
+where
+    T: Transport,
+{
+    async fn connect(&self) -> Result<(), AuthenticationClientError> {
+        self.inner.connect().await
+    }
+    
+    async fn send_request<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        let Some(observer) = self.observer.clone() else {
+            return self.inner.send_request(msg, access_token).await;
+        };
+        let parameters = redacted_parameters(&msg);
+        let result = self.inner.send_request(msg, access_token).await;
+        
+        Self::observe(observer, <Msg as ApiRequest>::NAME, parameters, result)
+    }
+    
+    async fn send_request_with_headers<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+        headers: HeaderMap,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        let Some(observer) = self.observer.clone() else {
+            return self.inner.send_request_with_headers(msg, access_token, headers).await;
+        };
+        let parameters = redacted_parameters(&msg);
+        let result = self.inner.send_request_with_headers(msg, access_token, headers).await;
+        
+        Self::observe(observer, <Msg as ApiRequest>::NAME, parameters, result)
+    }
+}
+
+/// Formats `msg` in protobuf text format with its secrets redacted.
+fn redacted_parameters<Msg>(msg: &Msg) -> String
+where
+    Msg: ApiRequest,
+{
+    let mut msg = msg.clone();
+    
+    redact(&mut msg);
+    protobuf::text_format::print_to_string(&msg)
+}
+
+/// Checks if the field `name` holds a secret.
+fn is_secret(name: &str) -> bool {
+    name == "code" ||
+    ["password", "token", "secret", "signature", "guard_data", "machine_id"]
+        .iter()
+        .any(|secret| name.contains(secret))
+}
+
+/// Redacts the secrets in `msg`, including those of nested messages.
+fn redact(msg: &mut dyn MessageDyn) {
+    let descriptor = msg.descriptor_dyn();
+    
+    for field in descriptor.fields() {
+        match field.runtime_field_type() {
+            RuntimeFieldType::Singular(RuntimeType::Message(_)) if field.has_field(msg) => {
+                redact(field.mut_message(msg));
+            },
+            RuntimeFieldType::Singular(RuntimeType::String) if is_secret(field.name()) && field.has_field(msg) => {
+                field.set_singular_field(msg, ReflectValueBox::String(REDACTED.into()));
+            },
+            RuntimeFieldType::Singular(RuntimeType::VecU8) if is_secret(field.name()) && field.has_field(msg) => {
+                field.set_singular_field(msg, ReflectValueBox::Bytes(REDACTED.into()));
+            },
+            // Secrets of other types can't hold the placeholder.
+            _ if is_secret(field.name()) => field.clear_field(msg),
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transports::{ReplayTransport, RecordedExchange};
+    use crate::helpers::encode_base64;
+    use crate::proto::custom::CAuthentication_BeginAuthSessionViaCredentials_Request_BinaryGuardData;
+    use crate::proto::steammessages_auth_steamclient::{
+        CAuthentication_BeginAuthSessionViaCredentials_Response,
+        CAuthentication_DeviceDetails,
+    };
+    use protobuf::Message;
+    use std::sync::Mutex;
+    
+    #[tokio::test]
+    async fn reports_redacted_requests() {
+        let mut msg = CAuthentication_BeginAuthSessionViaCredentials_Request_BinaryGuardData::new();
+        let mut device_details = CAuthentication_DeviceDetails::new();
+        
+        device_details.set_device_friendly_name("Galaxy S22".into());
+        device_details.set_machine_id(b"machine".to_vec());
+        msg.set_account_name("gaben".into());
+        msg.set_encrypted_password("hunter2".into());
+        msg.set_guard_data(b"guard-data".to_vec());
+        msg.device_details = Some(device_details).into();
+        
+        let transport = ReplayTransport::new(vec![
+            RecordedExchange {
+                name: <CAuthentication_BeginAuthSessionViaCredentials_Request_BinaryGuardData as ApiRequest>::NAME.into(),
+                request: encode_base64(msg.write_to_bytes().unwrap()),
+                response: Some(encode_base64(CAuthentication_BeginAuthSessionViaCredentials_Response::new().write_to_bytes().unwrap())),
+                eresult: None,
+                error: None,
+            },
+        ]);
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let observed = records.clone();
+        let transport = AuditLayer::new(transport)
+            .observer(move |record: &AuditRecord| observed.lock().unwrap().push(record.clone()));
+        
+        transport.send_request(msg, None).await.unwrap().await.unwrap().unwrap();
+        
+        let records = records.lock().unwrap();
+        
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].eresult, Some(EResult::OK));
+        assert!(records[0].parameters.contains("gaben"));
+        assert!(records[0].parameters.contains("Galaxy S22"));
+        
+        for secret in ["hunter2", "guard-data", "machine\""] {
+            assert!(!records[0].parameters.contains(secret), "{secret} wasn't redacted");
+        }
+    }
+}
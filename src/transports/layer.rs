@@ -0,0 +1,329 @@
+//! Transports which wrap another transport to add behavior to its requests. Layers are composed 
+//! by nesting them, e.g. `RetryLayer::new(LoggingLayer::new(WebApiTransport::new()))` logs each 
+//! attempt and retries the ones that fail because Steam is temporarily unavailable.
+
+use super::{Transport, RetryPolicy, ResponseReceiver};
+use super::retry::{retry, is_retriable, retry_after};
+use crate::authentication_client::Error as AuthenticationClientError;
+use crate::net::ApiRequest;
+use std::time::Instant;
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use tokio::sync::oneshot;
+
+/// A transport which wraps another transport, adding behavior to its requests.
+pub trait TransportLayer: Transport {
+    /// The wrapped transport.
+    type Inner: Transport;
+    
+    /// Gets the wrapped transport.
+    fn inner(&self) -> &Self::Inner;
+}
+
+/// Retries requests which fail because Steam is temporarily unavailable, as decided by 
+/// [`is_retriable`](super::is_retriable). Each attempt is sent as a new request using the 
+/// wrapped transport.
+/// 
+/// The response is awaited before the receiver is returned, so that a failed attempt can be 
+/// retried.
+#[derive(Debug)]
+pub struct RetryLayer<T> {
+    inner: T,
+    policy: RetryPolicy,
+}
+
+impl<T> RetryLayer<T>
+where
+    T: Transport,
+{
+    /// Creates a new [`RetryLayer`] wrapping `inner`, retrying up to 3 times with the default 
+    /// backoff.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            policy: RetryPolicy::new(3),
+        }
+    }
+    
+    /// Sets the policy for retrying requests.
+    pub fn policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+    
+    /// Unwraps the wrapped transport.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> TransportLayer for RetryLayer<T>
+where
+    T: Transport,
+{
+    type Inner = T;
+    
+    fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<T> Transport for RetryLayer<T>
+where
+    T: Transport,
+{
+    async fn connect(&self) -> Result<(), AuthenticationClientError> {
+        self.inner.connect().await
+    }
+    
+    async fn send_request<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        let response = retry(&self.policy, is_retriable, retry_after, || {
+            let msg = msg.clone();
+            let access_token = access_token.clone();
+            
+            async move {
+                self.inner.send_request(msg, access_token).await?.await?
+            }
+        }).await;
+        
+        Ok(ready(response))
+    }
+    
+    async fn send_request_with_headers<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+        headers: HeaderMap,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        let response = retry(&self.policy, is_retriable, retry_after, || {
+            let msg = msg.clone();
+            let access_token = access_token.clone();
+            let headers = headers.clone();
+            
+            async move {
+                self.inner.send_request_with_headers(msg, access_token, headers).await?.await?
+            }
+        }).await;
+        
+        Ok(ready(response))
+    }
+}
+
+/// Logs each request along with how long it took and whether it failed. Successful requests are 
+/// logged at the configured level, [`log::Level::Debug`] by default, and failed requests at 
+/// [`log::Level::Warn`].
+#[derive(Debug)]
+pub struct LoggingLayer<T> {
+    inner: T,
+    level: log::Level,
+}
+
+impl<T> LoggingLayer<T>
+where
+    T: Transport,
+{
+    /// Creates a new [`LoggingLayer`] wrapping `inner`.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            level: log::Level::Debug,
+        }
+    }
+    
+    /// Sets the level successful requests are logged at.
+    pub fn level(mut self, level: log::Level) -> Self {
+        self.level = level;
+        self
+    }
+    
+    /// Unwraps the wrapped transport.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+    
+    /// Logs the outcome of the request for `name` once its response is received.
+    fn observe<R>(
+        &self,
+        name: &'static str,
+        started_at: Instant,
+        result: Result<ResponseReceiver<R>, AuthenticationClientError>,
+    ) -> Result<ResponseReceiver<R>, AuthenticationClientError>
+    where
+        R: Send + 'static,
+    {
+        let receiver = match result {
+            Ok(receiver) => receiver,
+            Err(error) => {
+                log::warn!("{name} failed to send after {:?}: {error}", started_at.elapsed());
+                return Err(error);
+            },
+        };
+        let level = self.level;
+        
+        Ok(receiver.inspect(move |response| match response {
+            Ok(_) => log::log!(level, "{name} succeeded in {:?}", started_at.elapsed()),
+            Err(error) => log::warn!("{name} failed after {:?}: {error}", started_at.elapsed()),
+        }))
+    }
+}
+
+impl<T> TransportLayer for LoggingLayer<T>
+where
+    T: Transport,
+{
+    type Inner = T;
+    
+    fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<T> Transport for LoggingLayer<T>
+where
+    T: Transport,
+{
+    async fn connect(&self) -> Result<(), AuthenticationClientError> {
+        self.inner.connect().await
+    }
+    
+    async fn send_request<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        let started_at = Instant::now();
+        
+        log::log!(self.level, "Sending {}", <Msg as ApiRequest>::NAME);
+        
+        let result = self.inner.send_request(msg, access_token).await;
+        
+        self.observe(<Msg as ApiRequest>::NAME, started_at, result)
+    }
+    
+    async fn send_request_with_headers<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+        headers: HeaderMap,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        let started_at = Instant::now();
+        
+        log::log!(self.level, "Sending {}", <Msg as ApiRequest>::NAME);
+        
+        let result = self.inner.send_request_with_headers(msg, access_token, headers).await;
+        
+        self.observe(<Msg as ApiRequest>::NAME, started_at, result)
+    }
+}
+
+/// Creates a receiver which resolves to `response`.
+fn ready<R>(response: Result<R, AuthenticationClientError>) -> ResponseReceiver<R> {
+    let (tx, rx) = oneshot::channel();
+    
+    tx.send(response).ok();
+    rx.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::EResult;
+    use crate::net::ApiResponse;
+    use crate::proto::steammessages_auth_steamclient::CAuthentication_GetPasswordRSAPublicKey_Request;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+    
+    /// Fails the first `failures` requests with a retriable error.
+    #[derive(Debug, Default)]
+    struct FlakyTransport {
+        requests: AtomicU32,
+        failures: u32,
+    }
+    
+    #[async_trait]
+    impl Transport for FlakyTransport {
+        async fn send_request<Msg>(
+            &self,
+            _msg: Msg,
+            _access_token: Option<String>,
+        ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+        where
+            Msg: ApiRequest,
+            <Msg as ApiRequest>::Response: Send,
+        {
+            let response = if self.requests.fetch_add(1, Ordering::Relaxed) < self.failures {
+                Err(AuthenticationClientError::EResultNotOK(EResult::ServiceUnavailable))
+            } else {
+                Msg::Response::parse_from_reader(&mut [].as_slice()).map_err(|_error| AuthenticationClientError::NoJob)
+            };
+            
+            Ok(ready(response))
+        }
+    }
+    
+    fn no_backoff(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            ..RetryPolicy::new(max_retries)
+        }
+    }
+    
+    async fn send<T: Transport>(transport: &T) -> Result<(), AuthenticationClientError> {
+        transport.send_request(CAuthentication_GetPasswordRSAPublicKey_Request::new(), None)
+            .await?
+            .await??;
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    async fn layers_compose() {
+        let transport = RetryLayer::new(LoggingLayer::new(FlakyTransport {
+            failures: 2,
+            ..Default::default()
+        }))
+            .policy(no_backoff(2));
+        
+        send(&transport).await.unwrap();
+        
+        assert_eq!(transport.inner().inner().requests.load(Ordering::Relaxed), 3);
+    }
+    
+    #[tokio::test]
+    async fn retry_layer_returns_last_error() {
+        let transport = RetryLayer::new(FlakyTransport {
+            failures: 5,
+            ..Default::default()
+        })
+            .policy(no_backoff(1));
+        
+        assert!(matches!(
+            send(&transport).await,
+            Err(AuthenticationClientError::EResultNotOK(EResult::ServiceUnavailable)),
+        ));
+        assert_eq!(transport.into_inner().requests.load(Ordering::Relaxed), 2);
+    }
+}
@@ -1,20 +1,90 @@
 pub mod web_api;
 pub mod websocket;
 
+mod retry;
+mod receiver;
+mod round_robin;
+mod layer;
+
+#[cfg(any(test, feature = "record"))]
+mod record;
+#[cfg(any(test, feature = "audit"))]
+mod audit;
+
 pub use websocket::WebSocketCMTransport;
+pub use retry::{RetryPolicy, is_retriable, retry_after};
+pub use receiver::ResponseReceiver;
+pub use round_robin::{RoundRobinTransport, Rotation};
+pub use layer::{TransportLayer, RetryLayer, LoggingLayer};
+#[cfg(any(test, feature = "record"))]
+pub use record::{RecordingTransport, ReplayTransport, RecordedExchange};
+#[cfg(any(test, feature = "audit"))]
+pub use audit::{AuditLayer, AuditObserver, AuditRecord};
 
 use crate::authentication_client::Error as AuthenticationClientError;
-use crate::net::ApiRequest;
-use tokio::sync::oneshot;
+use crate::net::{ApiRequest, RawResponse};
+use reqwest::header::HeaderMap;
 
 #[async_trait::async_trait]
 pub trait Transport: Sync + Send {
+    /// Establishes the transport's connection, e.g. to warm it up before sending the first 
+    /// request. Does nothing if already connected. Stateless transports such as 
+    /// [`WebApiTransport`](web_api::WebApiTransport) have nothing to connect, which is the 
+    /// default.
+    /// 
+    /// Calling this is optional: `send_request` connects first if needed.
+    async fn connect(&self) -> Result<(), AuthenticationClientError> {
+        Ok(())
+    }
+    
+    /// Sends a request, connecting first if [`Transport::connect`] wasn't called or the 
+    /// connection was lost.
     async fn send_request<Msg>(
         &self,
         msg: Msg,
         access_token: Option<String>,
-    ) -> Result<oneshot::Receiver<Result<Msg::Response, AuthenticationClientError>>, AuthenticationClientError> 
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError> 
     where
         Msg: ApiRequest,
         <Msg as ApiRequest>::Response: Send;
+    
+    /// Sends a request with additional HTTP `headers`, e.g. the user agent of the platform type. 
+    /// Transports that don't send HTTP requests ignore the headers, which is the default.
+    async fn send_request_with_headers<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+        _headers: HeaderMap,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError> 
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        self.send_request(msg, access_token).await
+    }
+    
+    /// Sends a request and waits for the response, returning its protobuf-encoded body along 
+    /// with the typed response. This is an escape hatch for fields the crate doesn't surface.
+    /// 
+    /// The body is encoded from the typed response. Fields which aren't in the crate's protobuf 
+    /// definitions are kept as unknown fields when parsing, so the body contains every field 
+    /// Steam sent, though not necessarily in the same order. Unknown fields can also be read 
+    /// from the typed response using `special_fields`.
+    async fn send_request_raw<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+    ) -> Result<RawResponse<Msg::Response>, AuthenticationClientError> 
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: protobuf::Message + Send,
+    {
+        let response = self.send_request(msg, access_token).await?.await??;
+        let body = protobuf::Message::write_to_bytes(&response)?;
+        
+        Ok(RawResponse {
+            response,
+            body,
+        })
+    }
 }
\ No newline at end of file
@@ -0,0 +1,54 @@
+use crate::authentication_client::Error as AuthenticationClientError;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::oneshot;
+
+/// Receives the response to a request sent using a [`Transport`](super::Transport). Await it 
+/// to get the response.
+/// 
+/// The request is sent regardless, but dropping the receiver discards the response.
+#[must_use = "the response is discarded unless the receiver is awaited"]
+#[derive(Debug)]
+pub struct ResponseReceiver<T> {
+    rx: oneshot::Receiver<Result<T, AuthenticationClientError>>,
+}
+
+impl<T> From<oneshot::Receiver<Result<T, AuthenticationClientError>>> for ResponseReceiver<T> {
+    fn from(rx: oneshot::Receiver<Result<T, AuthenticationClientError>>) -> Self {
+        Self {
+            rx,
+        }
+    }
+}
+
+impl<T> ResponseReceiver<T>
+where
+    T: Send + 'static,
+{
+    /// Calls `f` with the response once it's received, then passes the response on to the 
+    /// returned receiver.
+    pub(crate) fn inspect<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&Result<T, AuthenticationClientError>) + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        
+        tokio::spawn(async move {
+            let response = self.await.unwrap_or_else(|error| Err(error.into()));
+            
+            f(&response);
+            tx.send(response).ok();
+        });
+        
+        rx.into()
+    }
+}
+
+impl<T> Future for ResponseReceiver<T> {
+    type Output = Result<Result<T, AuthenticationClientError>, oneshot::error::RecvError>;
+    
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx).poll(cx)
+    }
+}
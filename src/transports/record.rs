@@ -0,0 +1,326 @@
+//! Transports for developing against a recorded session instead of Steam. 
+//! [`RecordingTransport`] writes each request sent using another transport along with its 
+//! response to a file, which [`ReplayTransport`] serves responses from offline.
+//! 
+//! Recordings contain the responses as sent by Steam, including any refresh and access tokens, 
+//! so they should be kept as private as the tokens themselves.
+
+use super::{Transport, TransportLayer, ResponseReceiver};
+use crate::authentication_client::Error as AuthenticationClientError;
+use crate::helpers::{encode_base64, decode_base64};
+use crate::net::{ApiRequest, ApiResponse};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+/// A request along with its response, as recorded by [`RecordingTransport`]. Recordings are 
+/// stored as JSON, one exchange per line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedExchange {
+    /// The name of the service method, e.g. `"Authentication.PollAuthSessionStatus#1"`.
+    pub name: String,
+    /// The base64-encoded protobuf body of the request.
+    pub request: String,
+    /// The base64-encoded protobuf body of the response, if the request succeeded.
+    pub response: Option<String>,
+    /// The raw [`EResult`](crate::enums::EResult) the request failed with, if any.
+    pub eresult: Option<i32>,
+    /// The error the request failed with, if any.
+    pub error: Option<String>,
+}
+
+impl RecordedExchange {
+    /// Creates a new [`RecordedExchange`] for `request` with the result `response`.
+    fn new<Msg>(
+        request: &Msg,
+        response: &Result<Msg::Response, AuthenticationClientError>,
+    ) -> Result<Self, protobuf::Error>
+    where
+        Msg: ApiRequest,
+    {
+        let (response, eresult, error) = match response {
+            Ok(response) => (Some(encode_base64(response.to_bytes()?)), None, None),
+            Err(error) => (None, error.eresult().map(i32::from), Some(error.to_string())),
+        };
+        
+        Ok(Self {
+            name: <Msg as ApiRequest>::NAME.into(),
+            request: encode_base64(request.write_to_bytes()?),
+            response,
+            eresult,
+            error,
+        })
+    }
+    
+    /// Converts the recorded result to the response to `Msg`.
+    fn into_response<Msg>(self) -> Result<Msg::Response, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+    {
+        if let Some(response) = self.response {
+            let body = decode_base64(&response)?;
+            
+            return Ok(Msg::Response::parse_from_reader(&mut body.as_slice())?);
+        }
+        
+        match self.eresult.map(crate::enums::EResult::try_from) {
+            Some(Ok(eresult)) => Err(AuthenticationClientError::EResultNotOK(eresult)),
+            _ => Err(AuthenticationClientError::Replay(self.error.unwrap_or_default())),
+        }
+    }
+}
+
+/// Wraps another transport, appending each request and its response to a file as a 
+/// [`RecordedExchange`] for replaying using [`ReplayTransport`]. Requests which fail to send 
+/// aren't recorded.
+#[derive(Debug)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    file: Arc<Mutex<File>>,
+}
+
+impl<T> RecordingTransport<T>
+where
+    T: Transport,
+{
+    /// Creates a new [`RecordingTransport`] wrapping `inner`, appending to the file at `path`. 
+    /// The file is created if it doesn't exist.
+    pub fn new(inner: T, path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        
+        Ok(Self {
+            inner,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+    
+    /// Unwraps the wrapped transport.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+    
+    /// Records the response to `msg` once it's received.
+    fn record<Msg>(
+        &self,
+        msg: Msg,
+        result: Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        let receiver = result?;
+        let file = self.file.clone();
+        
+        Ok(receiver.inspect(move |response| {
+            let line = RecordedExchange::new(&msg, response)
+                .map_err(|error| error.to_string())
+                .and_then(|exchange| serde_json::to_string(&exchange).map_err(|error| error.to_string()));
+            
+            match line {
+                Ok(line) => if let Err(error) = writeln!(file.lock().unwrap(), "{line}") {
+                    log::warn!("Failed to record {}: {error}", <Msg as ApiRequest>::NAME);
+                },
+                Err(error) => log::warn!("Failed to encode {} for recording: {error}", <Msg as ApiRequest>::NAME),
+            }
+        }))
+    }
+}
+
+impl<T> TransportLayer for RecordingTransport<T>
+where
+    T: Transport,
+{
+    type Inner = T;
+    
+    fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<T> Transport for RecordingTransport<T>
+where
+    T: Transport,
+{
+    async fn connect(&self) -> Result<(), AuthenticationClientError> {
+        self.inner.connect().await
+    }
+    
+    async fn send_request<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        let result = self.inner.send_request(msg.clone(), access_token).await;
+        
+        self.record(msg, result)
+    }
+    
+    async fn send_request_with_headers<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+        headers: HeaderMap,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        let result = self.inner.send_request_with_headers(msg.clone(), access_token, headers).await;
+        
+        self.record(msg, result)
+    }
+}
+
+/// Serves responses from a recording made by [`RecordingTransport`] without contacting Steam.
+/// 
+/// Each request is answered by the first exchange not yet replayed with the same method and 
+/// request body, so requests which are sent repeatedly such as polls are answered in the order 
+/// they were recorded. Requests without a matching exchange fail with 
+/// [`Error::Replay`](AuthenticationClientError::Replay).
+#[derive(Debug)]
+pub struct ReplayTransport {
+    exchanges: Mutex<Vec<RecordedExchange>>,
+}
+
+impl ReplayTransport {
+    /// Creates a new [`ReplayTransport`] serving `exchanges`.
+    pub fn new(exchanges: Vec<RecordedExchange>) -> Self {
+        Self {
+            exchanges: Mutex::new(exchanges),
+        }
+    }
+    
+    /// Loads the recording at `path`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let exchanges = BufReader::new(File::open(path)?)
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .map(|line| serde_json::from_str(&line?).map_err(io::Error::from))
+            .collect::<io::Result<Vec<_>>>()?;
+        
+        Ok(Self::new(exchanges))
+    }
+    
+    /// Gets the number of exchanges which haven't been replayed.
+    pub fn remaining(&self) -> usize {
+        self.exchanges.lock().unwrap().len()
+    }
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn send_request<Msg>(
+        &self,
+        msg: Msg,
+        _access_token: Option<String>,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        let request = encode_base64(msg.write_to_bytes()?);
+        let exchange = {
+            let mut exchanges = self.exchanges.lock().unwrap();
+            
+            exchanges
+                .iter()
+                .position(|exchange| exchange.name == <Msg as ApiRequest>::NAME && exchange.request == request)
+                .map(|index| exchanges.remove(index))
+        };
+        let response = match exchange {
+            Some(exchange) => exchange.into_response::<Msg>(),
+            None => Err(AuthenticationClientError::Replay(format!("No recorded response for {}", <Msg as ApiRequest>::NAME))),
+        };
+        let (tx, rx) = oneshot::channel();
+        
+        tx.send(response).ok();
+        Ok(rx.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::EResult;
+    use crate::proto::steammessages_auth_steamclient::{
+        CAuthentication_GetPasswordRSAPublicKey_Request,
+        CAuthentication_GetPasswordRSAPublicKey_Response,
+    };
+    use protobuf::Message;
+    
+    /// Responds with the account name as the modulus, failing for "banned".
+    #[derive(Debug)]
+    struct MockTransport;
+    
+    #[async_trait]
+    impl Transport for MockTransport {
+        async fn send_request<Msg>(
+            &self,
+            msg: Msg,
+            _access_token: Option<String>,
+        ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+        where
+            Msg: ApiRequest,
+            <Msg as ApiRequest>::Response: Send,
+        {
+            let request = CAuthentication_GetPasswordRSAPublicKey_Request::parse_from_bytes(&msg.write_to_bytes()?)?;
+            let response = if request.account_name() == "banned" {
+                Err(AuthenticationClientError::EResultNotOK(EResult::Banned))
+            } else {
+                let mut response = CAuthentication_GetPasswordRSAPublicKey_Response::new();
+                
+                response.set_publickey_mod(request.account_name().into());
+                Msg::Response::parse_from_reader(&mut response.write_to_bytes()?.as_slice()).map_err(Into::into)
+            };
+            let (tx, rx) = oneshot::channel();
+            
+            tx.send(response).ok();
+            Ok(rx.into())
+        }
+    }
+    
+    async fn get_rsa_key<T: Transport>(
+        transport: &T,
+        account_name: &str,
+    ) -> Result<CAuthentication_GetPasswordRSAPublicKey_Response, AuthenticationClientError> {
+        let mut msg = CAuthentication_GetPasswordRSAPublicKey_Request::new();
+        
+        msg.set_account_name(account_name.into());
+        transport.send_request(msg, None).await?.await?
+    }
+    
+    #[tokio::test]
+    async fn replays_recorded_session() {
+        let path = std::env::temp_dir().join(format!("steam-session-recording-{}.jsonl", rand::random::<u64>()));
+        let transport = RecordingTransport::new(MockTransport, &path).unwrap();
+        
+        assert_eq!(get_rsa_key(&transport, "gabe").await.unwrap().publickey_mod(), "gabe");
+        assert!(get_rsa_key(&transport, "banned").await.is_err());
+        
+        let transport = ReplayTransport::load(&path).unwrap();
+        
+        std::fs::remove_file(&path).unwrap();
+        
+        assert!(matches!(
+            get_rsa_key(&transport, "banned").await,
+            Err(AuthenticationClientError::EResultNotOK(EResult::Banned)),
+        ));
+        assert_eq!(get_rsa_key(&transport, "gabe").await.unwrap().publickey_mod(), "gabe");
+        assert!(matches!(get_rsa_key(&transport, "gabe").await, Err(AuthenticationClientError::Replay(_))));
+        assert_eq!(transport.remaining(), 0);
+    }
+}
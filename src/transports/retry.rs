@@ -0,0 +1,288 @@
+use crate::authentication_client::Error as AuthenticationClientError;
+use crate::enums::EResult;
+use std::future::Future;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+
+/// Controls retrying requests which fail because Steam is temporarily unavailable, e.g. with 
+/// [`EResult::ServiceUnavailable`] during maintenance. Requests are retried by wrapping a 
+/// transport in a [`RetryLayer`](super::RetryLayer), and which errors are retried is decided by 
+/// [`is_retriable`].
+/// 
+/// The delay before each retry doubles, starting at `initial_backoff` and capped at 
+/// `max_backoff`. When Steam asks to wait a given time using `Retry-After`, at least that long 
+/// is waited, up to `max_retry_after`. Retries are disabled by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of retries. `0` disables retries.
+    pub max_retries: u32,
+    /// The delay before the first retry.
+    pub initial_backoff: Duration,
+    /// The maximum delay between retries.
+    pub max_backoff: Duration,
+    /// Whether to randomize each delay to between half and all of the backoff, so that clients 
+    /// which failed at the same time don't retry at the same time.
+    pub jitter: bool,
+    /// The longest delay asked for using `Retry-After` that is waited for. An error asking for 
+    /// a longer delay is returned without retrying, still carrying the delay. This is separate 
+    /// from `max_backoff`, as Steam may ask to wait longer than the backoff would. Defaults to 5 
+    /// minutes.
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            jitter: false,
+            max_retry_after: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a [`RetryPolicy`] which retries up to `max_retries` times with the default 
+    /// backoff.
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Default::default()
+        }
+    }
+    
+    /// Gets the delay before retry number `attempt`, starting at 0.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+    
+    /// Gets the delay before retry number `attempt`, applying jitter if enabled.
+    fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff(attempt);
+        
+        if !self.jitter {
+            return backoff;
+        }
+        
+        rand::thread_rng().gen_range(backoff / 2..=backoff)
+    }
+}
+
+/// Checks if a request that failed with `error` may succeed if retried later. This is the case 
+/// when Steam is temporarily unavailable or rate limiting requests, or when the connection was 
+/// interrupted before the request was answered.
+pub fn is_retriable(error: &AuthenticationClientError) -> bool {
+    match error {
+        AuthenticationClientError::WebAPI(error) => error.is_retriable(),
+        AuthenticationClientError::WebSocketCM(error) => error.is_retriable(),
+        AuthenticationClientError::EResultNotOK(eresult) => is_retriable_eresult(*eresult),
+        _ => false,
+    }
+}
+
+/// Checks if a request that failed with `eresult` may succeed if retried later.
+pub(crate) fn is_retriable_eresult(eresult: EResult) -> bool {
+    eresult.is_retriable() || eresult == EResult::RateLimitExceeded
+}
+
+/// Gets how long Steam asked to wait before retrying a request that failed with `error`, from 
+/// the `Retry-After` header of a rate limited Web API response or of the CM server directory.
+pub fn retry_after(error: &AuthenticationClientError) -> Option<Duration> {
+    match error {
+        AuthenticationClientError::WebAPI(error) => error.retry_after(),
+        AuthenticationClientError::WebSocketCM(crate::transports::websocket::Error::NoCmServersAvailable { retry_after }) => *retry_after,
+        _ => None,
+    }
+}
+
+/// Parses the value of a `Retry-After` header, given either in seconds or as an HTTP date, into 
+/// the delay from `now`. A date in the past results in no delay.
+pub(crate) fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    
+    Some(date.signed_duration_since(now).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Calls `f` until it succeeds, fails with an error that isn't retriable, or the retries in 
+/// `policy` are exhausted. The last result is returned.
+/// 
+/// Before each retry, at least the delay `retry_after` gets from the error is waited. An error 
+/// asking for a longer delay than `policy.max_retry_after` isn't retried.
+pub(crate) async fn retry<T, E, F, Fut>(
+    policy: &RetryPolicy,
+    is_retriable: impl Fn(&E) -> bool,
+    retry_after: impl Fn(&E) -> Option<Duration>,
+    mut f: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: std::fmt::Display,
+{
+    let mut attempt = 0;
+    
+    loop {
+        match f().await {
+            Err(error) if attempt < policy.max_retries && is_retriable(&error) => {
+                let delay = match retry_after(&error) {
+                    Some(retry_after) if retry_after > policy.max_retry_after => {
+                        log::debug!("Not retrying as Steam asked to wait {retry_after:?} after error: {error}");
+                        return Err(error);
+                    },
+                    Some(retry_after) => retry_after.max(policy.delay(attempt)),
+                    None => policy.delay(attempt),
+                };
+                
+                log::debug!("Retrying in {delay:?} after error: {error}");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            },
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            ..RetryPolicy::default()
+        };
+        
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(1), Duration::from_secs(2));
+        assert_eq!(policy.backoff(2), Duration::from_secs(4));
+        assert_eq!(policy.backoff(3), Duration::from_secs(5));
+        assert_eq!(policy.backoff(40), Duration::from_secs(5));
+    }
+    
+    #[tokio::test]
+    async fn retries_retriable_errors_only() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            ..RetryPolicy::default()
+        };
+        let mut calls = 0;
+        let result: Result<(), &str> = retry(&policy, |error| *error == "busy", |_| None, || {
+            calls += 1;
+            async { Err("busy") }
+        }).await;
+        
+        assert_eq!(result, Err("busy"));
+        assert_eq!(calls, 4);
+        
+        let mut calls = 0;
+        let result: Result<(), &str> = retry(&policy, |error| *error == "busy", |_| None, || {
+            calls += 1;
+            async { Err("denied") }
+        }).await;
+        
+        assert_eq!(result, Err("denied"));
+        assert_eq!(calls, 1);
+    }
+    
+    #[test]
+    fn jitter_stays_within_backoff() {
+        let policy = RetryPolicy {
+            jitter: true,
+            ..RetryPolicy::new(3)
+        };
+        
+        for attempt in 0..3 {
+            let delay = policy.delay(attempt);
+            
+            assert!(delay >= policy.backoff(attempt) / 2 && delay <= policy.backoff(attempt));
+        }
+    }
+    
+    #[test]
+    fn classifies_errors_from_both_transports() {
+        use crate::transports::{web_api, websocket};
+        
+        assert!(is_retriable(&AuthenticationClientError::WebAPI(web_api::Error::EResultNotOK(EResult::ServiceUnavailable))));
+        assert!(is_retriable(&AuthenticationClientError::WebSocketCM(websocket::Error::EResultNotOK(EResult::RateLimitExceeded))));
+        assert!(is_retriable(&AuthenticationClientError::WebSocketCM(websocket::Error::Reconnected)));
+        assert!(!is_retriable(&AuthenticationClientError::EResultNotOK(EResult::InvalidPassword)));
+        assert!(!is_retriable(&AuthenticationClientError::NoJob));
+    }
+    
+    #[test]
+    fn parses_retry_after_in_both_forms() {
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT").unwrap().with_timezone(&Utc);
+        
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+    
+    #[tokio::test]
+    async fn waits_for_retry_after() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::from_millis(10),
+            jitter: false,
+            max_retry_after: Duration::from_secs(1),
+        };
+        let retry_after = |error: &&str| match *error {
+            "busy" => Some(Duration::from_millis(20)),
+            "down" => Some(Duration::from_secs(60)),
+            _ => None,
+        };
+        let mut calls = 0;
+        let started = std::time::Instant::now();
+        let result: Result<(), &str> = retry(&policy, |_| true, retry_after, || {
+            calls += 1;
+            async { Err("busy") }
+        }).await;
+        
+        assert_eq!(result, Err("busy"));
+        // the delay Steam asked for is waited even though it's longer than the backoff
+        assert_eq!(calls, 4);
+        assert!(started.elapsed() >= Duration::from_millis(60));
+        
+        // a delay longer than the policy allows is returned rather than waited for
+        let mut calls = 0;
+        let result: Result<(), &str> = retry(&policy, |_| true, retry_after, || {
+            calls += 1;
+            async { Err("down") }
+        }).await;
+        
+        assert_eq!(result, Err("down"));
+        assert_eq!(calls, 1);
+    }
+    
+    #[test]
+    fn gets_retry_after_from_both_transports() {
+        use crate::transports::{web_api, websocket};
+        
+        let rate_limited = web_api::Error::TooManyRequests { retry_after: Some(Duration::from_secs(5)) };
+        
+        assert!(is_retriable(&AuthenticationClientError::WebAPI(web_api::Error::TooManyRequests { retry_after: None })));
+        assert_eq!(retry_after(&AuthenticationClientError::WebAPI(rate_limited)), Some(Duration::from_secs(5)));
+        assert_eq!(
+            retry_after(&AuthenticationClientError::WebSocketCM(websocket::Error::NoCmServersAvailable { retry_after: Some(Duration::from_secs(10)) })),
+            Some(Duration::from_secs(10)),
+        );
+        assert_eq!(retry_after(&AuthenticationClientError::NoJob), None);
+    }
+}
@@ -0,0 +1,299 @@
+use super::{Transport, ResponseReceiver};
+use crate::authentication_client::Error as AuthenticationClientError;
+use crate::enums::EResult;
+use crate::net::ApiRequest;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use rand::seq::SliceRandom;
+use reqwest::header::HeaderMap;
+
+/// How [`RoundRobinTransport`] picks the transport for the next request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    /// Each transport in turn.
+    #[default]
+    RoundRobin,
+    /// A random transport.
+    Random,
+    /// The transport which has gone the longest without sending a request.
+    LeastRecentlyUsed,
+}
+
+/// A transport along with its usage.
+#[derive(Debug)]
+struct Member<T> {
+    transport: T,
+    last_used_at: Mutex<Option<Instant>>,
+    failed_at: std::sync::Arc<Mutex<Option<Instant>>>,
+}
+
+/// A transport which distributes requests across multiple transports, e.g. to spread requests 
+/// across several proxies and avoid per-IP rate limits.
+/// 
+/// A transport whose request fails without reaching Steam, or with an [`EResult`] indicating 
+/// it should back off such as [`EResult::RateLimitExceeded`], is skipped until the failure 
+/// cooldown has passed. If every transport has recently failed, they're all used.
+#[derive(Debug)]
+pub struct RoundRobinTransport<T> {
+    members: Vec<Member<T>>,
+    rotation: Rotation,
+    failure_cooldown: Duration,
+    next: AtomicUsize,
+}
+
+impl<T> RoundRobinTransport<T>
+where
+    T: Transport,
+{
+    /// Creates a new [`RoundRobinTransport`] which sends requests using `transports`, using 
+    /// [`Rotation::RoundRobin`] and a failure cooldown of 30 seconds.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `transports` is empty.
+    pub fn new(transports: Vec<T>) -> Self {
+        assert!(!transports.is_empty(), "RoundRobinTransport requires at least one transport");
+        
+        Self {
+            members: transports
+                .into_iter()
+                .map(|transport| Member {
+                    transport,
+                    last_used_at: Mutex::new(None),
+                    failed_at: Default::default(),
+                })
+                .collect(),
+            rotation: Rotation::default(),
+            failure_cooldown: Duration::from_secs(30),
+            next: AtomicUsize::new(0),
+        }
+    }
+    
+    /// Sets how the transport for each request is picked.
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+    
+    /// Sets how long a transport is skipped after a request sent with it fails. 
+    /// [`Duration::ZERO`] disables skipping.
+    pub fn failure_cooldown(mut self, failure_cooldown: Duration) -> Self {
+        self.failure_cooldown = failure_cooldown;
+        self
+    }
+    
+    /// Gets the transports.
+    pub fn transports(&self) -> impl Iterator<Item = &T> {
+        self.members.iter().map(|member| &member.transport)
+    }
+    
+    /// Picks the index of the transport for the next request.
+    fn pick(&self) -> usize {
+        let now = Instant::now();
+        let is_healthy = |member: &Member<T>| member.failed_at
+            .lock()
+            .unwrap()
+            .is_none_or(|failed_at| now.duration_since(failed_at) >= self.failure_cooldown);
+        let mut candidates = (0..self.members.len())
+            .filter(|index| is_healthy(&self.members[*index]))
+            .collect::<Vec<_>>();
+        
+        if candidates.is_empty() {
+            candidates = (0..self.members.len()).collect();
+        }
+        
+        let index = match self.rotation {
+            Rotation::RoundRobin => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed);
+                
+                (0..self.members.len())
+                    .map(|offset| (start + offset) % self.members.len())
+                    .find(|index| candidates.contains(index))
+                    .unwrap_or(candidates[0])
+            },
+            Rotation::Random => *candidates
+                .choose(&mut rand::thread_rng())
+                .unwrap_or(&candidates[0]),
+            Rotation::LeastRecentlyUsed => candidates
+                .iter()
+                .copied()
+                .min_by_key(|index| *self.members[*index].last_used_at.lock().unwrap())
+                .unwrap_or(candidates[0]),
+        };
+        
+        *self.members[index].last_used_at.lock().unwrap() = Some(now);
+        index
+    }
+    
+    /// Records failures of the request sent with the transport at `index`.
+    fn observe<R>(
+        &self,
+        index: usize,
+        result: Result<ResponseReceiver<R>, AuthenticationClientError>,
+    ) -> Result<ResponseReceiver<R>, AuthenticationClientError>
+    where
+        R: Send + 'static,
+    {
+        let failed_at = self.members[index].failed_at.clone();
+        let receiver = match result {
+            Ok(receiver) => receiver,
+            Err(error) => {
+                *failed_at.lock().unwrap() = Some(Instant::now());
+                return Err(error);
+            },
+        };
+        
+        Ok(receiver.inspect(move |response| match response {
+            Err(error) if is_failure(error) => {
+                *failed_at.lock().unwrap() = Some(Instant::now());
+            },
+            Ok(_) => {
+                *failed_at.lock().unwrap() = None;
+            },
+            _ => {},
+        }))
+    }
+}
+
+/// Checks if `error` indicates a problem with the transport rather than the request.
+fn is_failure(error: &AuthenticationClientError) -> bool {
+    error.eresult().is_none_or(|eresult| {
+        eresult == EResult::RateLimitExceeded ||
+        eresult == EResult::AccountLoginDeniedThrottle ||
+        eresult.is_retriable()
+    })
+}
+
+#[async_trait]
+impl<T> Transport for RoundRobinTransport<T>
+where
+    T: Transport,
+{
+    /// Connects every transport.
+    async fn connect(&self) -> Result<(), AuthenticationClientError> {
+        futures::future::try_join_all(self.transports().map(|transport| transport.connect())).await?;
+        
+        Ok(())
+    }
+    
+    async fn send_request<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        let index = self.pick();
+        let result = self.members[index].transport.send_request(msg, access_token).await;
+        
+        self.observe(index, result)
+    }
+    
+    async fn send_request_with_headers<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+        headers: HeaderMap,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        let index = self.pick();
+        let result = self.members[index].transport
+            .send_request_with_headers(msg, access_token, headers)
+            .await;
+        
+        self.observe(index, result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::ApiResponse;
+    use tokio::sync::oneshot;
+    use crate::proto::steammessages_auth_steamclient::CAuthentication_GetPasswordRSAPublicKey_Request;
+    
+    /// Counts requests, failing them if `fails` is set.
+    #[derive(Debug, Default)]
+    struct MockTransport {
+        requests: AtomicUsize,
+        fails: bool,
+    }
+    
+    #[async_trait]
+    impl Transport for MockTransport {
+        async fn send_request<Msg>(
+            &self,
+            _msg: Msg,
+            _access_token: Option<String>,
+        ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError>
+        where
+            Msg: ApiRequest,
+            <Msg as ApiRequest>::Response: Send,
+        {
+            let (tx, rx) = oneshot::channel();
+            let response = if self.fails {
+                Err(AuthenticationClientError::EResultNotOK(EResult::RateLimitExceeded))
+            } else {
+                Msg::Response::parse_from_reader(&mut [].as_slice()).map_err(|_error| AuthenticationClientError::NoJob)
+            };
+            
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            tx.send(response).ok();
+            
+            Ok(rx.into())
+        }
+    }
+    
+    async fn send(transport: &RoundRobinTransport<MockTransport>) -> Result<(), AuthenticationClientError> {
+        transport.send_request(CAuthentication_GetPasswordRSAPublicKey_Request::new(), None)
+            .await?
+            .await??;
+        
+        Ok(())
+    }
+    
+    fn requests(transport: &RoundRobinTransport<MockTransport>) -> Vec<usize> {
+        transport.transports()
+            .map(|transport| transport.requests.load(Ordering::Relaxed))
+            .collect()
+    }
+    
+    #[tokio::test]
+    async fn rotates_across_transports() {
+        let transport = RoundRobinTransport::new(vec![
+            MockTransport::default(),
+            MockTransport::default(),
+            MockTransport::default(),
+        ]);
+        
+        for _ in 0..6 {
+            send(&transport).await.unwrap();
+        }
+        
+        assert_eq!(requests(&transport), [2, 2, 2]);
+    }
+    
+    #[tokio::test]
+    async fn skips_failed_transports() {
+        let transport = RoundRobinTransport::new(vec![
+            MockTransport { fails: true, ..Default::default() },
+            MockTransport::default(),
+        ])
+            .rotation(Rotation::LeastRecentlyUsed);
+        
+        assert!(send(&transport).await.is_err());
+        
+        for _ in 0..3 {
+            send(&transport).await.unwrap();
+        }
+        
+        assert_eq!(requests(&transport), [1, 3]);
+    }
+}
@@ -10,8 +10,53 @@ pub enum Error {
     Proto(#[from] protobuf::Error),
     #[error("{}", .0)]
     InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
-    #[error("Unknown EResult: {}", .0)]
+    #[error("Unknown EResult: {}", EResult::describe(*.0))]
     UnknownEResult(i32),
-    #[error("Received EResult other than OK: {:?}", .0)]
+    #[error("Steam returned EResult {}", .0)]
     EResultNotOK(EResult),
+    #[error("Steam returned EResult {}: {}", .eresult, .message)]
+    EResultWithMessage {
+        /// The result.
+        eresult: EResult,
+        /// The error message sent along with the result in the `x-error_message` header.
+        message: String,
+    },
+    #[error("Request was redirected with status {} to {}", .0, .1.as_deref().unwrap_or("unknown location"))]
+    UnexpectedRedirect(reqwest::StatusCode, Option<String>),
+    #[error("Request failed with status {}: {}", .code, .body_snippet)]
+    Status {
+        /// The status code of the response.
+        code: reqwest::StatusCode,
+        /// The start of the response body, for debugging.
+        body_snippet: String,
+    },
+    #[error("Too many requests{}", .retry_after.map(|retry_after| format!(" (retry after {retry_after:?})")).unwrap_or_default())]
+    TooManyRequests {
+        /// How long Steam asked to wait before retrying, from the `Retry-After` header.
+        retry_after: Option<std::time::Duration>,
+    },
+    #[error("HTTP backend error: {}", .0)]
+    HttpBackend(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    /// Checks if the request may succeed if retried later. Requests which couldn't connect are 
+    /// retriable as they never reached Steam.
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            Self::EResultNotOK(eresult) |
+            Self::EResultWithMessage { eresult, .. } => crate::transports::retry::is_retriable_eresult(*eresult),
+            Self::Http(error) => error.is_connect(),
+            Self::TooManyRequests { .. } => true,
+            _ => false,
+        }
+    }
+    
+    /// Gets how long Steam asked to wait before retrying a rate limited request.
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            Self::TooManyRequests { retry_after } => *retry_after,
+            _ => None,
+        }
+    }
 }
\ No newline at end of file
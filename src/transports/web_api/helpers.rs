@@ -1,60 +1,74 @@
-use super::{Error, WebApiTransport};
-use crate::enums::EResult;
-use crate::net::{ApiRequest, ApiResponse};
+use super::{Error, WebApiTransport, MAX_REDIRECTS};
+use super::http::{HttpClient, HttpMethod, HttpRequest, HttpResponse};
+use crate::enums::{EResult, ELanguage};
+use crate::net::{ApiRequest, ApiResponse, ApiMethod};
 use crate::helpers::{encode_base64, create_api_headers};
-use std::ops::Deref;
+use crate::transports::retry::parse_retry_after;
 use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE, LOCATION, RETRY_AFTER};
+use reqwest::redirect;
 use bytes::{BytesMut, Buf};
 use lazy_static::lazy_static;
 
-/// Gets a response.
-pub async fn get_response<Msg>(
-    client: &reqwest::Client,
+/// The most of a failed response's body kept in [`Error::Status`].
+const MAX_BODY_SNIPPET_LENGTH: usize = 1024;
+
+/// Gets a response. `extra_headers` are added to the request, replacing any default headers of 
+/// the same name.
+pub async fn get_response<Msg, C>(
+    client: &C,
     msg: Msg,
     access_token: Option<String>,
+    extra_headers: HeaderMap,
+    language: ELanguage,
 ) -> Result<Msg::Response, Error>
 where
     Msg: ApiRequest,
     <Msg as ApiRequest>::Response: Send,
+    C: HttpClient,
 {
-    let pathname = format!(
-        "I{}Service/{}/v{}",
-        Msg::INTERFACE,
-        Msg::METHOD,
-        Msg::VERSION,
-    );
-    let headers = create_api_headers()?;
+    let pathname = ApiMethod::of::<Msg>().pathname();
+    let mut headers = create_api_headers()?;
+    
+    headers.append(ACCEPT_LANGUAGE, HeaderValue::from_str(language.web_api_language_code())?);
+    headers.extend(extra_headers);
+    
     let url = WebApiTransport::get_url(&pathname);
     let encoded_message = encode_base64(msg.write_to_bytes()?);
-    let request = if is_get_request(&pathname) {
-        let mut query = vec![("input_protobuf_encoded", encoded_message.as_str())];
+    let params = vec![
+        ("input_protobuf_encoded", encoded_message),
+        ("language", language.api_name().to_string()),
+    ];
+    let request = if Msg::HTTP_METHOD == HttpMethod::Get {
+        let mut query = params;
 
-        if let Some(access_token) = &access_token {
-            query.push(("access_token", access_token.as_str()));
+        if let Some(access_token) = access_token {
+            query.push(("access_token", access_token));
         }
 
         log::debug!("GET {}", url);
-        client.get(&url)
-            .query(&query)
+        HttpRequest {
+            method: HttpMethod::Get,
+            url,
+            headers,
+            query,
+            form: Vec::new(),
+        }
     } else {
-        let form = reqwest::multipart::Form::new()
-            .text("input_protobuf_encoded", encoded_message);
-
         log::debug!("POST {}", url);
-        client.post(&url)
-            .multipart(form)
+        HttpRequest {
+            method: HttpMethod::Post,
+            url,
+            headers,
+            query: Vec::new(),
+            form: params,
+        }
     };
-    let response = request
-        .headers(headers)
-        .send()
-        .await?;
+    let response = client.execute(request).await?;
 
     check_response_for_errors(&response)?;
 
-    let response = response
-        .bytes()
-        .await?;
-    let bytes = BytesMut::from(response.deref());
+    let bytes = BytesMut::from(response.body.as_slice());
     let mut reader = bytes.reader();
     let response = Msg::Response::parse_from_reader(&mut reader)?;
 
@@ -62,14 +76,33 @@ where
 }
 
 // Checks response for errors.
-fn check_response_for_errors(response: &reqwest::Response) -> Result<(), Error> {
-    let headers = response.headers();
+fn check_response_for_errors(response: &HttpResponse) -> Result<(), Error> {
+    let headers = &response.headers;
 
+    // Checked first so the delay Steam asked for is kept regardless of the EResult
+    if response.status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = headers.get(RETRY_AFTER)
+            .and_then(|retry_after| retry_after.to_str().ok())
+            .and_then(|retry_after| parse_retry_after(retry_after, chrono::Utc::now()));
+        
+        return Err(Error::TooManyRequests { retry_after });
+    }
+    
     if let Some(eresult) = headers.get("x-eresult") {
         if let Ok(Ok(eresult)) = eresult.to_str().map(|s| s.parse::<i32>()) {
             if let Ok(eresult) = EResult::try_from(eresult) {
                 if eresult != EResult::OK {
-                    return Err(Error::EResultNotOK(eresult));
+                    let message = headers.get("x-error_message")
+                        .and_then(|message| message.to_str().ok())
+                        .filter(|message| !message.is_empty());
+                    
+                    return Err(match message {
+                        Some(message) => Error::EResultWithMessage {
+                            eresult,
+                            message: message.into(),
+                        },
+                        None => Error::EResultNotOK(eresult),
+                    });
                 }
             } else {
                 return Err(Error::UnknownEResult(eresult));
@@ -77,14 +110,265 @@ fn check_response_for_errors(response: &reqwest::Response) -> Result<(), Error>
         }
     }
 
-    if response.status() != StatusCode::OK {
-
+    if response.status.is_redirection() {
+        let location = response.headers
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .map(String::from);
+        
+        return Err(Error::UnexpectedRedirect(response.status, location));
+    }
+    
+    // Steam responds with 503 during maintenance
+    if response.status == StatusCode::SERVICE_UNAVAILABLE {
+        return Err(Error::EResultNotOK(EResult::ServiceUnavailable));
+    }
+    
+    if !response.status.is_success() {
+        return Err(Error::Status {
+            code: response.status,
+            body_snippet: body_snippet(&response.body),
+        });
     }
 
     Ok(())
 }
 
-/// Checks if the request is a GET request.
-fn is_get_request(endpoint: &str) -> bool {
-    endpoint == "IAuthenticationService/GetPasswordRSAPublicKey/v1"
-}
\ No newline at end of file
+/// Gets the start of `body` as text, up to [`MAX_BODY_SNIPPET_LENGTH`] bytes.
+fn body_snippet(body: &[u8]) -> String {
+    let mut snippet = String::from_utf8_lossy(&body[..body.len().min(MAX_BODY_SNIPPET_LENGTH)])
+        .into_owned();
+    
+    if body.len() > MAX_BODY_SNIPPET_LENGTH {
+        snippet.push_str("...");
+    }
+    
+    snippet
+}
+
+/// Creates a client builder with the defaults for the Web API client.
+pub fn client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .redirect(redirect_policy())
+}
+
+/// Creates the proxies configured by the `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY` and 
+/// `NO_PROXY` variables, read using `var`. Lowercase names take precedence, and more specific 
+/// proxies are returned first so they're tried before `ALL_PROXY`.
+pub fn env_proxies(var: impl Fn(&str) -> Option<String>) -> Result<Vec<reqwest::Proxy>, Error> {
+    let no_proxy = proxy_var(&var, "NO_PROXY")
+        .and_then(|no_proxy| reqwest::NoProxy::from_string(&no_proxy));
+    let mut proxies = Vec::new();
+    
+    if let Some(url) = proxy_var(&var, "HTTPS_PROXY") {
+        proxies.push(reqwest::Proxy::https(url)?.no_proxy(no_proxy.clone()));
+    }
+    
+    if let Some(url) = proxy_var(&var, "HTTP_PROXY") {
+        proxies.push(reqwest::Proxy::http(url)?.no_proxy(no_proxy.clone()));
+    }
+    
+    if let Some(url) = proxy_var(&var, "ALL_PROXY") {
+        proxies.push(reqwest::Proxy::all(url)?.no_proxy(no_proxy));
+    }
+    
+    Ok(proxies)
+}
+
+/// Reads the proxy variable `name` using `var`, preferring the lowercase name. Empty values are 
+/// ignored.
+fn proxy_var(var: &impl Fn(&str) -> Option<String>, name: &str) -> Option<String> {
+    [name.to_lowercase(), name.to_string()]
+        .iter()
+        .filter_map(|name| var(name))
+        .find(|value| !value.is_empty())
+}
+
+/// Creates the redirect policy for the Web API client.
+/// 
+/// Redirects are only followed to the same host and at most [`MAX_REDIRECTS`] times. A redirect 
+/// to another host is not followed so that the `access_token` query parameter is never sent to a 
+/// different origin. The redirect response is returned instead, which results in an 
+/// [`Error::UnexpectedRedirect`]. Note that a redirected POST is retried as a GET without its 
+/// body, which Steam will reject.
+pub fn redirect_policy() -> redirect::Policy {
+    redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() > MAX_REDIRECTS {
+            return attempt.error("too many redirects");
+        }
+        
+        let is_same_host = attempt.previous()
+            .first()
+            .map(|original| original.host_str() == attempt.url().host_str())
+            .unwrap_or(false);
+        
+        if is_same_host {
+            attempt.follow()
+        } else {
+            log::debug!("Not following cross-origin redirect to {}", attempt.url());
+            attempt.stop()
+        }
+    })
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::proto::steammessages_auth_steamclient::{
+        CAuthentication_GetPasswordRSAPublicKey_Request,
+        CAuthentication_GetPasswordRSAPublicKey_Response,
+    };
+    use async_trait::async_trait;
+    use reqwest::header::{USER_AGENT, COOKIE};
+    use protobuf::Message;
+    use std::sync::Mutex;
+    
+    /// Responds to every request with `status` and `body`, recording the requests.
+    pub(crate) struct MockClient {
+        status: StatusCode,
+        body: Vec<u8>,
+        pub(crate) requests: Mutex<Vec<HttpRequest>>,
+    }
+    
+    impl MockClient {
+        /// Creates a client responding with `status` and `body`.
+        pub(crate) fn new(status: StatusCode, body: Vec<u8>) -> Self {
+            Self {
+                status,
+                body,
+                requests: Mutex::new(Vec::new()),
+            }
+        }
+    }
+    
+    #[async_trait]
+    impl HttpClient for MockClient {
+        async fn execute(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+            self.requests.lock().unwrap().push(request);
+            
+            Ok(HttpResponse {
+                status: self.status,
+                headers: HeaderMap::new(),
+                body: self.body.clone(),
+            })
+        }
+    }
+    
+    #[tokio::test]
+    async fn gets_response_using_http_client() {
+        let mut body = CAuthentication_GetPasswordRSAPublicKey_Response::new();
+        
+        body.set_publickey_mod("c0ffee".into());
+        
+        let client = MockClient::new(StatusCode::OK, body.write_to_bytes().unwrap());
+        let mut msg = CAuthentication_GetPasswordRSAPublicKey_Request::new();
+        
+        msg.set_account_name("gabe".into());
+        
+        let response = get_response(&client, msg, None, HeaderMap::new(), ELanguage::English).await.unwrap();
+        let requests = client.requests.lock().unwrap();
+        
+        assert_eq!(response.publickey_mod(), "c0ffee");
+        assert_eq!(requests[0].method, HttpMethod::Get);
+        assert_eq!(requests[0].url, "https://api.steampowered.com/IAuthenticationService/GetPasswordRSAPublicKey/v1");
+        assert!(requests[0].query.iter().any(|(name, value)| *name == "language" && value == "english"));
+    }
+    
+    #[tokio::test]
+    async fn service_unavailable_is_retriable() {
+        let client = MockClient::new(StatusCode::SERVICE_UNAVAILABLE, Vec::new());
+        let error = get_response(&client, CAuthentication_GetPasswordRSAPublicKey_Request::new(), None, HeaderMap::new(), ELanguage::English)
+            .await
+            .unwrap_err();
+        
+        assert!(matches!(error, Error::EResultNotOK(EResult::ServiceUnavailable)));
+        assert!(error.is_retriable());
+    }
+    
+    #[test]
+    fn keeps_error_message_of_failed_response() {
+        let mut headers = HeaderMap::new();
+        
+        headers.insert("x-eresult", "84".parse().unwrap());
+        headers.insert("x-error_message", "Too many attempts".parse().unwrap());
+        
+        let response = HttpResponse {
+            status: StatusCode::OK,
+            headers,
+            body: Vec::new(),
+        };
+        
+        assert!(matches!(
+            check_response_for_errors(&response),
+            Err(Error::EResultWithMessage { eresult: EResult::RateLimitExceeded, message }) if message == "Too many attempts",
+        ));
+    }
+    
+    #[test]
+    fn keeps_retry_after_of_rate_limited_response() {
+        let mut headers = HeaderMap::new();
+        
+        headers.insert("x-eresult", "84".parse().unwrap());
+        headers.insert(RETRY_AFTER, "30".parse().unwrap());
+        
+        let response = HttpResponse {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            headers,
+            body: Vec::new(),
+        };
+        let error = check_response_for_errors(&response).unwrap_err();
+        
+        assert!(matches!(error, Error::TooManyRequests { retry_after: Some(retry_after) } if retry_after.as_secs() == 30));
+        assert!(error.is_retriable());
+        assert_eq!(error.to_string(), "Too many requests (retry after 30s)");
+    }
+    
+    #[tokio::test]
+    async fn captures_body_of_failed_response() {
+        let client = MockClient::new(StatusCode::BAD_REQUEST, [b"Invalid ".as_slice(), &[b'x'; 2000]].concat());
+        let error = get_response(&client, CAuthentication_GetPasswordRSAPublicKey_Request::new(), None, HeaderMap::new(), ELanguage::English)
+            .await
+            .unwrap_err();
+        
+        assert!(matches!(
+            error,
+            Error::Status { code: StatusCode::BAD_REQUEST, body_snippet }
+                if body_snippet.starts_with("Invalid ") && body_snippet.len() == MAX_BODY_SNIPPET_LENGTH + 3
+        ));
+    }
+    
+    #[test]
+    fn reads_proxies_from_env() {
+        let var = |name: &str| match name {
+            "https_proxy" => Some("http://lower:8080".to_string()),
+            "HTTPS_PROXY" => Some("http://upper:8080".to_string()),
+            "HTTP_PROXY" => Some(String::new()),
+            "NO_PROXY" => Some("localhost,.example.com".to_string()),
+            _ => None,
+        };
+        
+        assert_eq!(proxy_var(&var, "HTTPS_PROXY").as_deref(), Some("http://lower:8080"));
+        assert_eq!(proxy_var(&var, "HTTP_PROXY"), None);
+        assert_eq!(env_proxies(var).unwrap().len(), 1);
+        assert!(env_proxies(|name| (name == "ALL_PROXY").then(|| "not a url".to_string())).is_err());
+    }
+    
+    #[tokio::test]
+    async fn sends_platform_headers() {
+        let client = MockClient::new(StatusCode::OK, Vec::new());
+        let mut headers = HeaderMap::new();
+        
+        headers.insert(USER_AGENT, HeaderValue::from_static(crate::helpers::MOBILE_USER_AGENT));
+        headers.insert(COOKIE, HeaderValue::from_static(crate::helpers::MOBILE_CLIENT_COOKIE));
+        
+        get_response(&client, CAuthentication_GetPasswordRSAPublicKey_Request::new(), None, headers, ELanguage::English)
+            .await
+            .unwrap();
+        
+        let requests = client.requests.lock().unwrap();
+        
+        assert_eq!(requests[0].headers[USER_AGENT], "okhttp/3.12.12");
+        assert!(requests[0].headers[COOKIE].to_str().unwrap().contains("mobileClient=android"));
+        assert!(requests[0].headers.contains_key(ACCEPT_LANGUAGE));
+    }
+}
@@ -0,0 +1,104 @@
+use super::Error;
+use reqwest::StatusCode;
+use reqwest::header::HeaderMap;
+use async_trait::async_trait;
+
+/// The method of an [`HttpRequest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HttpMethod {
+    Get,
+    Post,
+}
+
+/// A request to the Web API.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: HttpMethod,
+    pub url: String,
+    pub headers: HeaderMap,
+    /// Parameters sent in the query string.
+    pub query: Vec<(&'static str, String)>,
+    /// Parameters sent as a `multipart/form-data` body. Empty for GET requests.
+    pub form: Vec<(&'static str, String)>,
+}
+
+/// A response from the Web API.
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// An HTTP client used by [`super::WebApiTransport`] to send requests. This is implemented for 
+/// [`reqwest::Client`], which is used by default. Implement it to use another HTTP backend.
+/// 
+/// `reqwest` can't be left out of the build by swapping the client. Transferring web cookies, 
+/// fetching the CM server list, syncing the Steam Guard clock, OpenID logins and the login 
+/// approver all call it directly, and the request and response types use its headers and 
+/// status codes, so there is no feature to disable it.
+#[async_trait]
+pub trait HttpClient: Send + Sync + 'static {
+    /// Sends `request` and reads the full response.
+    async fn execute(&self, request: HttpRequest) -> Result<HttpResponse, Error>;
+}
+
+#[async_trait]
+impl HttpClient for reqwest::Client {
+    async fn execute(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        let builder = match request.method {
+            HttpMethod::Get => self.get(&request.url),
+            HttpMethod::Post => {
+                let form = request.form
+                    .into_iter()
+                    .fold(reqwest::multipart::Form::new(), |form, (name, value)| form.text(name, value));
+                
+                self.post(&request.url).multipart(form)
+            },
+        };
+        let response = builder
+            .query(&request.query)
+            .headers(request.headers)
+            .send()
+            .await
+            .map_err(without_query)?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await.map_err(without_query)?.to_vec();
+        
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// Removes the query string from the URL of `error`, which may contain the access token, so 
+/// the error can be displayed safely.
+fn without_query(mut error: reqwest::Error) -> reqwest::Error {
+    if let Some(url) = error.url_mut() {
+        url.set_query(None);
+    }
+    
+    error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[tokio::test]
+    async fn errors_omit_access_token() {
+        let request = HttpRequest {
+            method: HttpMethod::Get,
+            url: "http://127.0.0.1:0/".into(),
+            headers: HeaderMap::new(),
+            query: vec![("access_token", "secret".into())],
+            form: Vec::new(),
+        };
+        let error = HttpClient::execute(&reqwest::Client::new(), request).await.unwrap_err();
+        
+        assert!(!error.to_string().contains("secret"));
+    }
+}
@@ -1,13 +1,13 @@
 use super::cm_server::CmServer;
 use std::ops::{Deref, DerefMut};
 use std::fmt;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use chrono::{Duration, Utc};
 use rand::seq::SliceRandom;
 use serde::Deserialize;
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use reqwest::header::{HeaderMap, HeaderValue, InvalidHeaderValue};
-use reqwest::header::{USER_AGENT, ACCEPT_CHARSET, ACCEPT};
+use reqwest::header::{USER_AGENT, ACCEPT_CHARSET, ACCEPT, RETRY_AFTER};
 use lazy_static::lazy_static;
 
 lazy_static! {
@@ -23,7 +23,7 @@ pub enum Error {
     #[error("{}", .0)]
     Reqwest(#[from] reqwest::Error),
     #[error("HTTP request returned with response status: {}", .0.status())]
-    ReqwestResponseNotOk(reqwest::Response),
+    ReqwestResponseNotOk(Box<reqwest::Response>),
     #[error("{}", .0)]
     InvalidHeaderValue(#[from] InvalidHeaderValue),
     #[error("CM server returned an error with message: {}", .0)]
@@ -32,12 +32,39 @@ pub enum Error {
     VdfParse(#[from] Box<keyvalues_serde::error::Error>),
 }
 
+impl Error {
+    /// Checks if the directory has no servers to connect to, e.g. because Steam is down for 
+    /// maintenance, rather than the request itself failing.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            Self::NoCmServer |
+            Self::NoCmServerList |
+            Self::CmServerListResponseMessage(_) => true,
+            Self::ReqwestResponseNotOk(response) => response.status() == StatusCode::SERVICE_UNAVAILABLE,
+            _ => false,
+        }
+    }
+    
+    /// Gets the delay from the `Retry-After` header of the response, if one was given.
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        let Self::ReqwestResponseNotOk(response) = self else {
+            return None;
+        };
+        let retry_after = response.headers()
+            .get(RETRY_AFTER)?
+            .to_str().ok()?;
+        
+        crate::transports::retry::parse_retry_after(retry_after, Utc::now())
+    }
+}
+
 /// A container for a list of cached [`CmServer`].
 #[derive(Debug)]
 pub struct CmListCache {
     inner: Vec<CmServer>,
     expiry_duration: Duration,
     last_cached: Option<chrono::DateTime<Utc>>,
+    cell_id: u32,
 }
 
 impl Default for CmListCache {
@@ -53,16 +80,52 @@ impl CmListCache {
             inner: Vec::new(),
             expiry_duration: Duration::try_minutes(5).unwrap(),
             last_cached: None,
+            cell_id: 0,
         }
     }
     
+    /// Creates a [`CmListCache`] holding `servers`, treated as freshly fetched.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_servers(servers: Vec<CmServer>) -> Self {
+        Self {
+            inner: servers,
+            last_cached: Some(Utc::now()),
+            ..Self::new()
+        }
+    }
+    
+    /// Sets the cell ID sent when fetching the list, e.g. the one assigned in the logon 
+    /// response, so servers near it are returned. If it changed, the cached list is expired.
+    pub fn set_cell_id(&mut self, cell_id: u32) {
+        if cell_id != self.cell_id {
+            self.cell_id = cell_id;
+            self.last_cached = None;
+        }
+    }
+    
+    /// Gets the cell ID sent when fetching the list. Defaults to 0.
+    pub fn cell_id(&self) -> u32 {
+        self.cell_id
+    }
+    
     pub fn pick_random_websocket_server(&self) -> Option<CmServer> {
+        self.pick_random_websocket_server_excluding(&HashSet::new())
+    }
+    
+    /// Picks a random websocket server whose endpoint is not in `excluded`.
+    pub fn pick_random_websocket_server_excluding(&self, excluded: &HashSet<String>) -> Option<CmServer> {
         self.pick_random(&|cm_server| {
             cm_server.r#type == "websockets" &&
-            cm_server.realm == "steamglobal"
+            cm_server.realm == "steamglobal" &&
+            !excluded.contains(&cm_server.endpoint)
         })
     }
     
+    /// Checks if the list contains any websocket servers.
+    pub fn has_websocket_servers(&self) -> bool {
+        self.pick_random_websocket_server().is_some()
+    }
+    
     pub fn pick_random(&self, filter: &dyn Fn(&&CmServer) -> bool) -> Option<CmServer> {
         let mut servers = self.inner
             .iter()
@@ -78,6 +141,11 @@ impl CmListCache {
     
     /// Updates the list of servers, if they are oudated.
     pub async fn update(&mut self) -> Result<(), Error> {
+        self.update_with_client(&DEFAULT_CLIENT).await
+    }
+    
+    /// Same as `update`, fetching the list using `client`, e.g. one configured with a proxy.
+    pub async fn update_with_client(&mut self, client: &Client) -> Result<(), Error> {
         let now = chrono::offset::Utc::now();
         let is_expired = if let Some(last_cached) = self.last_cached {
             let difference = now - last_cached;
@@ -93,8 +161,18 @@ impl CmListCache {
             return Ok(())
         }
         
-        self.inner = get_cm_list().await?;
-        self.last_cached = Some(now);
+        self.refresh_with_client(client).await
+    }
+    
+    /// Fetches the list of servers, regardless of whether they are outdated.
+    pub async fn refresh(&mut self) -> Result<(), Error> {
+        self.refresh_with_client(&DEFAULT_CLIENT).await
+    }
+    
+    /// Same as `refresh`, fetching the list using `client`, e.g. one configured with a proxy.
+    pub async fn refresh_with_client(&mut self, client: &Client) -> Result<(), Error> {
+        self.inner = get_cm_list(client, self.cell_id).await?;
+        self.last_cached = Some(chrono::offset::Utc::now());
         
         Ok(())
     }
@@ -125,20 +203,20 @@ impl DerefMut for CmListCache {
     }
 }
     
-async fn get_cm_list() -> Result<Vec<CmServer>, Error> {
+async fn get_cm_list(client: &Client, cell_id: u32) -> Result<Vec<CmServer>, Error> {
     // todo handle errors
-    fetch_cm_list().await
+    fetch_cm_list(client, cell_id).await
 }
 
-async fn fetch_cm_list() -> Result<Vec<CmServer>, Error> {
-    let url = "https://api.steampowered.com/ISteamDirectory/GetCMListForConnect/v0001/?cellid=0&format=vdf";
+async fn fetch_cm_list(client: &Client, cell_id: u32) -> Result<Vec<CmServer>, Error> {
+    let url = format!("https://api.steampowered.com/ISteamDirectory/GetCMListForConnect/v0001/?cellid={cell_id}&format=vdf");
     let mut headers = HeaderMap::new();
     
     headers.append(USER_AGENT, HeaderValue::from_str("Valve/Steam HTTP Client 1.0")?);
     headers.append(ACCEPT_CHARSET,HeaderValue::from_str("ISO-8859-1,utf-8,*;q=0.7")?);
     headers.append(ACCEPT, HeaderValue::from_str("text/html,*/*;q=0.9")?);
     
-    let response = DEFAULT_CLIENT.get(url)
+    let response = client.get(url)
         .headers(headers)
         .send().await?;
     let text = check_response_ok(response).await?
@@ -150,9 +228,9 @@ async fn fetch_cm_list() -> Result<Vec<CmServer>, Error> {
 /// Checks if the response is OK.
 async fn check_response_ok(response: reqwest::Response) -> Result<reqwest::Response, Error> {
     match response.status().as_u16() {
-        300..=399 => Err(Error::ReqwestResponseNotOk(response)),
-        400..=499 => Err(Error::ReqwestResponseNotOk(response)),
-        500..=599 => Err(Error::ReqwestResponseNotOk(response)),
+        300..=399 => Err(Error::ReqwestResponseNotOk(Box::new(response))),
+        400..=499 => Err(Error::ReqwestResponseNotOk(Box::new(response))),
+        500..=599 => Err(Error::ReqwestResponseNotOk(Box::new(response))),
         _ => Ok(response),
     }
 }
@@ -200,4 +278,26 @@ mod tests {
         
         assert_eq!(serverlist.first().unwrap().endpoint, "ext1-ord1.steamserver.net:27017");
     }
+    
+    #[test]
+    fn maintenance_is_unavailable() {
+        let text = r#""response"
+        {
+            "serverlist"
+            {
+            }
+            "success"	"1"
+            "message"	""
+        }"#;
+        
+        assert!(parse_cm_list(text).unwrap_err().is_unavailable());
+        
+        let text = r#""response"
+        {
+            "success"	"2"
+            "message"	"Steam is down for maintenance"
+        }"#;
+        
+        assert!(parse_cm_list(text).unwrap_err().is_unavailable());
+    }
 }
\ No newline at end of file
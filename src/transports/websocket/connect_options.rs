@@ -0,0 +1,117 @@
+use super::{OverflowPolicy, JobIdGenerator, RandomJobIdGenerator, DEFAULT_MAX_DECOMPRESSED_SIZE};
+use crate::transports::RetryPolicy;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tokio_tungstenite::tungstenite::http::HeaderMap;
+
+/// Which CM servers a connection may use.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CmSelection {
+    /// Connects to a random server from the CM list, failing over to other servers if it can't 
+    /// be reached.
+    #[default]
+    Failover,
+    /// Only connects to `endpoint`, without fetching the CM list or failing over to other 
+    /// servers, e.g. to keep a session on one IP address.
+    Pinned {
+        /// The endpoint, either a `host:port` pair such as `cmp1-fra1.steamserver.net:443` or a 
+        /// full websocket URL. IPv6 addresses with a port are enclosed in brackets, e.g. 
+        /// `[2001:db8::1]:443`.
+        endpoint: String,
+        /// Whether [`WebSocketCMTransport::reconnect`] reconnects to the endpoint. If `false`, 
+        /// reconnecting fails with [`Error::PinnedEndpointDisconnected`].
+        /// 
+        /// [`WebSocketCMTransport::reconnect`]: super::WebSocketCMTransport::reconnect 
+        /// [`Error::PinnedEndpointDisconnected`]: super::Error::PinnedEndpointDisconnected
+        reconnect: bool,
+    },
+}
+
+/// Options for connecting to a CM server.
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    /// Headers sent with the websocket handshake. Defaults to none.
+    pub headers: HeaderMap,
+    /// Whether to set `TCP_NODELAY` on the socket, disabling Nagle's algorithm. The CM protocol 
+    /// is request/response, so small messages shouldn't wait to be batched. Defaults to `true`.
+    pub tcp_nodelay: bool,
+    /// How long the connection can be idle before TCP keepalive probes are sent. `None` leaves 
+    /// keepalive disabled. Defaults to `None`.
+    pub tcp_keepalive: Option<Duration>,
+    /// The policy for re-fetching the list of CM servers when the directory has no servers to 
+    /// connect to, e.g. during maintenance. If the list is still empty once retries are 
+    /// exhausted, connecting fails with `Error::NoCmServersAvailable`. Defaults to 2 retries.
+    pub directory_retry_policy: RetryPolicy,
+    /// What to do when messages that aren't responses to a request arrive faster than they're 
+    /// received. Defaults to [`OverflowPolicy::DropNewest`], which never stalls the connection.
+    pub overflow_policy: OverflowPolicy,
+    /// Which CM servers to connect to. Defaults to [`CmSelection::Failover`].
+    pub cm_selection: CmSelection,
+    /// Whether a message with an unknown `EResult` fails to be handled. If `false`, the raw value 
+    /// is passed to the request awaiting the message as [`Error::UnknownEResult`], and messages 
+    /// that can't be handled don't prevent handling the rest of a `Multi` message. Unknown 
+    /// `EMsg` values are always passed through. Enabling this is useful in tests to catch 
+    /// protocol changes. Defaults to `false`.
+    /// 
+    /// [`Error::UnknownEResult`]: super::Error::UnknownEResult
+    pub strict_enums: bool,
+    /// The client used to fetch the list of CM servers from `ISteamDirectory`, e.g. the same 
+    /// client configured with a proxy and timeouts used for other requests, so the lookup 
+    /// doesn't bypass the proxy. The websocket connection itself is made directly to the CM 
+    /// server. Defaults to a shared client with the default settings.
+    pub directory_client: Option<reqwest::Client>,
+    /// The source of the job IDs used to match responses to requests. Defaults to 
+    /// [`RandomJobIdGenerator`].
+    pub jobid_generator: Arc<dyn JobIdGenerator>,
+    /// The maximum size in bytes a compressed `Multi` message may have once decompressed. Larger 
+    /// messages fail with [`Error::DecompressedTooLarge`] rather than exhausting memory, 
+    /// protecting against a small payload inflating enormously. Defaults to 
+    /// [`DEFAULT_MAX_DECOMPRESSED_SIZE`] (16 MiB).
+    /// 
+    /// [`Error::DecompressedTooLarge`]: super::Error::DecompressedTooLarge
+    pub max_decompressed_size: usize,
+    /// How long the connection can go without receiving anything before it's treated as dead, 
+    /// e.g. when a half-open TCP connection goes silent without a close frame, which TCP 
+    /// keepalive may not detect. Requests in flight fail with [`Error::IdleTimeout`] and the next 
+    /// request reconnects. `None` disables the timeout, which is the default.
+    /// 
+    /// The transport doesn't send heartbeats, so a connection with no requests in flight goes 
+    /// quiet on its own. Set this longer than the gaps expected between requests.
+    /// 
+    /// [`Error::IdleTimeout`]: super::Error::IdleTimeout
+    pub idle_timeout: Option<Duration>,
+    /// Cancels the connection. Once cancelled, connecting, reconnecting and sending requests 
+    /// fail with [`Error::Cancelled`], requests in flight fail the same way, and the transport 
+    /// stops reading from and writing to the connection. Defaults to a token that is never 
+    /// cancelled.
+    /// 
+    /// [`Error::Cancelled`]: super::Error::Cancelled
+    pub cancellation_token: CancellationToken,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            headers: HeaderMap::new(),
+            tcp_nodelay: true,
+            tcp_keepalive: None,
+            directory_retry_policy: RetryPolicy::new(2),
+            overflow_policy: OverflowPolicy::default(),
+            cm_selection: CmSelection::default(),
+            strict_enums: false,
+            directory_client: None,
+            jobid_generator: Arc::new(RandomJobIdGenerator),
+            max_decompressed_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+            idle_timeout: None,
+            cancellation_token: CancellationToken::new(),
+        }
+    }
+}
+
+impl ConnectOptions {
+    /// Gets the client used to fetch the list of CM servers.
+    pub(crate) fn cm_list_client(&self) -> &reqwest::Client {
+        self.directory_client.as_ref().unwrap_or(&super::cm_list_cache::DEFAULT_CLIENT)
+    }
+}
@@ -0,0 +1,28 @@
+use crate::enums::EResult;
+
+/// Why a websocket connection to a CM server was closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The server closed the connection with a close frame.
+    ServerClose {
+        /// The close code, if the close frame had one.
+        code: Option<u16>,
+        /// The reason given for closing the connection.
+        reason: String,
+    },
+    /// The server told us to try another CM server.
+    TryAnotherCm(EResult),
+    /// The server rejected the protocol version sent in the `ClientHello`.
+    ProtocolVersionRejected(u32),
+    /// The connection failed with an IO error of this kind, or ended without a close frame.
+    Io(std::io::ErrorKind),
+    /// Nothing was received from the server for the idle timeout, so the connection was treated 
+    /// as dead.
+    IdleTimeout,
+    /// The connection failed with a websocket protocol error.
+    Protocol(String),
+    /// The connection was cancelled using [`ConnectOptions::cancellation_token`].
+    /// 
+    /// [`ConnectOptions::cancellation_token`]: super::ConnectOptions::cancellation_token
+    Cancelled,
+}
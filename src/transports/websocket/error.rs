@@ -1,5 +1,5 @@
 use super::cm_list_cache;
-use crate::enums::EResult;
+use crate::enums::{EResult, EMsg};
 use tokio_tungstenite::tungstenite;
 
 #[derive(Debug, thiserror::Error)]
@@ -8,6 +8,11 @@ pub enum Error {
     CmServer(#[from] cm_list_cache::Error),
     #[error("IO: {}", .0)]
     OI(#[from] std::io::Error),
+    #[error("No CM servers are available, Steam may be down for maintenance{}", .retry_after.map(|retry_after| format!(" (retry after {retry_after:?})")).unwrap_or_default())]
+    NoCmServersAvailable {
+        /// How long to wait before trying again, if the directory gave a hint.
+        retry_after: Option<std::time::Duration>,
+    },
     #[error("Invalid URI with websocket: {}", .0)]
     Url(#[from] tungstenite::http::uri::InvalidUri),
     #[error("Parsed URL does not contain hostname")]
@@ -15,27 +20,98 @@ pub enum Error {
     #[error("HTTP error with websocket: {}", .0)]
     Http(#[from] tungstenite::http::Error),
     #[error("Connection error with websocket: {}", .0)]
-    Connection(#[from] tungstenite::Error),
+    Connection(#[source] Box<tungstenite::Error>),
     #[error("Response error: {}", .0)]
     ResponseError(String),
-    #[error("Response returned empty body without an error message")]
+    #[error("Response has no body, result or error message")]
     NoBodyInResponse,
-    #[error("Received ClientLogOnResponse with result: {:?} (try another CM)", .0)]
-    ClientLogOnResponseTryAnotherCM(EResult),
-    #[error("Received unexpected non-protobuf message: {}", .0)]
+    #[error("CM rejected the logon with EResult {}{}; try another CM", .eresult, .eresult_extended.map(|extended| format!(" (extended result {extended})")).unwrap_or_default())]
+    ClientLogOnResponseTryAnotherCM {
+        /// The result of the logon.
+        eresult: EResult,
+        /// The extended result sent alongside `eresult`, which tells apart failures that share 
+        /// the same result such as [`EResult::Fail`], if one was sent.
+        eresult_extended: Option<i32>,
+    },
+    #[error("Received unexpected non-protobuf message: {}", EMsg::describe(*.0 & !super::PROTO_MASK))]
     UnexpectedNonProtobufMessage(u32),
+    #[error("Length {} exceeds the {} bytes remaining in the message", .length, .remaining)]
+    LengthOutOfBounds {
+        /// The length read from the message.
+        length: u32,
+        /// The number of bytes remaining after the length.
+        remaining: usize,
+    },
+    #[error("Multi message exceeds {} bytes when decompressed", .0)]
+    DecompressedTooLarge(usize),
     #[error("Error with protobuf message: {}", .0)]
     Proto(#[from] protobuf::Error),
     #[error("Wrong service method: expected {}; got {}", .0, .1)]
     DifferentServiceMethod(&'static str, String),
+    #[error("Header \"{}\" is set by the websocket handshake and can't be overridden", .0)]
+    ReservedHandshakeHeader(String),
+    #[error("Websocket connection is closed")]
+    ConnectionClosed,
+    #[error("Websocket connection was closed by the server with code {}: {}", .code.map(|code| code.to_string()).unwrap_or_else(|| "none".into()), .reason)]
+    ClosedByServer {
+        /// The close code, if the close frame had one.
+        code: Option<u16>,
+        /// The reason given for closing the connection.
+        reason: String,
+    },
+    #[error("Not reconnecting to pinned CM endpoint {}", .0)]
+    PinnedEndpointDisconnected(String),
+    #[error("Websocket connection was replaced before a response was received")]
+    Reconnected,
+    /// Nothing was received from the CM server for this long, so the connection was treated as 
+    /// dead, e.g. a half-open TCP connection. See [`ConnectOptions::idle_timeout`].
+    /// 
+    /// [`ConnectOptions::idle_timeout`]: super::ConnectOptions::idle_timeout
+    #[error("Nothing was received from the CM server for {:?}", .0)]
+    IdleTimeout(std::time::Duration),
+    #[error("Job ID {} was reused by another request before a response was received", .0)]
+    JobIdCollision(u64),
+    #[error("Job ID generator returned 0, which is reserved for messages without a job")]
+    ZeroJobId,
+    #[error("CM server rejected protocol version {}", .0)]
+    ProtocolVersionRejected(u32),
+    #[error("Operation was cancelled")]
+    Cancelled,
     #[error("Response timed out")]
     Timeout,
     #[error("Receiver error: {}", .0)]
     RecvError(#[from] tokio::sync::oneshot::error::RecvError),
-    #[error("Unknown EMsg: {}", .0)]
-    UnknownEMsg(u32),
-    #[error("Unknown EResult: {}", .0)]
+    #[error("Unknown EResult: {}", EResult::describe(*.0))]
     UnknownEResult(i32),
-    #[error("Received EResult other than OK: {:?}", .0)]
+    #[error("Steam returned EResult {}", .0)]
     EResultNotOK(EResult),
+    #[error("Steam returned EResult {}: {}", .eresult, .message)]
+    EResultWithMessage {
+        /// The result.
+        eresult: EResult,
+        /// The error message sent along with the result in the `error_message` header field.
+        message: String,
+    },
+}
+
+// boxed as it's much larger than the other variants
+impl From<tungstenite::Error> for Error {
+    fn from(error: tungstenite::Error) -> Self {
+        Self::Connection(Box::new(error))
+    }
+}
+
+impl Error {
+    /// Checks if the request may succeed if retried later.
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            Self::EResultNotOK(eresult) |
+            Self::EResultWithMessage { eresult, .. } => crate::transports::retry::is_retriable_eresult(*eresult),
+            Self::Reconnected |
+            Self::IdleTimeout(_) |
+            Self::JobIdCollision(_) |
+            Self::NoCmServersAvailable { .. } => true,
+            _ => false,
+        }
+    }
 }
\ No newline at end of file
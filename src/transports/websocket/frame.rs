@@ -0,0 +1,259 @@
+use super::{Error, PROTO_MASK};
+use crate::enums::{EMsg, EResult};
+use crate::proto::steammessages_base::CMsgProtoBufHeader;
+use std::fmt;
+use crate::proto::steammessages_base::CMsgMulti;
+use std::io::{Cursor, Read};
+use protobuf::Message as ProtoMessage;
+use byteorder::{LittleEndian, ReadBytesExt};
+use flate2::read::GzDecoder;
+
+/// The default maximum size of a decompressed `Multi` message payload, see 
+/// [`ConnectOptions::max_decompressed_size`](super::ConnectOptions::max_decompressed_size).
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 16 * 1024 * 1024;
+
+/// A CM message decoded from a websocket frame, for inspecting traffic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedFrame {
+    /// The message type.
+    pub emsg: EMsg,
+    /// The job ID of the request this message belongs to.
+    pub jobid_source: u64,
+    /// The job ID of the request this message responds to.
+    pub jobid_target: u64,
+    /// The name of the service method, for service method calls.
+    pub target_job_name: Option<String>,
+    /// The SteamID the message is for.
+    pub steamid: u64,
+    /// The client session ID.
+    pub client_sessionid: i32,
+    /// The result of the request.
+    pub eresult: EResult,
+    /// The length of the message body in bytes.
+    pub body_len: usize,
+}
+
+impl fmt::Display for DecodedFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.emsg)?;
+        
+        if let Some(target_job_name) = &self.target_job_name {
+            write!(f, " {target_job_name}")?;
+        }
+        
+        write!(
+            f,
+            " (jobid {} -> {}, steamid {}, sessionid {}, eresult {:?}, {} bytes)",
+            self.jobid_source,
+            self.jobid_target,
+            self.steamid,
+            self.client_sessionid,
+            self.eresult,
+            self.body_len,
+        )
+    }
+}
+
+/// Decodes a CM message from the bytes of a websocket frame. Only protobuf messages are 
+/// supported.
+pub fn decode_frame(bytes: &[u8]) -> Result<DecodedFrame, Error> {
+    let (emsg, header, body) = split_frame(bytes)?;
+    let eresult = EResult::try_from(header.eresult())
+        .map_err(|_| Error::UnknownEResult(header.eresult()))?;
+    
+    Ok(DecodedFrame {
+        emsg,
+        jobid_source: header.jobid_source(),
+        jobid_target: header.jobid_target(),
+        target_job_name: header.target_job_name.clone(),
+        steamid: header.steamid(),
+        client_sessionid: header.client_sessionid(),
+        eresult,
+        body_len: body.len(),
+    })
+}
+
+/// Decodes the CM messages in the bytes of a websocket frame. `Multi` messages are expanded 
+/// into the messages they contain, nested `Multi` messages are not. Compressed `Multi` 
+/// messages are limited to [`DEFAULT_MAX_DECOMPRESSED_SIZE`] when decompressed.
+/// 
+/// Frames come from the server, so this only ever returns an error on malformed input, never 
+/// panics.
+pub fn decode_frames(bytes: &[u8]) -> Result<Vec<DecodedFrame>, Error> {
+    let (emsg, _header, body) = split_frame(bytes)?;
+    
+    if emsg != EMsg::Multi {
+        return Ok(vec![decode_frame(bytes)?]);
+    }
+    
+    let payload = multi_payload(body, DEFAULT_MAX_DECOMPRESSED_SIZE)?;
+    
+    multi_chunks(&payload)?
+        .into_iter()
+        .map(decode_frame)
+        .collect()
+}
+
+/// Gets the payload of a `Multi` message body, decompressing it if needed. The size claimed by 
+/// the message is untrusted, so decompression stops after `max_decompressed_size` bytes rather 
+/// than exhausting memory.
+pub(super) fn multi_payload(body: &[u8], max_decompressed_size: usize) -> Result<Vec<u8>, Error> {
+    let message = CMsgMulti::parse_from_bytes(body)?;
+    let payload = message.message_body();
+    
+    if message.size_unzipped() == 0 {
+        return Ok(payload.to_vec());
+    }
+    
+    let mut unzipped = Vec::new();
+    
+    GzDecoder::new(payload)
+        .take(max_decompressed_size as u64 + 1)
+        .read_to_end(&mut unzipped)?;
+    
+    if unzipped.len() > max_decompressed_size {
+        return Err(Error::DecompressedTooLarge(max_decompressed_size));
+    }
+    
+    Ok(unzipped)
+}
+
+/// Splits the payload of a `Multi` message into the frames it contains. Trailing bytes too 
+/// short to hold a chunk size are ignored.
+pub(super) fn multi_chunks(payload: &[u8]) -> Result<Vec<&[u8]>, Error> {
+    let mut cursor = Cursor::new(payload);
+    let mut chunks = Vec::new();
+    
+    while let Ok(chunk_size) = cursor.read_u32::<LittleEndian>() {
+        chunks.push(read_slice(&mut cursor, chunk_size)?);
+    }
+    
+    Ok(chunks)
+}
+
+/// Splits a frame into its [`EMsg`], header and body.
+pub(super) fn split_frame(bytes: &[u8]) -> Result<(EMsg, CMsgProtoBufHeader, &[u8]), Error> {
+    let mut cursor = Cursor::new(bytes);
+    let raw_emsg = cursor.read_u32::<LittleEndian>()?;
+    let header_length = cursor.read_u32::<LittleEndian>()?;
+    let header_buffer = read_slice(&mut cursor, header_length)?;
+    
+    if raw_emsg & PROTO_MASK == 0 {
+        return Err(Error::UnexpectedNonProtobufMessage(raw_emsg));
+    }
+    
+    let raw_emsg = raw_emsg & !PROTO_MASK;
+    let header = CMsgProtoBufHeader::parse_from_bytes(header_buffer)?;
+    let emsg = EMsg::from(raw_emsg);
+    let body = &bytes[cursor.position() as usize..];
+    
+    Ok((emsg, header, body))
+}
+
+/// Reads `length` bytes from `cursor` without copying. Lengths come from untrusted input, so 
+/// they are checked against the bytes remaining rather than used to allocate.
+pub(super) fn read_slice<'a>(
+    cursor: &mut Cursor<&'a [u8]>,
+    length: u32,
+) -> Result<&'a [u8], Error> {
+    let bytes = *cursor.get_ref();
+    let position = usize::try_from(cursor.position()).unwrap_or(usize::MAX).min(bytes.len());
+    let remaining = bytes.len() - position;
+    let end = usize::try_from(length)
+        .ok()
+        .filter(|length| *length <= remaining)
+        .map(|length| position + length)
+        .ok_or(Error::LengthOutOfBounds { length, remaining })?;
+    
+    cursor.set_position(end as u64);
+    Ok(&bytes[position..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn decodes_frame() {
+        let frame = include_bytes!("./fixtures/service_method_response.bin");
+        let decoded = decode_frame(frame).unwrap();
+        
+        assert_eq!(decoded.emsg, EMsg::ServiceMethodResponse);
+        assert_eq!(decoded.jobid_target, 0x0102030405060708);
+        assert_eq!(decoded.client_sessionid, 42);
+        assert_eq!(decoded.eresult, EResult::OK);
+        assert!(decoded.body_len > 0);
+        assert!(decoded.to_string().starts_with("ServiceMethodResponse"));
+    }
+    
+    #[test]
+    fn keeps_unknown_emsg() {
+        let mut frame = include_bytes!("./fixtures/service_method_response.bin").to_vec();
+        
+        frame[0..4].copy_from_slice(&(99999 | PROTO_MASK).to_le_bytes());
+        
+        let decoded = decode_frame(&frame).unwrap();
+        
+        assert_eq!(decoded.emsg, EMsg::Unknown(99999));
+        assert_eq!(u32::from(decoded.emsg), 99999);
+    }
+    
+    #[test]
+    fn rejects_oversized_header_length() {
+        let mut frame = include_bytes!("./fixtures/service_method_response.bin").to_vec();
+        
+        frame[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        
+        assert!(matches!(
+            split_frame(&frame),
+            Err(Error::LengthOutOfBounds { length: u32::MAX, .. }),
+        ));
+    }
+    
+    #[test]
+    fn decodes_frames_in_multi() {
+        let frame = include_bytes!("./fixtures/multi_gzip.bin");
+        let decoded = decode_frames(frame).unwrap();
+        
+        assert!(!decoded.is_empty());
+        assert!(decoded.iter().all(|frame| frame.emsg != EMsg::Multi));
+    }
+    
+    #[test]
+    fn truncated_frames_fail_without_panicking() {
+        let fixtures: [&[u8]; 3] = [
+            include_bytes!("./fixtures/service_method_response.bin"),
+            include_bytes!("./fixtures/service_method_response_invalid_password.bin"),
+            include_bytes!("./fixtures/multi_gzip.bin"),
+        ];
+        
+        for fixture in fixtures {
+            for length in 0..fixture.len() {
+                let _ = decode_frames(&fixture[..length]);
+            }
+        }
+    }
+    
+    #[test]
+    fn rejects_oversized_multi_payload() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+        
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        
+        encoder.write_all(&vec![0; 1025]).unwrap();
+        
+        let mut message = CMsgMulti::new();
+        
+        message.set_size_unzipped(1);
+        message.set_message_body(encoder.finish().unwrap());
+        
+        let body = message.write_to_bytes().unwrap();
+        
+        assert!(matches!(
+            multi_payload(&body, 1024),
+            Err(Error::DecompressedTooLarge(1024)),
+        ));
+        assert_eq!(multi_payload(&body, 1025).unwrap().len(), 1025);
+    }
+}
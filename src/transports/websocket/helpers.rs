@@ -1,14 +1,36 @@
-use super::{Error, CmListError, WebSocketCMTransport, CmListCache};
+use super::{Error, CmListError, WebSocketCMTransport, CmListCache, ConnectOptions, CmSelection};
 use super::response::ApiResponseBody;
 use crate::net::ApiRequest;
 use crate::authentication_client::Error as AuthenticationClientError;
+use crate::transports::retry::retry;
+use std::collections::HashSet;
+use std::net::Ipv6Addr;
 use std::sync::Arc;
-use futures::StreamExt;
 use tokio::sync::oneshot;
 use tokio::time::timeout;
 use tokio_tungstenite::tungstenite::http::uri::Uri;
 use tokio_tungstenite::tungstenite::http::request::Request;
-use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::http::{HeaderMap, HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::http::header::{
+    USER_AGENT,
+    ORIGIN,
+    COOKIE,
+    HOST,
+    CONNECTION,
+    UPGRADE,
+    SEC_WEBSOCKET_KEY,
+    SEC_WEBSOCKET_VERSION,
+};
+use crate::enums::EAuthTokenPlatformType;
+use crate::helpers::{DEFAULT_USER_AGENT, STEAM_CLIENT_USER_AGENT, MOBILE_USER_AGENT, MOBILE_CLIENT_COOKIE};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{WebSocketStream, MaybeTlsStream};
+#[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+use tokio_tungstenite::client_async_tls;
+use socket2::{SockRef, TcpKeepalive};
+
+/// A websocket connection to a CM server.
+pub type WebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
 /// Generate a random key for the `Sec-WebSocket-Key` header.
 fn generate_key() -> String {
@@ -18,41 +40,269 @@ fn generate_key() -> String {
     data_encoding::BASE64.encode(&r)
 }
 
-pub async fn connect_to_cm(cm_list: &Arc<tokio::sync::Mutex<CmListCache>>) -> Result<WebSocketCMTransport, Error> {
-    let cm_server = {
-        let mut cm_list = cm_list.lock().await;
-        
-        cm_list.update().await?;
+/// Headers that are set by the handshake itself and can't be overridden.
+const RESERVED_HANDSHAKE_HEADERS: [HeaderName; 5] = [
+    HOST,
+    CONNECTION,
+    UPGRADE,
+    SEC_WEBSOCKET_KEY,
+    SEC_WEBSOCKET_VERSION,
+];
+
+/// Gets the handshake headers matching those sent by the official clients for `platform_type`.
+pub fn handshake_headers(platform_type: EAuthTokenPlatformType) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    
+    match platform_type {
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_SteamClient => {
+            headers.insert(USER_AGENT, HeaderValue::from_static(STEAM_CLIENT_USER_AGENT));
+            headers.insert(ORIGIN, HeaderValue::from_static("https://steamloopback.host"));
+        },
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser => {
+            headers.insert(USER_AGENT, HeaderValue::from_static(DEFAULT_USER_AGENT));
+            headers.insert(ORIGIN, HeaderValue::from_static("https://steamcommunity.com"));
+        },
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_MobileApp => {
+            headers.insert(USER_AGENT, HeaderValue::from_static(MOBILE_USER_AGENT));
+            headers.insert(COOKIE, HeaderValue::from_static(MOBILE_CLIENT_COOKIE));
+        },
+        EAuthTokenPlatformType::k_EAuthTokenPlatformType_Unknown => {},
+    }
+    
+    headers
+}
+
+/// Checks that `headers` doesn't contain any headers set by the handshake.
+pub fn validate_handshake_headers(headers: &HeaderMap) -> Result<(), Error> {
+    for name in headers.keys() {
+        if RESERVED_HANDSHAKE_HEADERS.contains(name) {
+            return Err(Error::ReservedHandshakeHeader(name.to_string()));
+        }
+    }
+    
+    Ok(())
+}
+
+/// Converts a CM list error into an [`Error`], surfacing an empty or unavailable directory as 
+/// [`Error::NoCmServersAvailable`].
+pub fn cm_list_error(error: CmListError) -> Error {
+    if error.is_unavailable() {
+        return Error::NoCmServersAvailable {
+            retry_after: error.retry_after(),
+        };
+    }
+    
+    Error::CmServer(error)
+}
+
+/// Updates `cm_list` if it has expired, or re-fetches it if `refresh` is set. If the directory 
+/// has no websocket servers, the list is re-fetched using the directory retry policy in 
+/// `options`, failing with [`Error::NoCmServersAvailable`] once retries are exhausted. A delay 
+/// the directory asked for using `Retry-After` is waited before re-fetching.
+async fn update_cm_list(
+    cm_list: &Arc<tokio::sync::Mutex<CmListCache>>,
+    options: &ConnectOptions,
+    refresh: bool,
+) -> Result<(), Error> {
+    let mut refresh = refresh;
+    
+    retry(
+        &options.directory_retry_policy,
+        |error| matches!(error, Error::NoCmServersAvailable { .. }),
+        |error| match error {
+            Error::NoCmServersAvailable { retry_after } => *retry_after,
+            _ => None,
+        },
+        || {
+            // always re-fetch when retrying
+            let refresh = std::mem::replace(&mut refresh, true);
+            
+            async move {
+                let mut cm_list = cm_list.lock().await;
+                let client = options.cm_list_client();
+                let result = if refresh {
+                    cm_list.refresh_with_client(client).await
+                } else {
+                    cm_list.update_with_client(client).await
+                };
+                
+                result.map_err(cm_list_error)?;
+                
+                if !cm_list.has_websocket_servers() {
+                    return Err(Error::NoCmServersAvailable { retry_after: None });
+                }
+                
+                Ok(())
+            }
+        },
+    ).await
+}
+
+/// Connects to a CM server chosen according to [`ConnectOptions::cm_selection`].
+pub async fn connect_to_cm(
+    cm_list: &Arc<tokio::sync::Mutex<CmListCache>>,
+    options: &ConnectOptions,
+) -> Result<WebSocketCMTransport, Error> {
+    let (websocket, cm) = open_cm_websocket(cm_list, options, None).await?;
+    
+    Ok(WebSocketCMTransport::new(websocket, cm, cm_list.clone(), options))
+}
+
+/// Opens a websocket to a CM server chosen according to [`ConnectOptions::cm_selection`], 
+/// along with the `host:port` of the server.
+/// 
+/// A pinned endpoint is connected to directly. Otherwise, a random CM server from `cm_list` is 
+/// connected to. If connecting fails, another server is tried. Once every server in the list 
+/// has failed, the list is re-fetched and the servers in the new list are tried. If the 
+/// directory has no servers, it's re-fetched with a backoff before failing with 
+/// [`Error::NoCmServersAvailable`].
+/// 
+/// The server at `exclude`, a `host:port`, is never picked from the list, e.g. when it told us 
+/// to try another CM.
+pub async fn open_cm_websocket(
+    cm_list: &Arc<tokio::sync::Mutex<CmListCache>>,
+    options: &ConnectOptions,
+    exclude: Option<&str>,
+) -> Result<(WebSocket, String), Error> {
+    if let CmSelection::Pinned { endpoint, .. } = &options.cm_selection {
+        return open_websocket(&endpoint_url(endpoint), options).await;
+    }
+    
+    let mut failed = HashSet::new();
+    let mut is_refreshed = false;
+    let mut last_error = None;
+    
+    update_cm_list(cm_list, options, false).await?;
+    
+    loop {
         // pick a random server
-        cm_list.pick_random_websocket_server()
-    }.ok_or(Error::CmServer(CmListError::NoCmServer))?;
-    let connect_addr = format!("wss://{}/cmsocket/", cm_server.endpoint);
+        let cm_server = cm_list.lock().await.pick_random_websocket_server_excluding(&failed);
+        let Some(cm_server) = cm_server else {
+            if is_refreshed {
+                return Err(last_error.unwrap_or(Error::NoCmServersAvailable { retry_after: None }));
+            }
+            
+            // every server in the current list has failed
+            log::debug!("All CM servers in list failed; refreshing CM list");
+            update_cm_list(cm_list, options, true).await?;
+            failed.clear();
+            is_refreshed = true;
+            continue;
+        };
+        
+        if exclude.is_some() && endpoint_cm(&cm_server.endpoint).as_deref() == exclude {
+            failed.insert(cm_server.endpoint);
+            continue;
+        }
+        
+        match open_websocket(&endpoint_url(&cm_server.endpoint), options).await {
+            Ok(connection) => return Ok(connection),
+            Err(error) => {
+                log::debug!("Failed to connect to CM {}: {error}", cm_server.endpoint);
+                failed.insert(cm_server.endpoint);
+                last_error = Some(error);
+            },
+        }
+    }
+}
+
+/// Gets the websocket URL for a CM `endpoint`, which is either a full URL or a host with an 
+/// optional port. The host is a hostname, an IPv4 address or an IPv6 address. An IPv6 address 
+/// with a port must be enclosed in brackets, e.g. `[2001:db8::1]:443`.
+fn endpoint_url(endpoint: &str) -> String {
+    if endpoint.contains("://") {
+        endpoint.to_string()
+    } else if let Ok(ip) = endpoint.parse::<Ipv6Addr>() {
+        // a bare IPv6 address would be mistaken for a host and port
+        format!("wss://[{ip}]/cmsocket/")
+    } else {
+        format!("wss://{endpoint}/cmsocket/")
+    }
+}
+
+/// Gets the `host:port` of a CM `endpoint`, in the same form as returned by 
+/// [`open_cm_websocket`].
+fn endpoint_cm(endpoint: &str) -> Option<String> {
+    let uri = endpoint_url(endpoint).parse::<Uri>().ok()?;
+    let (host, port) = host_and_port(&uri)?;
+    
+    Some(format!("{host}:{port}"))
+}
+
+/// Gets the host and port of `uri`, using the default port for the scheme if it has none.
+fn host_and_port(uri: &Uri) -> Option<(&str, u16)> {
+    let host = uri.host()?;
+    let port = uri.port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("ws") { 80 } else { 443 });
+    
+    Some((host, port))
+}
+
+/// Opens a TCP connection to the host of `uri`, applying the socket options from `options`.
+async fn connect_tcp(uri: &Uri, options: &ConnectOptions) -> Result<TcpStream, Error> {
+    let (host, port) = host_and_port(uri).ok_or(Error::UrlNoHostName)?;
+    // IPv6 addresses are enclosed in brackets
+    let host = host
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let stream = TcpStream::connect((host, port)).await?;
+    
+    stream.set_nodelay(options.tcp_nodelay)?;
+    
+    if let Some(keepalive) = options.tcp_keepalive {
+        SockRef::from(&stream).set_tcp_keepalive(&TcpKeepalive::new().with_time(keepalive))?;
+    }
+    
+    Ok(stream)
+}
+
+/// Connects to the websocket at `connect_addr`.
+#[cfg(any(test, feature = "testing"))]
+pub async fn connect_to_url(
+    connect_addr: &str,
+    cm_list: &Arc<tokio::sync::Mutex<CmListCache>>,
+    options: &ConnectOptions,
+) -> Result<WebSocketCMTransport, Error> {
+    let (websocket, cm) = open_websocket(connect_addr, options).await?;
+    
+    Ok(WebSocketCMTransport::new(websocket, cm, cm_list.clone(), options))
+}
+
+/// Opens a websocket to `connect_addr`, along with its `host:port`.
+async fn open_websocket(
+    connect_addr: &str,
+    options: &ConnectOptions,
+) -> Result<(WebSocket, String), Error> {
     let uri = connect_addr.parse::<Uri>()?;
+    let (host, port) = host_and_port(&uri).ok_or(Error::UrlNoHostName)?;
+    let cm = format!("{host}:{port}");
+    let stream = connect_tcp(&uri, options).await?;
     let authority = uri.authority()
         .ok_or(Error::UrlNoHostName)?.as_str();
     let host = authority
         .find('@')
         .map(|idx| authority.split_at(idx + 1).1)
         .unwrap_or_else(|| authority);
-    let request = Request::builder()
+    let mut request = Request::builder()
         .header("batch-test", "true")
-        .header("Host", host)
-        .header("Connection", "Upgrade")
-        .header("Upgrade", "websocket")
-        .header("Sec-WebSocket-Version", "13")
-        .header("Sec-WebSocket-Key", generate_key())
+        .header(HOST, host)
+        .header(CONNECTION, "Upgrade")
+        .header(UPGRADE, "websocket")
+        .header(SEC_WEBSOCKET_VERSION, "13")
+        .header(SEC_WEBSOCKET_KEY, generate_key())
         .uri(uri)
         .body(())?;
+    
+    request.headers_mut().extend(options.headers.clone());
     // todo use timeout when connecting
     // let connect_timeout = Duration::seconds(CONNECTION_TIMEOUT_SECONDS);
-    let (ws_stream, _) = connect_async(request).await?;
-    let (ws_write, ws_read) = ws_stream.split();
-    let transport = WebSocketCMTransport::new(
-        ws_read,
-        ws_write,
-    );
-    
-    Ok(transport)
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    let (ws_stream, _) = client_async_tls(request, stream).await?;
+    // only reached along with the compile error in lib.rs asking for a TLS backend
+    #[cfg(not(any(feature = "native-tls", feature = "rustls-tls")))]
+    let (ws_stream, _) = tokio_tungstenite::client_async(request, MaybeTlsStream::Plain(stream)).await?;
+    
+    Ok((ws_stream, cm))
 }
 
 pub async fn wait_for_response<Msg>(
@@ -74,4 +324,51 @@ where
             Err(Error::Timeout.into())
         },
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn parse_endpoint(endpoint: &str) -> (String, String, u16) {
+        let url = endpoint_url(endpoint);
+        let uri = url.parse::<Uri>().unwrap();
+        let (host, port) = host_and_port(&uri).unwrap();
+        
+        (url, host.to_string(), port)
+    }
+    
+    #[test]
+    fn parses_hostname_endpoints() {
+        assert_eq!(parse_endpoint("cmp1-fra1.steamserver.net:27022"), (
+            String::from("wss://cmp1-fra1.steamserver.net:27022/cmsocket/"),
+            String::from("cmp1-fra1.steamserver.net"),
+            27022,
+        ));
+        assert_eq!(parse_endpoint("cmp1-fra1.steamserver.net").2, 443);
+        assert_eq!(parse_endpoint("ws://cmp1-fra1.steamserver.net/cmsocket/").2, 80);
+    }
+    
+    #[test]
+    fn parses_ipv4_endpoints() {
+        assert_eq!(parse_endpoint("162.254.197.40:27020"), (
+            String::from("wss://162.254.197.40:27020/cmsocket/"),
+            String::from("162.254.197.40"),
+            27020,
+        ));
+    }
+    
+    #[test]
+    fn parses_ipv6_endpoints() {
+        assert_eq!(parse_endpoint("[2001:db8::1]:27020"), (
+            String::from("wss://[2001:db8::1]:27020/cmsocket/"),
+            String::from("[2001:db8::1]"),
+            27020,
+        ));
+        assert_eq!(parse_endpoint("2001:db8::1"), (
+            String::from("wss://[2001:db8::1]/cmsocket/"),
+            String::from("[2001:db8::1]"),
+            443,
+        ));
+    }
+}
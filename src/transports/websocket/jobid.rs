@@ -0,0 +1,57 @@
+//! Sources of the job IDs used to match responses to requests sent over a 
+//! [`WebSocketCMTransport`](super::WebSocketCMTransport).
+//! 
+//! Job IDs are random by default. A [`JobIdGenerator`] can be supplied using 
+//! [`ConnectOptions::jobid_generator`](super::ConnectOptions::jobid_generator), e.g. a 
+//! [`SequentialJobIdGenerator`] to make job IDs predictable in tests or to correlate them with 
+//! your own tracing.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use rand::Rng;
+
+/// A source of job IDs. Job IDs must be unique among the requests in flight on a connection and 
+/// must not be 0, which is reserved for messages without a job. A request given a job ID of 0 
+/// fails with [`Error::ZeroJobId`](super::Error::ZeroJobId).
+pub trait JobIdGenerator: Debug + Send + Sync {
+    /// Gets the job ID for the next request.
+    fn next_jobid(&self) -> u64;
+}
+
+/// A [`JobIdGenerator`] which generates random 63-bit job IDs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomJobIdGenerator;
+
+impl JobIdGenerator for RandomJobIdGenerator {
+    fn next_jobid(&self) -> u64 {
+        rand::thread_rng().gen_range(1..=i64::MAX as u64)
+    }
+}
+
+/// A [`JobIdGenerator`] which counts up from a starting job ID.
+#[derive(Debug)]
+pub struct SequentialJobIdGenerator {
+    next: AtomicU64,
+}
+
+impl SequentialJobIdGenerator {
+    /// Creates a new [`SequentialJobIdGenerator`] whose first job ID is `start`.
+    pub fn new(start: u64) -> Self {
+        Self {
+            next: AtomicU64::new(start),
+        }
+    }
+}
+
+impl Default for SequentialJobIdGenerator {
+    /// Starts at 1.
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl JobIdGenerator for SequentialJobIdGenerator {
+    fn next_jobid(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,45 @@
+use super::Error;
+use crate::enums::EResult;
+use crate::proto::steammessages_clientserver_login::CMsgClientLogonResponse;
+use std::time::Duration;
+
+/// The fields of a `ClientLogOnResponse` which are used to maintain the connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogonInfo {
+    /// The result of logging on.
+    pub eresult: EResult,
+    /// The extended result, from `eresult_extended`, if sent. This tells apart failures that 
+    /// share the same `eresult` such as [`EResult::Fail`].
+    pub eresult_extended: Option<i32>,
+    /// How often heartbeats should be sent, from `heartbeat_seconds`, falling back to 
+    /// `legacy_out_of_game_heartbeat_seconds`. `None` if neither is positive.
+    pub heartbeat_interval: Option<Duration>,
+    /// The cell ID assigned by the server, from `cell_id`. Used when fetching the CM list so 
+    /// nearby servers are returned.
+    pub cell_id: Option<u32>,
+    /// The nonce for authenticating with the web API, from `webapi_authenticate_user_nonce`.
+    pub webapi_authenticate_user_nonce: Option<String>,
+}
+
+impl TryFrom<&CMsgClientLogonResponse> for LogonInfo {
+    type Error = Error;
+    
+    fn try_from(response: &CMsgClientLogonResponse) -> Result<Self, Self::Error> {
+        let eresult = EResult::try_from(response.eresult())
+            .map_err(|_| Error::UnknownEResult(response.eresult()))?;
+        let heartbeat_seconds = Some(response.heartbeat_seconds())
+            .filter(|seconds| *seconds > 0)
+            .or(Some(response.legacy_out_of_game_heartbeat_seconds()))
+            .filter(|seconds| *seconds > 0);
+        
+        Ok(Self {
+            eresult,
+            eresult_extended: response.eresult_extended,
+            heartbeat_interval: heartbeat_seconds
+                .map(|seconds| Duration::from_secs(seconds as u64)),
+            cell_id: response.cell_id,
+            webapi_authenticate_user_nonce: response.webapi_authenticate_user_nonce.clone()
+                .filter(|nonce| !nonce.is_empty()),
+        })
+    }
+}
@@ -1,4 +1,10 @@
+use crate::enums::EMsg;
+
+/// A message from the CM server that isn't a response to a request.
 #[derive(Debug, Clone)]
 pub struct Message {
-    
-}
\ No newline at end of file
+    /// The message type.
+    pub emsg: EMsg,
+    /// The message body.
+    pub body: Vec<u8>,
+}
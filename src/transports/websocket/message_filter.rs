@@ -1,143 +1,383 @@
-use super::Error;
-use super::PROTO_MASK;
+use super::{Error, DisconnectReason, PROTOCOL_VERSION};
+use super::frame::{split_frame, multi_payload, multi_chunks};
 use super::message::Message;
 use super::response::ApiResponseBody;
+use super::message_queue::{self, OverflowPolicy, QueueReceiver};
+use super::logon_info::LogonInfo;
 use crate::enums::{EMsg, EResult};
-use crate::proto::steammessages_base::{CMsgProtoBufHeader, CMsgMulti};
 use crate::proto::steammessages_clientserver_login::CMsgClientLogonResponse;
-use std::io::{Cursor, Read};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicI32, Ordering};
-use futures::stream::SplitStream;
-use futures::StreamExt;
-use tokio::net::TcpStream;
-use tokio::sync::{oneshot, mpsc};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::time::Duration;
+use futures::{Stream, StreamExt};
+use tokio::sync::oneshot;
+use tokio::task::AbortHandle;
 use tokio_tungstenite::tungstenite;
-use tokio_tungstenite::{WebSocketStream, MaybeTlsStream};
+use tokio_util::sync::CancellationToken;
 use dashmap::DashMap;
 use protobuf::Message as ProtoMessage;
-use byteorder::{LittleEndian, ReadBytesExt};
-use flate2::read::GzDecoder;
 
 #[derive(Debug)]
 struct MessageData {
-    eresult: EResult,
+    /// The result, or the raw value if it isn't known.
+    eresult: Result<EResult, i32>,
+    /// The error message sent along with the result, if any.
+    error_message: Option<String>,
     emsg: EMsg,
     body: Vec<u8>,
     jobid_target: u64,
     client_sessionid: i32,
 }
 
+/// Routes messages read from a websocket connection to the requests awaiting them.
+/// 
+/// The filter outlives any single connection: after reconnecting, [`MessageFilter::attach`] 
+/// starts reading from the new connection while keeping the `client_sessionid` and the last 
+/// disconnect reason.
 #[derive(Debug, Clone)]
 pub struct MessageFilter {
     job_id_filters: Arc<DashMap<u64, oneshot::Sender<Result<ApiResponseBody, Error>>>>,
     client_sessionid: Arc<AtomicI32>,
+    last_disconnect_reason: Arc<Mutex<Option<DisconnectReason>>>,
+    reader: Arc<Mutex<Option<AbortHandle>>>,
+    overflow_policy: OverflowPolicy,
+    dropped_messages: Arc<AtomicU64>,
+    logon_info: Arc<Mutex<Option<LogonInfo>>>,
+    strict_enums: bool,
+    max_decompressed_size: usize,
+    idle_timeout: Option<Duration>,
+    try_another_cm: Arc<AtomicBool>,
+    cancellation_token: CancellationToken,
 }
 
 impl MessageFilter {
-    pub fn new(
-        mut source: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    pub fn new<S>(
+        source: S,
         client_sessionid: Arc<AtomicI32>,
-    ) -> (Self, mpsc::Receiver<Result<Message, Error>>) {
-        let (
-            _rest_tx,
-            rx,
-        ) = mpsc::channel::<Result<Message, Error>>(16);
+        overflow_policy: OverflowPolicy,
+        strict_enums: bool,
+        max_decompressed_size: usize,
+        idle_timeout: Option<Duration>,
+        cancellation_token: CancellationToken,
+    ) -> (Self, QueueReceiver<Result<Message, Error>>)
+    where
+        S: Stream<Item = Result<tungstenite::Message, tungstenite::Error>> + Send + Unpin + 'static,
+    {
         let filter = MessageFilter {
             job_id_filters: Default::default(),
             client_sessionid,
+            last_disconnect_reason: Default::default(),
+            reader: Default::default(),
+            overflow_policy,
+            dropped_messages: Default::default(),
+            logon_info: Default::default(),
+            strict_enums,
+            max_decompressed_size,
+            idle_timeout,
+            try_another_cm: Default::default(),
+            cancellation_token,
         };
-        let filter_send = filter.clone();
+        let rx = filter.attach(source);
+        
+        (filter, rx)
+    }
+    
+    /// Attaches the filter to a new connection, e.g. after reconnecting, and stops reading from 
+    /// the previous one. Returns a receiver for messages that aren't responses to a job. If the 
+    /// receiver falls behind, messages are handled using the [`OverflowPolicy`].
+    /// 
+    /// Responses to requests sent over the previous connection can't arrive over the new one, so 
+    /// any requests still in flight are failed with [`Error::Reconnected`], which is retriable. 
+    /// Requests sent after attaching are routed as usual.
+    /// 
+    /// If nothing is received for the idle timeout, reading stops and requests in flight are 
+    /// failed with [`Error::IdleTimeout`]. Likewise, if the cancellation token is cancelled, 
+    /// reading stops and requests in flight are failed with [`Error::Cancelled`].
+    pub fn attach<S>(
+        &self,
+        mut source: S,
+    ) -> QueueReceiver<Result<Message, Error>>
+    where
+        S: Stream<Item = Result<tungstenite::Message, tungstenite::Error>> + Send + Unpin + 'static,
+    {
+        let (
+            rest_tx,
+            rx,
+        ) = message_queue::channel::<Result<Message, Error>>(
+            16,
+            self.overflow_policy,
+            self.dropped_messages.clone(),
+        );
+        let filter_send = self.clone();
+        let mut reader = self.reader.lock().unwrap();
+        
+        if let Some(previous) = reader.take() {
+            previous.abort();
+            self.fail_pending(|| Error::Reconnected);
+        }
+        
+        // the reason belongs to the previous connection
+        *self.last_disconnect_reason.lock().unwrap() = None;
         
-        tokio::spawn(async move {
-            while let Some(res) = source.next().await {
+        let handle = tokio::spawn(async move {
+            loop {
+                let idle_timeout = filter_send.idle_timeout;
+                let read = async {
+                    match idle_timeout {
+                        Some(idle_timeout) => tokio::time::timeout(idle_timeout, source.next()).await.ok(),
+                        None => Some(source.next().await),
+                    }
+                };
+                let next = tokio::select! {
+                    biased;
+                    _ = filter_send.cancellation_token.cancelled() => {
+                        log::debug!("Websocket connection was cancelled");
+                        filter_send.set_disconnect_reason(DisconnectReason::Cancelled);
+                        filter_send.fail_pending(|| Error::Cancelled);
+                        break;
+                    },
+                    next = read => next,
+                };
+                let Some(next) = next else {
+                    let idle_timeout = idle_timeout.unwrap_or_default();
+                    
+                    log::warn!("Nothing received from websocket connection for {idle_timeout:?}; treating it as dead");
+                    filter_send.set_disconnect_reason(DisconnectReason::IdleTimeout);
+                    filter_send.fail_pending(|| Error::IdleTimeout(idle_timeout));
+                    rest_tx.send(Err(Error::IdleTimeout(idle_timeout))).await;
+                    break;
+                };
+                let Some(res) = next else {
+                    break;
+                };
+                
                 match res {
                     Ok(message) => match message {
+                        // tungstenite reassembles fragmented messages before returning them, so 
+                        // each binary message is a complete CM frame. Messages larger than the 
+                        // configured maximum size are returned as a capacity error instead.
                         tungstenite::Message::Binary(buffer) => {
                             log::debug!("Got {} bytes", buffer.len());
                             
-                            if let Err(error) = handle_ws_message(&filter_send, buffer.to_vec()) {
-                                log::warn!("Error handling websocket message: {}", error);
+                            match handle_ws_message(&filter_send, buffer.to_vec()) {
+                                Err(Error::ClientLogOnResponseTryAnotherCM { eresult, .. }) => {
+                                    filter_send.set_disconnect_reason(DisconnectReason::TryAnotherCm(eresult));
+                                    filter_send.set_try_another_cm();
+                                },
+                                Err(Error::ProtocolVersionRejected(protocol_version)) => {
+                                    log::warn!("CM server rejected protocol version {protocol_version}");
+                                    filter_send.set_disconnect_reason(DisconnectReason::ProtocolVersionRejected(protocol_version));
+                                    // no request can succeed over this connection
+                                    filter_send.fail_pending(|| Error::ProtocolVersionRejected(protocol_version));
+                                },
+                                Err(error) => {
+                                    log::warn!("Error handling websocket message: {}", error);
+                                },
+                                Ok(Some(message)) => {
+                                    rest_tx.send(Ok(message)).await;
+                                },
+                                Ok(None) => {},
                             }
                         },
+                        tungstenite::Message::Close(frame) => {
+                            let (code, reason) = frame
+                                .map(|frame| (Some(u16::from(frame.code)), frame.reason.to_string()))
+                                .unwrap_or_default();
+                            
+                            log::debug!("Websocket closed by server with code {code:?}: {reason}");
+                            filter_send.set_disconnect_reason(DisconnectReason::ServerClose {
+                                code,
+                                reason: reason.clone(),
+                            });
+                            filter_send.fail_pending(|| Error::ClosedByServer {
+                                code,
+                                reason: reason.clone(),
+                            });
+                            rest_tx.send(Err(Error::ClosedByServer {
+                                code,
+                                reason,
+                            })).await;
+                            break;
+                        },
+                        tungstenite::Message::Frame(_) => {
+                            // Raw frames are only used when writing, a partial message should 
+                            // never be parsed as a CM frame.
+                            log::warn!("Websocket received an unexpected raw frame; ignoring");
+                        },
                         _ => {
                             log::debug!("Websocket received message with type other than binary");
                         },
                     },
+                    Err(tungstenite::Error::Capacity(error)) => {
+                        // The message was dropped, surface this rather than waiting for a 
+                        // response that will never be routed.
+                        log::warn!("Websocket message exceeded capacity: {error}");
+                        filter_send.set_disconnect_reason(DisconnectReason::Protocol(error.to_string()));
+                        rest_tx.send(Err(tungstenite::Error::Capacity(error).into())).await;
+                    },
                     Err(error) => {
                         log::warn!("Error received from websocket connection {}", error);
+                        filter_send.set_disconnect_reason(match error {
+                            tungstenite::Error::Io(error) => DisconnectReason::Io(error.kind()),
+                            error => DisconnectReason::Protocol(error.to_string()),
+                        });
+                        break;
                     },
                 }
             }
+            
+            // the stream ended without a close frame
+            filter_send.last_disconnect_reason
+                .lock()
+                .unwrap()
+                .get_or_insert(DisconnectReason::Io(std::io::ErrorKind::UnexpectedEof));
+            // no responses can arrive once reading stops
+            filter_send.fail_pending(|| Error::ConnectionClosed);
         });
         
-        (filter, rx)
+        *reader = Some(handle.abort_handle());
+        rx
     }
     
+    /// Registers a filter for responses to job `id`.
+    /// 
+    /// If a request is already waiting on `id`, it can no longer be told apart from this one, so 
+    /// it's failed with [`Error::JobIdCollision`] rather than left waiting forever. Filters whose 
+    /// receiver was dropped are removed.
     pub fn on_job_id(
         &self,
         id: u64,
     ) -> oneshot::Receiver<Result<ApiResponseBody, Error>> {
         let (tx, rx) = oneshot::channel();
-        self.job_id_filters.insert(id, tx);
+        
+        self.remove_closed_jobs();
+        
+        if let Some(displaced) = self.job_id_filters.insert(id, tx) {
+            log::warn!("Job ID {id} is already in use; failing the previous request");
+            let _ = displaced.send(Err(Error::JobIdCollision(id)));
+        }
+        
         rx
     }
+    
+    /// Gets the number of messages that weren't responses to a job which were dropped because 
+    /// the receiver fell behind.
+    pub fn dropped_messages(&self) -> u64 {
+        self.dropped_messages.load(Ordering::Relaxed)
+    }
+    
+    /// Gets the reason the connection was last closed, if it has been.
+    pub fn last_disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.last_disconnect_reason.lock().unwrap().clone()
+    }
+    
+    /// Checks if the filter is reading from a connection which hasn't closed.
+    pub fn is_attached(&self) -> bool {
+        self.reader
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|reader| !reader.is_finished())
+    }
+    
+    /// Gets the fields of the last `ClientLogOnResponse`, if one was received.
+    pub fn logon_info(&self) -> Option<LogonInfo> {
+        self.logon_info.lock().unwrap().clone()
+    }
+    
+    /// Marks the connection as one the CM server told us to move away from, e.g. with 
+    /// [`EResult::TryAnotherCM`], so the next request fails over to another server.
+    pub fn set_try_another_cm(&self) {
+        self.try_another_cm.store(true, Ordering::Relaxed);
+    }
+    
+    /// Checks if the CM server told us to try another one since this was last called.
+    pub fn take_try_another_cm(&self) -> bool {
+        self.try_another_cm.swap(false, Ordering::Relaxed)
+    }
+    
+    fn set_disconnect_reason(&self, reason: DisconnectReason) {
+        *self.last_disconnect_reason.lock().unwrap() = Some(reason);
+    }
+    
+    /// Fails all pending requests with the error created by `error`.
+    fn fail_pending<F>(&self, error: F)
+    where
+        F: Fn() -> Error,
+    {
+        let jobids = self.job_id_filters
+            .iter()
+            .map(|entry| *entry.key())
+            .collect::<Vec<_>>();
+        
+        for jobid in jobids {
+            if let Some((_, tx)) = self.job_id_filters.remove(&jobid) {
+                let _ = tx.send(Err(error()));
+            }
+        }
+    }
+    
+    /// Removes the filters whose receiver was dropped, e.g. because the request was cancelled, 
+    /// so responses that never arrive don't leave them behind.
+    fn remove_closed_jobs(&self) {
+        self.job_id_filters.retain(|jobid, tx| {
+            if tx.is_closed() {
+                log::debug!("Removing filter for job {jobid}; the request was cancelled");
+            }
+            
+            !tx.is_closed()
+        });
+    }
+    
+    /// Removes the filter for a job ID, if it is still registered and its receiver has been 
+    /// dropped. A filter registered by another request with the same ID is kept.
+    pub fn remove_job_id(&self, id: u64) {
+        self.job_id_filters.remove_if(&id, |_, tx| tx.is_closed());
+    }
 }
 
 fn process_multi_message(
     filter: &MessageFilter,
     body_buffer: &[u8],
 ) -> Result<(), Error> {
-    let message = CMsgMulti::parse_from_bytes(body_buffer)?;
-    let payload = message.message_body();
+    let payload = multi_payload(body_buffer, filter.max_decompressed_size)?;
+    
     log::debug!("Process multi {} bytes", payload.len());
-    let mut s = Vec::new();
-    let payload = if message.size_unzipped() != 0 {
-        GzDecoder::new(payload).read_to_end(&mut s)?;
-        
-        s.as_slice()
-    } else {
-        payload
-    };
-    let mut cursor = Cursor::new(payload);
     
-    while let Ok(chunk_size) = cursor.read_u32::<LittleEndian>() {
-        let mut chunk_buffer: Vec<u8> = vec![0; chunk_size as usize];
-        
-        cursor.read_exact(&mut chunk_buffer)?;
-        check_ws_message(filter, chunk_buffer)?;
+    for chunk in multi_chunks(&payload)? {
+        match check_ws_message(filter, chunk.to_vec()) {
+            // One message that can't be handled doesn't prevent handling the rest.
+            Err(error) if !filter.strict_enums => {
+                log::warn!("Error handling message in multi: {error}");
+            },
+            result => {
+                result?;
+            },
+        }
     }
     
     Ok(())
 }
 
-fn parse_message(msg: Vec<u8>) -> Result<MessageData, Error> {
-    let mut cursor = Cursor::new(msg.as_slice());
-    let raw_emsg = cursor.read_u32::<LittleEndian>()?;
-    let header_length = cursor.read_u32::<LittleEndian>()?;
-    let mut header_buffer: Vec<u8> = vec![0; header_length as usize];
-    
-    cursor.read_exact(&mut header_buffer)?;
-    
-    let mut body: Vec<u8> = Vec::new();
-    
-    cursor.read_to_end(&mut body)?;
-    
-    if raw_emsg & PROTO_MASK == 0 {
-        return Err(Error::UnexpectedNonProtobufMessage(raw_emsg));
-    }
-    
-    let raw_emsg = raw_emsg & !PROTO_MASK;
-    let header = CMsgProtoBufHeader::parse_from_bytes(&header_buffer)?;
+/// Parses a message. An unknown [`EResult`] fails parsing if `strict_enums` is set, otherwise 
+/// its raw value is kept.
+fn parse_message(msg: Vec<u8>, strict_enums: bool) -> Result<MessageData, Error> {
+    let (emsg, header, body) = split_frame(&msg)?;
+    let body = body.to_vec();
     let client_sessionid = header.client_sessionid();
-    let emsg = EMsg::try_from(raw_emsg)
-        .map_err(|_| Error::UnknownEMsg(raw_emsg))?;
     let jobid_target = header.jobid_target();
-    let eresult =  EResult::try_from(header.eresult())
-        .map_err(|_| Error::UnknownEResult(header.eresult()))?;
+    let eresult = EResult::try_from(header.eresult())
+        .map_err(|_| header.eresult());
+    let error_message = header.error_message
+        .filter(|error_message| !error_message.is_empty());
+    
+    if let (true, Err(eresult)) = (strict_enums, eresult) {
+        return Err(Error::UnknownEResult(eresult));
+    }
     
     Ok(MessageData {
         eresult,
+        error_message,
         emsg,
         jobid_target,
         client_sessionid,
@@ -151,11 +391,12 @@ fn check_ws_message(
 ) -> Result<Option<(EMsg, Vec<u8>)>, Error> {
     let MessageData {
         eresult,
+        error_message,
         emsg,
         jobid_target,
         client_sessionid,
         body,
-    } = parse_message(msg)?;
+    } = parse_message(msg, filter.strict_enums)?;
     
     if client_sessionid != 0 && client_sessionid != filter.client_sessionid.load(Ordering::Relaxed) {
         log::debug!("Got new client sessionid: {client_sessionid}");
@@ -169,17 +410,30 @@ fn check_ws_message(
             .job_id_filters
             .remove(&jobid_target)
         {
-            let message = if eresult == EResult::OK {
-                Ok(ApiResponseBody {
-                    eresult: Some(eresult),
+            if tx.is_closed() {
+                log::debug!("Discarding response to job {jobid_target}; the request was cancelled");
+                return Ok(None);
+            }
+            
+            let message = match eresult {
+                Ok(EResult::OK) => Ok(ApiResponseBody {
+                    eresult: Some(EResult::OK),
                     error_message: None,
                     body: Some(body),
-                })
-            } else {
-                Err(Error::EResultNotOK(eresult))
+                }),
+                Ok(EResult::InvalidProtocolVer) => Err(Error::ProtocolVersionRejected(PROTOCOL_VERSION)),
+                Ok(eresult) => Err(match error_message {
+                    Some(message) => Error::EResultWithMessage {
+                        eresult,
+                        message,
+                    },
+                    None => Error::EResultNotOK(eresult),
+                }),
+                Err(eresult) => Err(Error::UnknownEResult(eresult)),
             };
-            // todo maybe propogate the error
-            let _ = tx.send(message);
+            if tx.send(message).is_err() {
+                log::debug!("Discarding response to job {jobid_target}; the request was cancelled");
+            }
             
             return Ok(None);
         }
@@ -188,29 +442,495 @@ fn check_ws_message(
     Ok(Some((emsg, body)))
 }
 
-fn handle_ws_message(filter: &MessageFilter, msg: Vec<u8>) -> Result<(), Error> {
+/// Handles a message, returning it if it isn't a response to a job and isn't handled here.
+fn handle_ws_message(filter: &MessageFilter, msg: Vec<u8>) -> Result<Option<Message>, Error> {
     if let Some((emsg, body)) = check_ws_message(filter, msg)? {
         // this isn't a response message, so figure out what it is
         match emsg {
-            // The only time we expect to receive ClientLogOnResponse is when the CM is telling us to try another CM
+            // Kept for maintaining the connection. Any result other than OK is the CM telling us 
+            // to try another CM.
             EMsg::ClientLogOnResponse => {
                 let logon_response = CMsgClientLogonResponse::parse_from_bytes(&body)?;
-                let eresult =  EResult::try_from(logon_response.eresult())
-                    .map_err(|_| Error::UnknownEResult(logon_response.eresult()))?;
+                let logon_info = LogonInfo::try_from(&logon_response)?;
+                let eresult = logon_info.eresult;
+                let eresult_extended = logon_info.eresult_extended;
+                
+                log::debug!("Received ClientLogOnResponse with result: {eresult:?} (extended {eresult_extended:?})");
+                *filter.logon_info.lock().unwrap() = Some(logon_info);
+                
+                if eresult == EResult::InvalidProtocolVer {
+                    return Err(Error::ProtocolVersionRejected(PROTOCOL_VERSION));
+                }
                 
-                log::debug!("Received ClientLogOnResponse with result: {eresult:?}");
-                // websocket connection should be closed
+                if eresult != EResult::OK {
+                    // websocket connection should be closed
+                    return Err(Error::ClientLogOnResponseTryAnotherCM {
+                        eresult,
+                        eresult_extended,
+                    });
+                }
                 
-                return Err(Error::ClientLogOnResponseTryAnotherCM(eresult));
+                return Ok(Some(Message {
+                    emsg,
+                    body,
+                }));
             },
             EMsg::Multi => {
                 process_multi_message(filter, &body)?;
             },
             emsg => {
                 log::debug!("Received unexpected message: {emsg:?}");
+                
+                return Ok(Some(Message {
+                    emsg,
+                    body,
+                }));
             },
         }
     }
     
-    Ok(())
-}
\ No newline at end of file
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::steammessages_auth_steamclient::CAuthentication_GetPasswordRSAPublicKey_Response;
+    use crate::proto::steammessages_base::CMsgMulti;
+    use crate::transports::websocket::DEFAULT_MAX_DECOMPRESSED_SIZE;
+    
+    fn test_filter() -> MessageFilter {
+        MessageFilter {
+            job_id_filters: Default::default(),
+            client_sessionid: Arc::new(AtomicI32::new(0)),
+            last_disconnect_reason: Default::default(),
+            reader: Default::default(),
+            overflow_policy: OverflowPolicy::default(),
+            dropped_messages: Default::default(),
+            logon_info: Default::default(),
+            strict_enums: true,
+            max_decompressed_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+            idle_timeout: None,
+            try_another_cm: Default::default(),
+            cancellation_token: CancellationToken::new(),
+        }
+    }
+    
+    #[test]
+    fn parses_plain_message() {
+        let frame = include_bytes!("./fixtures/service_method_response.bin");
+        let message = parse_message(frame.to_vec(), true).unwrap();
+        let body = CAuthentication_GetPasswordRSAPublicKey_Response::parse_from_bytes(&message.body).unwrap();
+        
+        assert_eq!(message.emsg, EMsg::ServiceMethodResponse);
+        assert_eq!(message.eresult, Ok(EResult::OK));
+        assert_eq!(message.jobid_target, 0x0102030405060708);
+        assert_eq!(message.client_sessionid, 42);
+        assert_eq!(body.publickey_mod(), "c0ffee");
+        assert_eq!(body.publickey_exp(), "010001");
+        assert_eq!(body.timestamp(), 1700000000);
+    }
+    
+    #[test]
+    fn routes_plain_message_to_job() {
+        let frame = include_bytes!("./fixtures/service_method_response.bin");
+        let filter = test_filter();
+        let mut rx = filter.on_job_id(0x0102030405060708);
+        
+        assert!(check_ws_message(&filter, frame.to_vec()).unwrap().is_none());
+        
+        let response = rx.try_recv().unwrap().unwrap();
+        
+        assert_eq!(response.eresult, Some(EResult::OK));
+        assert!(response.body.is_some());
+        assert_eq!(filter.client_sessionid.load(Ordering::Relaxed), 42);
+    }
+    
+    #[test]
+    fn routes_error_eresult_to_job() {
+        let frame = include_bytes!("./fixtures/service_method_response_invalid_password.bin");
+        let filter = test_filter();
+        let mut rx = filter.on_job_id(0x0a0b0c0d0e0f1011);
+        
+        assert!(check_ws_message(&filter, frame.to_vec()).unwrap().is_none());
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            Err(Error::EResultNotOK(EResult::InvalidPassword)),
+        ));
+    }
+    
+    #[test]
+    fn routes_error_message_to_job() {
+        let mut header = crate::proto::steammessages_base::CMsgProtoBufHeader::new();
+        
+        header.set_jobid_target(7);
+        header.set_eresult(EResult::RateLimitExceeded.into());
+        header.set_error_message("Too many attempts".into());
+        
+        let header = header.write_to_bytes().unwrap();
+        let frame = [
+            &(u32::from(EMsg::ServiceMethodResponse) | crate::transports::websocket::PROTO_MASK).to_le_bytes()[..],
+            &(header.len() as u32).to_le_bytes(),
+            &header,
+        ].concat();
+        let filter = test_filter();
+        let mut rx = filter.on_job_id(7);
+        
+        assert!(check_ws_message(&filter, frame).unwrap().is_none());
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            Err(Error::EResultWithMessage { eresult: EResult::RateLimitExceeded, message }) if message == "Too many attempts",
+        ));
+    }
+    
+    #[test]
+    fn routes_unknown_eresult_to_job_unless_strict() {
+        let mut header = crate::proto::steammessages_base::CMsgProtoBufHeader::new();
+        
+        header.set_jobid_target(7);
+        header.set_eresult(99999);
+        
+        let header = header.write_to_bytes().unwrap();
+        let frame = [
+            &(u32::from(EMsg::ServiceMethodResponse) | crate::transports::websocket::PROTO_MASK).to_le_bytes()[..],
+            &(header.len() as u32).to_le_bytes(),
+            &header,
+        ].concat();
+        let filter = test_filter();
+        
+        assert!(matches!(
+            check_ws_message(&filter, frame.clone()),
+            Err(Error::UnknownEResult(99999)),
+        ));
+        
+        let filter = MessageFilter {
+            strict_enums: false,
+            ..test_filter()
+        };
+        let mut rx = filter.on_job_id(7);
+        
+        assert!(check_ws_message(&filter, frame).unwrap().is_none());
+        assert!(matches!(rx.try_recv().unwrap(), Err(Error::UnknownEResult(99999))));
+    }
+    
+    #[test]
+    fn returns_unrouted_message() {
+        let frame = include_bytes!("./fixtures/service_method_response.bin");
+        let filter = test_filter();
+        let (emsg, body) = check_ws_message(&filter, frame.to_vec()).unwrap().unwrap();
+        
+        assert_eq!(emsg, EMsg::ServiceMethodResponse);
+        assert!(!body.is_empty());
+    }
+    
+    #[test]
+    fn keeps_logon_response_fields() {
+        let mut logon_response = CMsgClientLogonResponse::new();
+        
+        logon_response.set_eresult(EResult::OK.into());
+        logon_response.set_legacy_out_of_game_heartbeat_seconds(9);
+        logon_response.set_cell_id(66);
+        logon_response.set_webapi_authenticate_user_nonce("nonce".into());
+        
+        let frame = crate::transports::websocket::testing::response_frame(
+            EMsg::ClientLogOnResponse,
+            0,
+            EResult::OK,
+            &logon_response.write_to_bytes().unwrap(),
+        );
+        let filter = test_filter();
+        let message = handle_ws_message(&filter, frame).unwrap().unwrap();
+        
+        assert_eq!(message.emsg, EMsg::ClientLogOnResponse);
+        assert_eq!(filter.logon_info(), Some(LogonInfo {
+            eresult: EResult::OK,
+            eresult_extended: None,
+            heartbeat_interval: Some(std::time::Duration::from_secs(9)),
+            cell_id: Some(66),
+            webapi_authenticate_user_nonce: Some("nonce".into()),
+        }));
+    }
+    
+    #[test]
+    fn keeps_extended_result_of_rejected_logon() {
+        let mut logon_response = CMsgClientLogonResponse::new();
+        
+        logon_response.set_eresult(EResult::Fail.into());
+        logon_response.set_eresult_extended(7);
+        
+        let frame = crate::transports::websocket::testing::response_frame(
+            EMsg::ClientLogOnResponse,
+            0,
+            EResult::OK,
+            &logon_response.write_to_bytes().unwrap(),
+        );
+        let filter = test_filter();
+        let error = handle_ws_message(&filter, frame).unwrap_err();
+        
+        assert!(error.to_string().contains("(extended result 7)"));
+        assert!(matches!(error, Error::ClientLogOnResponseTryAnotherCM {
+            eresult: EResult::Fail,
+            eresult_extended: Some(7),
+        }));
+        assert_eq!(filter.logon_info().unwrap().eresult_extended, Some(7));
+    }
+    
+    #[test]
+    fn rejects_protocol_version() {
+        let mut logon_response = CMsgClientLogonResponse::new();
+        
+        logon_response.set_eresult(EResult::InvalidProtocolVer.into());
+        
+        let frame = crate::transports::websocket::testing::response_frame(
+            EMsg::ClientLogOnResponse,
+            0,
+            EResult::OK,
+            &logon_response.write_to_bytes().unwrap(),
+        );
+        let filter = test_filter();
+        
+        assert!(matches!(
+            handle_ws_message(&filter, frame),
+            Err(Error::ProtocolVersionRejected(PROTOCOL_VERSION)),
+        ));
+        
+        let (tx, rx) = oneshot::channel();
+        
+        filter.job_id_filters.insert(1, tx);
+        
+        let frame = crate::transports::websocket::testing::response_frame(
+            EMsg::ServiceMethodResponse,
+            1,
+            EResult::InvalidProtocolVer,
+            &[],
+        );
+        
+        assert!(handle_ws_message(&filter, frame).unwrap().is_none());
+        assert!(matches!(
+            rx.blocking_recv().unwrap(),
+            Err(Error::ProtocolVersionRejected(PROTOCOL_VERSION)),
+        ));
+    }
+    
+    #[test]
+    fn discards_responses_to_cancelled_jobs() {
+        let frame = include_bytes!("./fixtures/service_method_response.bin");
+        let filter = test_filter();
+        
+        drop(filter.on_job_id(0x0102030405060708));
+        
+        // consumed rather than returned as an unrouted message
+        assert!(check_ws_message(&filter, frame.to_vec()).unwrap().is_none());
+        
+        drop(filter.on_job_id(1));
+        
+        let _rx = filter.on_job_id(2);
+        
+        assert_eq!(filter.job_id_filters.len(), 1);
+    }
+    
+    #[test]
+    fn processes_gzip_multi_message() {
+        let frame = include_bytes!("./fixtures/multi_gzip.bin");
+        let message = parse_message(frame.to_vec(), true).unwrap();
+        
+        assert_eq!(message.emsg, EMsg::Multi);
+        
+        let filter = test_filter();
+        let mut rx_1 = filter.on_job_id(1001);
+        let mut rx_2 = filter.on_job_id(1002);
+        
+        process_multi_message(&filter, &message.body).unwrap();
+        
+        let body_1 = rx_1.try_recv().unwrap().unwrap().body.unwrap();
+        let body_2 = rx_2.try_recv().unwrap().unwrap().body.unwrap();
+        let response_1 = CAuthentication_GetPasswordRSAPublicKey_Response::parse_from_bytes(&body_1).unwrap();
+        let response_2 = CAuthentication_GetPasswordRSAPublicKey_Response::parse_from_bytes(&body_2).unwrap();
+        
+        assert_eq!(response_1.publickey_mod(), "c0ffee");
+        assert_eq!(response_2.timestamp(), 1700000001);
+    }
+    
+    #[test]
+    fn rejects_truncated_message() {
+        let frame = include_bytes!("./fixtures/service_method_response.bin");
+        
+        assert!(parse_message(frame[..10].to_vec(), true).is_err());
+    }
+    
+    #[test]
+    fn fails_pending_jobs_on_close() {
+        let filter = test_filter();
+        let mut rx = filter.on_job_id(1001);
+        
+        filter.fail_pending(|| Error::ClosedByServer {
+            code: Some(1001),
+            reason: "going away".into(),
+        });
+        
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            Err(Error::ClosedByServer { code: Some(1001), reason }) if reason == "going away"
+        ));
+        assert!(filter.job_id_filters.is_empty());
+    }
+    
+    #[test]
+    fn fails_displaced_job_on_collision() {
+        let frame = include_bytes!("./fixtures/service_method_response.bin");
+        let filter = test_filter();
+        let mut displaced = filter.on_job_id(0x0102030405060708);
+        let mut rx = filter.on_job_id(0x0102030405060708);
+        
+        assert!(matches!(
+            displaced.try_recv().unwrap(),
+            Err(Error::JobIdCollision(0x0102030405060708)),
+        ));
+        
+        drop(displaced);
+        // the displaced request cleaning up doesn't remove the new filter
+        filter.remove_job_id(0x0102030405060708);
+        check_ws_message(&filter, frame.to_vec()).unwrap();
+        
+        assert!(rx.try_recv().unwrap().is_ok());
+    }
+    
+    #[tokio::test]
+    async fn idle_connection_fails_in_flight_jobs() {
+        let (filter, _rest) = MessageFilter::new(
+            futures::stream::pending(),
+            Arc::new(AtomicI32::new(0)),
+            OverflowPolicy::default(),
+            true,
+            DEFAULT_MAX_DECOMPRESSED_SIZE,
+            Some(Duration::from_millis(50)),
+            CancellationToken::new(),
+        );
+        let in_flight = filter.on_job_id(1001);
+        
+        assert!(matches!(
+            in_flight.await.unwrap(),
+            Err(Error::IdleTimeout(idle_timeout)) if idle_timeout == Duration::from_millis(50),
+        ));
+        assert_eq!(filter.last_disconnect_reason(), Some(DisconnectReason::IdleTimeout));
+        
+        tokio::task::yield_now().await;
+        
+        assert!(!filter.is_attached());
+    }
+    
+    #[tokio::test]
+    async fn end_of_stream_fails_in_flight_jobs_with_fresh_reason() {
+        let (filter, _rest) = MessageFilter::new(
+            futures::stream::pending(),
+            Arc::new(AtomicI32::new(0)),
+            OverflowPolicy::default(),
+            true,
+            DEFAULT_MAX_DECOMPRESSED_SIZE,
+            Some(Duration::from_millis(10)),
+            CancellationToken::new(),
+        );
+        
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        
+        assert_eq!(filter.last_disconnect_reason(), Some(DisconnectReason::IdleTimeout));
+        
+        let (source_tx, source) = futures::channel::mpsc::unbounded::<Result<tungstenite::Message, tungstenite::Error>>();
+        let _rest = filter.attach(source);
+        let in_flight = filter.on_job_id(1001);
+        
+        assert_eq!(filter.last_disconnect_reason(), None);
+        
+        drop(source_tx);
+        
+        assert!(matches!(in_flight.await.unwrap(), Err(Error::ConnectionClosed)));
+        assert_eq!(filter.last_disconnect_reason(), Some(DisconnectReason::Io(std::io::ErrorKind::UnexpectedEof)));
+    }
+    
+    #[tokio::test]
+    async fn cancellation_fails_in_flight_jobs() {
+        let cancellation_token = CancellationToken::new();
+        let (filter, _rest) = MessageFilter::new(
+            futures::stream::pending(),
+            Arc::new(AtomicI32::new(0)),
+            OverflowPolicy::default(),
+            true,
+            DEFAULT_MAX_DECOMPRESSED_SIZE,
+            None,
+            cancellation_token.clone(),
+        );
+        let in_flight = filter.on_job_id(1001);
+        
+        cancellation_token.cancel();
+        
+        assert!(matches!(in_flight.await.unwrap(), Err(Error::Cancelled)));
+        assert_eq!(filter.last_disconnect_reason(), Some(DisconnectReason::Cancelled));
+        assert!(filter.job_id_filters.is_empty());
+        
+        tokio::task::yield_now().await;
+        
+        assert!(!filter.is_attached());
+    }
+    
+    #[tokio::test]
+    async fn attach_fails_in_flight_jobs_and_routes_new_ones() {
+        let frame = include_bytes!("./fixtures/service_method_response.bin");
+        let (filter, _rest) = MessageFilter::new(
+            futures::stream::pending(),
+            Arc::new(AtomicI32::new(0)),
+            OverflowPolicy::default(),
+            true,
+            DEFAULT_MAX_DECOMPRESSED_SIZE,
+            None,
+            CancellationToken::new(),
+        );
+        let mut in_flight = filter.on_job_id(1001);
+        let (source_tx, source) = futures::channel::mpsc::unbounded();
+        let _rest = filter.attach(source);
+        
+        assert!(matches!(in_flight.try_recv().unwrap(), Err(Error::Reconnected)));
+        
+        let rx = filter.on_job_id(0x0102030405060708);
+        
+        source_tx.unbounded_send(Ok(tungstenite::Message::binary(frame.to_vec()))).unwrap();
+        
+        let response = rx.await.unwrap().unwrap();
+        
+        assert_eq!(response.eresult, Some(EResult::OK));
+        assert_eq!(filter.client_sessionid.load(Ordering::Relaxed), 42);
+    }
+    
+    #[test]
+    fn rejects_oversized_chunk_size() {
+        let mut message = CMsgMulti::new();
+        let mut payload = Vec::new();
+        
+        payload.extend(u32::MAX.to_le_bytes());
+        payload.extend([0; 16]);
+        message.set_message_body(payload);
+        
+        assert!(matches!(
+            process_multi_message(&test_filter(), &message.write_to_bytes().unwrap()),
+            Err(Error::LengthOutOfBounds { length: u32::MAX, remaining: 16 }),
+        ));
+    }
+    
+    #[tokio::test]
+    async fn surfaces_oversized_message() {
+        let (source_tx, source) = futures::channel::mpsc::unbounded();
+        let (filter, mut rest) = MessageFilter::new(source, Arc::new(AtomicI32::new(0)), OverflowPolicy::default(), true, DEFAULT_MAX_DECOMPRESSED_SIZE, None, CancellationToken::new());
+        
+        source_tx.unbounded_send(Err(tungstenite::Error::Capacity(
+            tungstenite::error::CapacityError::MessageTooLong {
+                size: 128 << 20,
+                max_size: 64 << 20,
+            },
+        ))).unwrap();
+        
+        assert!(matches!(
+            rest.recv().await,
+            Some(Err(Error::Connection(error))) if matches!(*error, tungstenite::Error::Capacity(_)),
+        ));
+        assert!(matches!(filter.last_disconnect_reason(), Some(DisconnectReason::Protocol(_))));
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// How long the read loop can be blocked on a full queue before a warning is logged.
+const BLOCK_WARNING_DURATION: Duration = Duration::from_secs(5);
+
+/// What the read loop does when the queue of messages that aren't responses to a request is 
+/// full, e.g. because they aren't being received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Drops the incoming message. The number of dropped messages is counted.
+    #[default]
+    DropNewest,
+    /// Drops the oldest queued message to make room for the incoming one. The number of dropped 
+    /// messages is counted.
+    DropOldest,
+    /// Waits until there's room. Responses and close frames aren't read while waiting, so 
+    /// in-flight requests may time out. A warning is logged if the wait is long.
+    Block,
+}
+
+#[derive(Debug)]
+struct Queue<T> {
+    items: VecDeque<T>,
+    is_sender_closed: bool,
+    is_receiver_closed: bool,
+    receiver_waker: Option<Waker>,
+}
+
+impl<T> Queue<T> {
+    /// Wakes the receiver if it's waiting for a message.
+    fn wake_receiver(&mut self) {
+        if let Some(waker) = self.receiver_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    queue: Mutex<Queue<T>>,
+    capacity: usize,
+    overflow_policy: OverflowPolicy,
+    dropped: Arc<AtomicU64>,
+    space_available: Notify,
+}
+
+/// Creates a bounded queue which handles overflow using `overflow_policy`, counting dropped 
+/// messages in `dropped`.
+pub fn channel<T>(
+    capacity: usize,
+    overflow_policy: OverflowPolicy,
+    dropped: Arc<AtomicU64>,
+) -> (QueueSender<T>, QueueReceiver<T>) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(Queue {
+            items: VecDeque::with_capacity(capacity),
+            is_sender_closed: false,
+            is_receiver_closed: false,
+            receiver_waker: None,
+        }),
+        capacity,
+        overflow_policy,
+        dropped,
+        space_available: Notify::new(),
+    });
+    
+    (QueueSender { shared: shared.clone() }, QueueReceiver { shared })
+}
+
+/// Sends messages to a [`QueueReceiver`].
+#[derive(Debug)]
+pub struct QueueSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> QueueSender<T> {
+    /// Sends `item`, handling a full queue using the overflow policy. Returns `false` if the 
+    /// receiver was dropped.
+    pub async fn send(&self, item: T) -> bool {
+        let mut item = Some(item);
+        let mut is_warned = false;
+        
+        loop {
+            {
+                let mut queue = self.shared.queue.lock().unwrap();
+                
+                if queue.is_receiver_closed {
+                    return false;
+                }
+                
+                if queue.items.len() >= self.shared.capacity {
+                    match self.shared.overflow_policy {
+                        OverflowPolicy::DropNewest => {
+                            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                            return true;
+                        },
+                        OverflowPolicy::DropOldest => {
+                            queue.items.pop_front();
+                            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                        },
+                        OverflowPolicy::Block => {},
+                    }
+                }
+                
+                if queue.items.len() < self.shared.capacity {
+                    if let Some(item) = item.take() {
+                        queue.items.push_back(item);
+                    }
+                    
+                    queue.wake_receiver();
+                    return true;
+                }
+            }
+            
+            let notified = self.shared.space_available.notified();
+            
+            if tokio::time::timeout(BLOCK_WARNING_DURATION, notified).await.is_err() && !is_warned {
+                log::warn!("Websocket read loop is blocked on a full message queue; responses and heartbeats are not being read");
+                is_warned = true;
+            }
+        }
+    }
+}
+
+impl<T> Drop for QueueSender<T> {
+    fn drop(&mut self) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        
+        queue.is_sender_closed = true;
+        queue.wake_receiver();
+    }
+}
+
+/// Receives messages from a [`QueueSender`].
+#[derive(Debug)]
+pub struct QueueReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> QueueReceiver<T> {
+    /// Receives the next message. Returns `None` once the sender is dropped and the queue is 
+    /// empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        std::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+            
+    /// Polls for the next message, registering the current task to be woken when one is queued. 
+    /// Returns `Poll::Ready(None)` once the sender is dropped and the queue is empty.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        
+        if let Some(item) = queue.items.pop_front() {
+            drop(queue);
+            self.shared.space_available.notify_one();
+            return Poll::Ready(Some(item));
+        }
+            
+        if queue.is_sender_closed {
+            return Poll::Ready(None);
+        }
+        
+        queue.receiver_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+    
+    /// Receives the next message if one is queued.
+    pub fn try_recv(&mut self) -> Option<T> {
+        let item = self.shared.queue.lock().unwrap().items.pop_front();
+        
+        if item.is_some() {
+            self.shared.space_available.notify_one();
+        }
+        
+        item
+    }
+}
+
+impl<T> Drop for QueueReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().is_receiver_closed = true;
+        self.shared.space_available.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[tokio::test]
+    async fn drop_newest_counts_dropped() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (tx, mut rx) = channel(2, OverflowPolicy::DropNewest, dropped.clone());
+        
+        for i in 0..4 {
+            assert!(tx.send(i).await);
+        }
+        
+        assert_eq!(dropped.load(Ordering::Relaxed), 2);
+        assert_eq!(rx.try_recv(), Some(0));
+        assert_eq!(rx.try_recv(), Some(1));
+        assert_eq!(rx.try_recv(), None);
+    }
+    
+    #[tokio::test]
+    async fn drop_oldest_keeps_latest() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (tx, mut rx) = channel(2, OverflowPolicy::DropOldest, dropped.clone());
+        
+        for i in 0..4 {
+            assert!(tx.send(i).await);
+        }
+        
+        drop(tx);
+        
+        assert_eq!(dropped.load(Ordering::Relaxed), 2);
+        assert_eq!(rx.recv().await, Some(2));
+        assert_eq!(rx.recv().await, Some(3));
+        assert_eq!(rx.recv().await, None);
+    }
+    
+    #[tokio::test]
+    async fn block_waits_for_space() {
+        let (tx, mut rx) = channel(1, OverflowPolicy::Block, Default::default());
+        
+        assert!(tx.send(0).await);
+        
+        let sender = tokio::spawn(async move {
+            tx.send(1).await
+        });
+        
+        assert_eq!(rx.recv().await, Some(0));
+        assert_eq!(rx.recv().await, Some(1));
+        assert!(sender.await.unwrap());
+    }
+}
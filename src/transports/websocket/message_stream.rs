@@ -0,0 +1,88 @@
+use super::{Error, Message, QueueReceiver};
+use crate::enums::EMsg;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures::{Stream, StreamExt};
+
+/// A [`Stream`] of messages from the CM server that aren't responses to a request, along with 
+/// errors that close the connection, as returned by 
+/// [`WebSocketCMTransport::take_messages`](super::WebSocketCMTransport::take_messages).
+/// 
+/// The underlying [`QueueReceiver`] is available using [`MessageStream::receiver`] or 
+/// [`MessageStream::into_inner`] for receiving messages directly.
+#[derive(Debug)]
+pub struct MessageStream {
+    receiver: QueueReceiver<Result<Message, Error>>,
+}
+
+impl MessageStream {
+    /// Creates a new [`MessageStream`] receiving from `receiver`.
+    pub fn new(receiver: QueueReceiver<Result<Message, Error>>) -> Self {
+        Self {
+            receiver,
+        }
+    }
+    
+    /// Filters the stream to messages of type `emsg`. Errors are kept, so consumers still see 
+    /// the connection closing.
+    pub fn filter_emsg(self, emsg: EMsg) -> impl Stream<Item = Result<Message, Error>> {
+        self.filter(move |message| {
+            let is_match = message
+                .as_ref()
+                .map_or(true, |message| message.emsg == emsg);
+            
+            async move { is_match }
+        })
+    }
+    
+    /// Gets the underlying receiver.
+    pub fn receiver(&mut self) -> &mut QueueReceiver<Result<Message, Error>> {
+        &mut self.receiver
+    }
+    
+    /// Unwraps the underlying receiver.
+    pub fn into_inner(self) -> QueueReceiver<Result<Message, Error>> {
+        self.receiver
+    }
+}
+
+impl Stream for MessageStream {
+    type Item = Result<Message, Error>;
+    
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::message_queue::{self, OverflowPolicy};
+    
+    fn message(emsg: EMsg) -> Result<Message, Error> {
+        Ok(Message {
+            emsg,
+            body: Vec::new(),
+        })
+    }
+    
+    #[tokio::test]
+    async fn filters_by_emsg() {
+        let (tx, rx) = message_queue::channel(4, OverflowPolicy::default(), Default::default());
+        
+        tx.send(message(EMsg::ClientLoggedOff)).await;
+        tx.send(message(EMsg::ClientHello)).await;
+        tx.send(Err(Error::Reconnected)).await;
+        tx.send(message(EMsg::ClientLoggedOff)).await;
+        drop(tx);
+        
+        let items = MessageStream::new(rx)
+            .filter_emsg(EMsg::ClientLoggedOff)
+            .collect::<Vec<_>>()
+            .await;
+        
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items[1], Err(Error::Reconnected)));
+        assert!(items.iter().filter_map(|item| item.as_ref().ok()).all(|message| message.emsg == EMsg::ClientLoggedOff));
+    }
+}
@@ -6,60 +6,131 @@ mod message_filter;
 mod message;
 mod response;
 mod helpers;
+mod disconnect_reason;
+mod frame;
+mod connect_options;
+mod message_queue;
+mod message_stream;
+mod logon_info;
+mod jobid;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 pub use cm_list_cache::Error as CmListError;
 pub use error::Error;
+pub use disconnect_reason::DisconnectReason;
+pub use frame::{decode_frame, decode_frames, DecodedFrame, DEFAULT_MAX_DECOMPRESSED_SIZE};
+pub use connect_options::{ConnectOptions, CmSelection};
+pub use message_queue::{OverflowPolicy, QueueReceiver};
+pub use message_stream::MessageStream;
+pub use message::Message;
+pub use logon_info::LogonInfo;
+pub use jobid::{JobIdGenerator, RandomJobIdGenerator, SequentialJobIdGenerator};
 
 use cm_list_cache::CmListCache;
+use cm_server::CmServer;
 use message_filter::MessageFilter;
 use steam_session_proto::steammessages_clientserver_login::CMsgClientHello;
 
-use crate::enums::EMsg;
+use crate::enums::{EMsg, EResult, EAuthTokenPlatformType};
 use crate::net::ApiRequest;
 use crate::proto::steammessages_base::CMsgProtoBufHeader;
-use crate::transports::Transport;
+use crate::transports::{Transport, ResponseReceiver};
 use crate::authentication_client::Error as AuthenticationClientError;
-use std::io::Cursor;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicI32, Ordering};
-use futures::stream::{SplitSink, SplitStream};
-use futures::SinkExt;
+use futures::stream::SplitSink;
+use futures::{SinkExt, StreamExt};
 use tokio::net::TcpStream;
-use tokio::sync::{Mutex, oneshot};
+use tokio::sync::{Mutex, oneshot, mpsc};
 use tokio_tungstenite::tungstenite;
+use tokio_tungstenite::tungstenite::http::HeaderMap;
 use tokio_tungstenite::{WebSocketStream, MaybeTlsStream};
 use protobuf::Message as ProtoMessage;
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
-use rand::Rng;
+use byteorder::{LittleEndian, WriteBytesExt};
 use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
 use lazy_static::lazy_static;
 
+/// The protocol version sent in the `ClientHello` which starts each connection, matching the 
+/// current Steam client. A CM server which doesn't accept it responds with 
+/// [`EResult::InvalidProtocolVer`](crate::enums::EResult::InvalidProtocolVer), which is 
+/// surfaced as [`Error::ProtocolVersionRejected`].
 pub const PROTOCOL_VERSION: u32 = 65580;
 pub const PROTO_MASK: u32 = 0x80000000;
 
+/// The number of outgoing messages that can be queued before senders wait for the writer.
+const WRITE_QUEUE_SIZE: usize = 64;
+
+/// An outgoing message along with a sender for the result of writing it.
+type WriteRequest = (tungstenite::Message, oneshot::Sender<Result<(), tungstenite::Error>>);
+
 lazy_static! {
     pub static ref DEFAULT_CM_LIST: Arc<Mutex<CmListCache>> = Arc::new(tokio::sync::Mutex::new(CmListCache::new()));
 }
 
 /// Represents a WebSocket CM transport.
+/// 
+/// Any number of requests can be in flight at once. Outgoing messages are queued to a dedicated 
+/// writer task, and responses are matched to their requests by job ID.
 #[derive(Debug)]
 pub struct WebSocketCMTransport {
-    websocket_write: tokio::sync::Mutex<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, tungstenite::Message>>,
+    websocket_write: std::sync::RwLock<mpsc::Sender<WriteRequest>>,
     filter: Arc<MessageFilter>,
+    messages: std::sync::Mutex<Option<QueueReceiver<Result<Message, Error>>>>,
     client_sessionid: Arc<AtomicI32>,
+    cm_list: std::sync::RwLock<Arc<Mutex<CmListCache>>>,
+    options: ConnectOptions,
+    connecting: Mutex<()>,
+    current_cm: std::sync::RwLock<String>,
 }
 
 #[async_trait]
 impl Transport for WebSocketCMTransport {
+    /// Reconnects if the connection was closed, as with [`WebSocketCMTransport::reconnect`]. The 
+    /// transport is connected when created, so this only does anything after the connection 
+    /// was lost.
+    async fn connect(&self) -> Result<(), AuthenticationClientError> {
+        self.ensure_connected().await
+    }
+    
     async fn send_request<Msg>(
+        &self,
+        msg: Msg,
+        access_token: Option<String>,
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError> 
+    where
+        Msg: ApiRequest,
+        <Msg as ApiRequest>::Response: Send,
+    {
+        WebSocketCMTransport::send_request(self, msg, access_token).await
+    }
+}
+
+impl WebSocketCMTransport {
+    /// Same as [`Transport::send_request`], without boxing the returned future. This is used 
+    /// when calling `send_request` on a [`WebSocketCMTransport`] directly.
+    /// 
+    /// The access token is unused, as requests are sent as unauthenticated service method calls 
+    /// whose header has no field for it.
+    /// 
+    /// Fails with [`Error::Cancelled`] once [`ConnectOptions::cancellation_token`] is cancelled.
+    pub async fn send_request<Msg>(
         &self,
         msg: Msg,
         _access_token: Option<String>,
-    ) -> Result<oneshot::Receiver<Result<Msg::Response, AuthenticationClientError>>, AuthenticationClientError> 
+    ) -> Result<ResponseReceiver<Msg::Response>, AuthenticationClientError> 
     where
         Msg: ApiRequest,
         <Msg as ApiRequest>::Response: Send,
     {
+        if self.options.cancellation_token.is_cancelled() {
+            return Err(Error::Cancelled.into());
+        }
+        
+        self.ensure_connected().await?;
+        
         if let Some(jobid) = self.send_message(
             <Msg as ApiRequest>::KIND,
             msg,
@@ -67,53 +138,338 @@ impl Transport for WebSocketCMTransport {
         ).await? {
             let filter_rx = self.filter.on_job_id(jobid);
             let (
-                tx,
+                mut tx,
                 rx,
             ) = oneshot::channel::<Result<Msg::Response, AuthenticationClientError>>();
+            let filter = self.filter.clone();
             
             tokio::spawn(async move {
-                tx.send(helpers::wait_for_response::<Msg>(filter_rx).await).ok();
+                let response = tokio::select! {
+                    response = helpers::wait_for_response::<Msg>(filter_rx) => Some(response),
+                    // the receiver was dropped e.g. the request was cancelled
+                    _ = tx.closed() => None,
+                };
+                
+                // the filter is no longer needed if the response timed out or was cancelled
+                filter.remove_job_id(jobid);
+                
+                let is_try_another_cm = response
+                    .as_ref()
+                    .and_then(|response| response.as_ref().err())
+                    .is_some_and(|error| error.eresult() == Some(EResult::TryAnotherCM));
+                
+                if is_try_another_cm {
+                    filter.set_try_another_cm();
+                }
+                
+                if let Some(response) = response {
+                    tx.send(response).ok();
+                }
             });
             
-            Ok(rx)
+            Ok(rx.into())
         } else {
             Err(AuthenticationClientError::NoJob)
         }
     }
-}
-
-impl WebSocketCMTransport {
+    
+    /// Reconnects if the connection was closed, or fails over to another CM server if the 
+    /// current one told us to try another. See [`Transport::connect`].
+    async fn ensure_connected(&self) -> Result<(), AuthenticationClientError> {
+        // held so concurrent requests don't each reconnect
+        let _connecting = self.connecting.lock().await;
+        
+        if !self.is_connected() {
+            self.reconnect().await?;
+        } else if self.filter.take_try_another_cm() {
+            self.fail_over().await;
+        }
+        
+        Ok(())
+    }
+    
+    /// Reconnects to a CM server other than the current one, e.g. after it responded with 
+    /// [`EResult::TryAnotherCM`]. If no other server can be connected to, the current 
+    /// connection is kept.
+    async fn fail_over(&self) {
+        let current_cm = self.current_cm.read().unwrap().clone();
+        
+        log::debug!("CM {current_cm} told us to try another CM; reconnecting");
+        
+        if let Err(error) = self.reconnect_excluding(Some(&current_cm)).await {
+            log::warn!("Failed to fail over from CM {current_cm}: {error}");
+        }
+    }
+    
+    /// Connects to a CM server which is disconnected when `cancellation_token` is cancelled. See 
+    /// [`ConnectOptions::cancellation_token`].
+    pub async fn connect_with_cancellation(
+        cancellation_token: CancellationToken,
+    ) -> Result<WebSocketCMTransport, Error> {
+        Self::connect_with_options(ConnectOptions {
+            cancellation_token,
+            ..Default::default()
+        }).await
+    }
+    
     /// Connects to a CM server.
     pub async fn connect() -> Result<WebSocketCMTransport, Error> {
-        let transport = helpers::connect_to_cm(&DEFAULT_CM_LIST).await?;
+        Self::connect_with_options(ConnectOptions::default()).await
+    }
+    
+    /// Connects to a CM server, sending `headers` with the websocket handshake. Headers 
+    /// matching the official clients can be created using [`Self::handshake_headers`].
+    /// 
+    /// Returns [`Error::ReservedHandshakeHeader`] if `headers` contains a header that is set by 
+    /// the handshake itself, such as `Sec-WebSocket-Key`.
+    pub async fn connect_with_headers(headers: HeaderMap) -> Result<WebSocketCMTransport, Error> {
+        Self::connect_with_options(ConnectOptions {
+            headers,
+            ..Default::default()
+        }).await
+    }
+    
+    /// Connects to a CM server, fetching the list of CM servers using `client`, e.g. the same 
+    /// client configured with a proxy used for other requests. See 
+    /// [`ConnectOptions::directory_client`].
+    pub async fn connect_with_client(client: reqwest::Client) -> Result<WebSocketCMTransport, Error> {
+        Self::connect_with_options(ConnectOptions {
+            directory_client: Some(client),
+            ..Default::default()
+        }).await
+    }
+    
+    /// Connects to a CM server using `options`, which control the handshake headers and the 
+    /// socket options applied before the handshake. See [`ConnectOptions`] for the defaults.
+    /// 
+    /// Returns [`Error::Cancelled`] if [`ConnectOptions::cancellation_token`] is cancelled before 
+    /// the connection is established.
+    pub async fn connect_with_options(options: ConnectOptions) -> Result<WebSocketCMTransport, Error> {
+        helpers::validate_handshake_headers(&options.headers)?;
+        
+        let cancellation_token = options.cancellation_token.clone();
+        
+        tokio::select! {
+            biased;
+            _ = cancellation_token.cancelled() => Err(Error::Cancelled),
+            result = async {
+                let transport = helpers::connect_to_cm(&DEFAULT_CM_LIST, &options).await?;
+                
+                transport.send_hello().await?;
+                Ok(transport)
+            } => result,
+        }
+    }
+    
+    /// Connects to `endpoint` only, e.g. one resolved ahead of time so the whole session uses 
+    /// the same CM server. `endpoint` is either a `host:port` pair or a full websocket URL. If 
+    /// `reconnect` is `true`, [`WebSocketCMTransport::reconnect`] reconnects to the same 
+    /// endpoint; otherwise it fails. See [`CmSelection::Pinned`].
+    pub async fn connect_pinned(
+        endpoint: impl Into<String>,
+        reconnect: bool,
+    ) -> Result<WebSocketCMTransport, Error> {
+        Self::connect_with_options(ConnectOptions {
+            cm_selection: CmSelection::Pinned {
+                endpoint: endpoint.into(),
+                reconnect,
+            },
+            ..Default::default()
+        }).await
+    }
+    
+    /// Reconnects, e.g. after the connection was closed. Requests still in flight over the 
+    /// previous connection fail with [`Error::Reconnected`].
+    /// 
+    /// With [`CmSelection::Failover`], a server is picked from the CM list as when connecting. 
+    /// With [`CmSelection::Pinned`], the pinned endpoint is reconnected to, or 
+    /// [`Error::PinnedEndpointDisconnected`] is returned if reconnecting is disabled.
+    /// 
+    /// The stream returned by [`WebSocketCMTransport::take_messages`] only receives messages 
+    /// from the previous connection, so it should be taken again after reconnecting.
+    /// 
+    /// Returns [`Error::Cancelled`] if [`ConnectOptions::cancellation_token`] is cancelled.
+    pub async fn reconnect(&self) -> Result<(), Error> {
+        self.reconnect_excluding(None).await
+    }
+    
+    /// Same as `reconnect`, but doesn't pick the CM server at `exclude`, a `host:port` as 
+    /// returned by [`WebSocketCMTransport::current_cm`].
+    async fn reconnect_excluding(&self, exclude: Option<&str>) -> Result<(), Error> {
+        if let CmSelection::Pinned { endpoint, reconnect: false } = &self.options.cm_selection {
+            return Err(Error::PinnedEndpointDisconnected(endpoint.clone()));
+        }
+        
+        tokio::select! {
+            biased;
+            _ = self.options.cancellation_token.cancelled() => Err(Error::Cancelled),
+            result = self.open_connection(exclude) => result,
+        }
+    }
+    
+    /// Opens a new connection to a CM server other than `exclude` and starts a session on it.
+    async fn open_connection(&self, exclude: Option<&str>) -> Result<(), Error> {
+        self.apply_cell_id().await;
+        
+        let (websocket, cm) = helpers::open_cm_websocket(&self.cm_list(), &self.options, exclude).await?;
+        let (websocket_write, source) = websocket.split();
+        let messages = self.filter.attach(source);
+        
+        *self.websocket_write.write().unwrap() = spawn_writer(websocket_write, self.options.cancellation_token.clone());
+        *self.current_cm.write().unwrap() = cm;
+        *self.messages.lock().unwrap() = Some(messages);
+        self.send_hello().await
+    }
+    
+    /// Sends the `ClientHello` which starts a session.
+    async fn send_hello(&self) -> Result<(), Error> {
         let mut hello = CMsgClientHello::new();
         
         hello.set_protocol_version(PROTOCOL_VERSION);
-        transport.send_message(
+        self.send_message(
             EMsg::ClientHello,
             hello,
             None,
         ).await?;
         
-        Ok(transport)
+        Ok(())
+    }
+    
+    /// Gets the handshake headers sent by the official clients for `platform_type`, i.e. the 
+    /// `User-Agent` and `Origin`.
+    pub fn handshake_headers(platform_type: EAuthTokenPlatformType) -> HeaderMap {
+        helpers::handshake_headers(platform_type)
     }
     
     /// Creates a new [`WebSocketCMTransport`].
     fn new(
-        source: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
-        websocket_write: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, tungstenite::Message>,
+        websocket: helpers::WebSocket,
+        cm: String,
+        cm_list: Arc<Mutex<CmListCache>>,
+        options: &ConnectOptions,
     ) -> Self {
+        let (websocket_write, source) = websocket.split();
         let client_sessionid = Arc::new(AtomicI32::new(0));
-        let (filter, _rest) = MessageFilter::new(
+        let (filter, messages) = MessageFilter::new(
             source,
             client_sessionid.clone(),
+            options.overflow_policy,
+            options.strict_enums,
+            options.max_decompressed_size,
+            options.idle_timeout,
+            options.cancellation_token.clone(),
         );
         
         Self {
-            websocket_write: tokio::sync::Mutex::new(websocket_write),
+            websocket_write: std::sync::RwLock::new(spawn_writer(websocket_write, options.cancellation_token.clone())),
             filter: Arc::new(filter),
+            messages: std::sync::Mutex::new(Some(messages)),
             client_sessionid,
+            cm_list: std::sync::RwLock::new(cm_list),
+            options: options.clone(),
+            connecting: Mutex::new(()),
+            current_cm: std::sync::RwLock::new(cm),
+        }
+    }
+    
+    /// Checks if the connection to the CM server is open.
+    pub fn is_connected(&self) -> bool {
+        self.filter.is_attached()
+    }
+    
+    /// Gets the `host:port` of the CM server the transport is connected to, e.g. to correlate 
+    /// issues with a specific server. This is updated on each reconnect. Returns `None` if the 
+    /// connection is closed.
+    pub fn current_cm(&self) -> Option<String> {
+        if !self.is_connected() {
+            return None;
         }
+        
+        Some(self.current_cm.read().unwrap().clone())
+    }
+    
+    /// Gets the maximum size in bytes a compressed `Multi` message may have once decompressed, 
+    /// as set using [`ConnectOptions::max_decompressed_size`].
+    pub fn max_decompressed_size(&self) -> usize {
+        self.options.max_decompressed_size
+    }
+    
+    /// Gets the reason the connection to the CM server was closed, if it has been. This is kept 
+    /// after the connection drops so it can be inspected after the fact, until the transport 
+    /// reconnects.
+    pub fn last_disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.filter.last_disconnect_reason()
+    }
+    
+    /// Takes the stream of messages from the CM server that aren't responses to a request, along 
+    /// with errors that close the connection. Returns `None` if it was already taken.
+    /// 
+    /// Messages are queued until received. If the queue is full, messages are handled according 
+    /// to [`ConnectOptions::overflow_policy`].
+    pub fn take_messages(&self) -> Option<MessageStream> {
+        self.messages.lock().unwrap().take().map(MessageStream::new)
+    }
+    
+    /// Gets the number of messages that weren't responses to a request which were dropped 
+    /// because they weren't received in time. See [`ConnectOptions::overflow_policy`].
+    pub fn dropped_messages(&self) -> u64 {
+        self.filter.dropped_messages()
+    }
+    
+    /// Gets the fields of the last `ClientLogOnResponse` received, if any. These are consumed as 
+    /// follows:
+    /// 
+    /// - `heartbeat_seconds` (or `legacy_out_of_game_heartbeat_seconds`) as the interval for 
+    ///   heartbeats. The transport doesn't log on, so it doesn't send heartbeats itself.
+    /// - `cell_id` when fetching the CM list, so servers near the assigned cell are picked when 
+    ///   reconnecting or refreshing the list.
+    /// - `webapi_authenticate_user_nonce` for authenticating with the web API.
+    /// 
+    /// A response with a result other than OK is the CM telling us to try another CM, see 
+    /// [`DisconnectReason::TryAnotherCm`]. The next request then reconnects to another server.
+    pub fn logon_info(&self) -> Option<LogonInfo> {
+        self.filter.logon_info()
+    }
+    
+    /// Gets the CM list used by this transport.
+    fn cm_list(&self) -> Arc<Mutex<CmListCache>> {
+        self.cm_list.read().unwrap().clone()
+    }
+    
+    /// Uses the cell ID from the last logon response, if any, when fetching the CM list.
+    /// 
+    /// The list may be shared with other transports, e.g. [`DEFAULT_CM_LIST`], so rather than 
+    /// changing its cell ID this transport switches to a list of its own.
+    async fn apply_cell_id(&self) {
+        let Some(cell_id) = self.logon_info().and_then(|logon_info| logon_info.cell_id) else {
+            return;
+        };
+        
+        if self.cm_list().lock().await.cell_id() == cell_id {
+            return;
+        }
+        
+        let mut cm_list = CmListCache::new();
+        
+        cm_list.set_cell_id(cell_id);
+        *self.cm_list.write().unwrap() = Arc::new(Mutex::new(cm_list));
+    }
+    
+    /// Re-fetches the list of CM servers, regardless of whether the cached list has expired. 
+    /// Returns the new list.
+    /// 
+    /// This is done automatically when connecting if every server in the cached list fails.
+    pub async fn refresh_cm_list(&self) -> Result<Vec<CmServer>, Error> {
+        self.apply_cell_id().await;
+        
+        let cm_list = self.cm_list();
+        let mut cm_list = cm_list.lock().await;
+        
+        cm_list.refresh_with_client(self.options.cm_list_client())
+            .await
+            .map_err(helpers::cm_list_error)?;
+        
+        Ok(cm_list.get().clone())
     }
     
     /// Sends a message to the CM server.
@@ -138,19 +494,17 @@ impl WebSocketCMTransport {
         proto_header.set_client_sessionid(client_sessionid);
         
         let jobid = if emsg == EMsg::ServiceMethodCallFromClientNonAuthed {
-            let mut jobid_buffer = rand::thread_rng().gen::<[u8; 8]>();
+            let jobid = self.options.jobid_generator.next_jobid();
             
-            jobid_buffer[0] &= 0x7f;
+            if jobid == 0 {
+                return Err(Error::ZeroJobId);
+            }
             
             if let Some(target_job_name) = service_method_name {
                 proto_header.set_target_job_name(target_job_name.to_string());
             }
             
             proto_header.set_realm(1);
-            
-            let mut jobid_buffer_reader = Cursor::new(jobid_buffer);
-            let jobid = jobid_buffer_reader.read_u64::<BigEndian>()?;
-            
             proto_header.set_jobid_source(jobid);
             
             Some(jobid)
@@ -164,7 +518,7 @@ impl WebSocketCMTransport {
         let mut header: Vec<u8> = Vec::new();
         let header_length = encoded_proto_header.len() as u32;
         
-        header.write_u32::<LittleEndian>(emsg as u32 | PROTO_MASK)?; // 4
+        header.write_u32::<LittleEndian>(u32::from(emsg) | PROTO_MASK)?; // 4
         header.write_u32::<LittleEndian>(header_length)?; // 8
         
         if let Some(jobid) = jobid {
@@ -180,9 +534,318 @@ impl WebSocketCMTransport {
         message.append(&mut body);
         
         let message = tungstenite::Message::binary(message);
+        let (tx, rx) = oneshot::channel();
+        
+        // cloned so a concurrent reconnect can replace the writer
+        let websocket_write = self.websocket_write.read().unwrap().clone();
         
-        self.websocket_write.lock().await.send(message).await?;
+        websocket_write.send((message, tx)).await
+            .map_err(|_error| Error::ConnectionClosed)?;
+        rx.await
+            .map_err(|_error| Error::ConnectionClosed)??;
         
         Ok(jobid)
     }
-}
\ No newline at end of file
+}
+
+/// Spawns a task which writes queued messages to `websocket_write`. The task ends and closes the 
+/// connection once every sender for the queue is dropped or `cancellation_token` is cancelled.
+fn spawn_writer(
+    mut websocket_write: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, tungstenite::Message>,
+    cancellation_token: CancellationToken,
+) -> mpsc::Sender<WriteRequest> {
+    let (tx, mut rx) = mpsc::channel::<WriteRequest>(WRITE_QUEUE_SIZE);
+    
+    tokio::spawn(async move {
+        loop {
+            let request = tokio::select! {
+                biased;
+                _ = cancellation_token.cancelled() => break,
+                request = rx.recv() => request,
+            };
+            let Some((message, result_tx)) = request else {
+                break;
+            };
+            let result = websocket_write.send(message).await;
+            
+            if let Err(error) = &result {
+                log::warn!("Error writing to websocket connection: {error}");
+            }
+            
+            let _ = result_tx.send(result);
+        }
+        
+        let _ = websocket_write.close().await;
+    });
+    
+    tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::steammessages_auth_steamclient::{
+        CAuthentication_GetPasswordRSAPublicKey_Request,
+        CAuthentication_GetPasswordRSAPublicKey_Response,
+    };
+    use futures::StreamExt;
+    use futures::future::join_all;
+    use std::io::Cursor;
+    use byteorder::ReadBytesExt;
+    use tokio::net::TcpListener;
+    
+    /// Builds a response frame for `jobid` containing `body`.
+    fn response_frame(jobid: u64, body: Vec<u8>) -> Vec<u8> {
+        let mut header = CMsgProtoBufHeader::new();
+        
+        header.set_jobid_target(jobid);
+        header.set_eresult(1);
+        
+        let header = header.write_to_bytes().unwrap();
+        let mut frame = Vec::new();
+        
+        frame.write_u32::<LittleEndian>(u32::from(EMsg::ServiceMethodResponse) | PROTO_MASK).unwrap();
+        frame.write_u32::<LittleEndian>(header.len() as u32).unwrap();
+        frame.extend(header);
+        frame.extend(body);
+        frame
+    }
+    
+    #[tokio::test]
+    async fn resolves_concurrent_requests() {
+        const REQUEST_COUNT: usize = 20;
+        
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut websocket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let mut responses = Vec::new();
+            
+            while responses.len() < REQUEST_COUNT {
+                let Some(Ok(tungstenite::Message::Binary(frame))) = websocket.next().await else {
+                    continue;
+                };
+                let mut cursor = Cursor::new(frame.as_ref());
+                let _emsg = cursor.read_u32::<LittleEndian>().unwrap();
+                let header_length = cursor.read_u32::<LittleEndian>().unwrap() as usize;
+                let header = CMsgProtoBufHeader::parse_from_bytes(&frame[8..8 + header_length]).unwrap();
+                let request = CAuthentication_GetPasswordRSAPublicKey_Request::parse_from_bytes(&frame[8 + header_length..]).unwrap();
+                let mut response = CAuthentication_GetPasswordRSAPublicKey_Response::new();
+                
+                response.set_publickey_mod(request.account_name().to_string());
+                responses.push(response_frame(header.jobid_source(), response.write_to_bytes().unwrap()));
+            }
+            
+            // respond in reverse order so responses don't line up with requests
+            for frame in responses.into_iter().rev() {
+                websocket.send(tungstenite::Message::binary(frame)).await.unwrap();
+            }
+        });
+        
+        let transport = helpers::connect_to_url(
+            &format!("ws://{addr}/cmsocket/"),
+            &DEFAULT_CM_LIST,
+            &ConnectOptions::default(),
+        ).await.unwrap();
+        let requests = (0..REQUEST_COUNT)
+            .map(|i| {
+                let transport = &transport;
+                
+                async move {
+                    let mut msg = CAuthentication_GetPasswordRSAPublicKey_Request::new();
+                    
+                    msg.set_account_name(format!("account{i}"));
+                    
+                    let response = transport.send_request(msg, None).await.unwrap().await.unwrap().unwrap();
+                    
+                    (i, response)
+                }
+            });
+        
+        for (i, response) in join_all(requests).await {
+            assert_eq!(response.publickey_mod(), format!("account{i}"));
+        }
+    }
+    
+    #[tokio::test]
+    async fn raw_response_keeps_unknown_fields() {
+        let mut response = CAuthentication_GetPasswordRSAPublicKey_Response::new();
+        
+        response.set_publickey_mod("c0ffee".into());
+        
+        let mut body = response.write_to_bytes().unwrap();
+        
+        // field 99, varint 1, which isn't in the response definition
+        body.extend([0x98, 0x06, 0x01]);
+        
+        let reply = body.clone();
+        let server = testing::MockCmServer::start(move |request| match request.jobid {
+            Some(_) => vec![testing::MockReply::Response {
+                eresult: crate::enums::EResult::OK,
+                body: reply.clone(),
+            }],
+            None => Vec::new(),
+        }).await.unwrap();
+        let transport = server.connect().await.unwrap();
+        let raw = transport.send_request_raw(CAuthentication_GetPasswordRSAPublicKey_Request::new(), None).await.unwrap();
+        
+        assert_eq!(raw.response.publickey_mod(), "c0ffee");
+        assert_eq!(raw.body, body);
+    }
+    
+    #[tokio::test]
+    async fn reconnects_to_pinned_endpoint() {
+        let server = testing::MockCmServer::echo().await.unwrap();
+        let transport = WebSocketCMTransport::connect_pinned(server.url(), true).await.unwrap();
+        let mut msg = CAuthentication_GetPasswordRSAPublicKey_Request::new();
+        
+        msg.set_account_name("account".into());
+        transport.send_request(msg.clone(), None).await.unwrap().await.unwrap().unwrap();
+        transport.reconnect().await.unwrap();
+        transport.send_request(msg, None).await.unwrap().await.unwrap().unwrap();
+        
+        let emsgs = server.requests()
+            .into_iter()
+            .map(|request| request.emsg)
+            .collect::<Vec<_>>();
+        
+        assert_eq!(emsgs, [
+            EMsg::ClientHello,
+            EMsg::ServiceMethodCallFromClientNonAuthed,
+            EMsg::ClientHello,
+            EMsg::ServiceMethodCallFromClientNonAuthed,
+        ]);
+    }
+    
+    #[tokio::test]
+    async fn reconnects_when_sending_after_disconnect() {
+        let closed = std::sync::atomic::AtomicBool::new(false);
+        let server = testing::MockCmServer::start(move |request| match request.jobid {
+            Some(_) if !closed.swap(true, Ordering::Relaxed) => vec![testing::MockReply::Close {
+                code: 1000,
+                reason: "bye".into(),
+            }],
+            Some(_) => vec![testing::MockReply::Response {
+                eresult: crate::enums::EResult::OK,
+                body: request.body.clone(),
+            }],
+            None => Vec::new(),
+        }).await.unwrap();
+        let transport = WebSocketCMTransport::connect_pinned(server.url(), true).await.unwrap();
+        
+        transport.connect().await.unwrap();
+        
+        assert!(transport.is_connected());
+        assert!(transport.send_request(CAuthentication_GetPasswordRSAPublicKey_Request::new(), None)
+            .await
+            .unwrap()
+            .await
+            .unwrap()
+            .is_err());
+        
+        while transport.is_connected() {
+            async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        
+        transport.send_request(CAuthentication_GetPasswordRSAPublicKey_Request::new(), None)
+            .await
+            .unwrap()
+            .await
+            .unwrap()
+            .unwrap();
+        
+        assert!(transport.is_connected());
+    }
+    
+    #[tokio::test]
+    async fn uses_injected_jobid_generator() {
+        let server = testing::MockCmServer::echo().await.unwrap();
+        let transport = server.connect_with_options(&ConnectOptions {
+            jobid_generator: Arc::new(SequentialJobIdGenerator::default()),
+            ..Default::default()
+        }).await.unwrap();
+        
+        for _ in 0..3 {
+            transport.send_request(CAuthentication_GetPasswordRSAPublicKey_Request::new(), None)
+                .await
+                .unwrap()
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        
+        let jobids = server.requests()
+            .into_iter()
+            .filter_map(|request| request.jobid)
+            .collect::<Vec<_>>();
+        
+        assert_eq!(jobids, [1, 2, 3]);
+    }
+    
+    #[tokio::test]
+    async fn rejects_zero_jobid() {
+        let server = testing::MockCmServer::echo().await.unwrap();
+        let transport = server.connect_with_options(&ConnectOptions {
+            jobid_generator: Arc::new(SequentialJobIdGenerator::new(0)),
+            ..Default::default()
+        }).await.unwrap();
+        
+        assert!(matches!(
+            transport.send_request(CAuthentication_GetPasswordRSAPublicKey_Request::new(), None).await,
+            Err(AuthenticationClientError::WebSocketCM(Error::ZeroJobId)),
+        ));
+    }
+    
+    #[tokio::test]
+    async fn tracks_current_cm() {
+        let server = testing::MockCmServer::start(|request| match request.jobid {
+            Some(_) => vec![testing::MockReply::Close {
+                code: 1000,
+                reason: "bye".into(),
+            }],
+            None => Vec::new(),
+        }).await.unwrap();
+        let transport = server.connect().await.unwrap();
+        let addr = server.url()
+            .trim_start_matches("ws://")
+            .trim_end_matches("/cmsocket/")
+            .to_string();
+        
+        assert_eq!(transport.current_cm(), Some(addr));
+        
+        transport.send_request(CAuthentication_GetPasswordRSAPublicKey_Request::new(), None).await.ok();
+        
+        while transport.is_connected() {
+            async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        
+        assert_eq!(transport.current_cm(), None);
+    }
+    
+    #[tokio::test]
+    async fn pinned_endpoint_without_reconnect_fails() {
+        let server = testing::MockCmServer::echo().await.unwrap();
+        let transport = WebSocketCMTransport::connect_pinned(server.url(), false).await.unwrap();
+        
+        assert!(matches!(
+            transport.reconnect().await,
+            Err(Error::PinnedEndpointDisconnected(endpoint)) if endpoint == server.url()
+        ));
+    }
+    
+    #[test]
+    fn rejects_reserved_handshake_headers() {
+        let mut headers = WebSocketCMTransport::handshake_headers(EAuthTokenPlatformType::k_EAuthTokenPlatformType_WebBrowser);
+        
+        assert!(helpers::validate_handshake_headers(&headers).is_ok());
+        
+        headers.insert("Sec-WebSocket-Key", tungstenite::http::HeaderValue::from_static("dGhlIHNhbXBsZSBub25jZQ=="));
+        
+        assert!(matches!(
+            helpers::validate_handshake_headers(&headers),
+            Err(Error::ReservedHandshakeHeader(name)) if name == "sec-websocket-key"
+        ));
+    }
+}
@@ -4,31 +4,72 @@ use crate::net::{ApiRequest, ApiResponse};
 use bytes::BytesMut;
 use bytes::Buf;
 
+/// The body of a response to a job, along with its result.
 #[derive(Debug, Clone)]
 pub struct ApiResponseBody {
     pub eresult: Option<EResult>,
     pub error_message: Option<String>,
+    /// The protobuf-encoded body. A successful response whose fields all have their default 
+    /// values has an empty body rather than none, e.g. `UpdateAuthSessionWithMobileConfirmation`, 
+    /// whose response has no fields, or `PollAuthSessionStatus` before the login is confirmed.
     pub body: Option<Vec<u8>>,
 }
 
 impl ApiResponseBody {
+    /// Parses the typed response. An empty body, or a missing body with a result of 
+    /// [`EResult::OK`], is parsed as the default message.
     pub fn into_response<Msg>(self) -> Result<Msg::Response, Error>
     where
         Msg: ApiRequest,
         <Msg as ApiRequest>::Response: Send,
     {
-        if let Some(body) = self.body {
-            let bytes = BytesMut::from(body.as_slice());
-            let mut reader = bytes.reader();
-            let response = Msg::Response::parse_from_reader(&mut reader)?;
-            
-            Ok(response)
-        } else if let Some(eresult) = self.eresult {
-            Err(Error::EResultNotOK(eresult))
-        } else if let Some(message) = self.error_message {
-            Err(Error::ResponseError(message))
-        } else {
-            Err(Error::NoBodyInResponse)
-        }
+        let body = match (self.body, self.eresult) {
+            (Some(body), _) => body,
+            // nothing to parse, but the request succeeded
+            (None, Some(EResult::OK)) => Vec::new(),
+            (None, Some(eresult)) => return Err(Error::EResultNotOK(eresult)),
+            (None, None) => return Err(match self.error_message {
+                Some(message) => Error::ResponseError(message),
+                None => Error::NoBodyInResponse,
+            }),
+        };
+        let bytes = BytesMut::from(body.as_slice());
+        let mut reader = bytes.reader();
+        let response = Msg::Response::parse_from_reader(&mut reader)?;
+        
+        Ok(response)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::steammessages_auth_steamclient::{
+        CAuthentication_PollAuthSessionStatus_Request,
+        CAuthentication_PollAuthSessionStatus_Response,
+        CAuthentication_UpdateAuthSessionWithMobileConfirmation_Request,
+    };
+    
+    #[test]
+    fn parses_empty_body_as_default_message() {
+        let body = |body: Option<Vec<u8>>| ApiResponseBody {
+            eresult: Some(EResult::OK),
+            error_message: None,
+            body,
+        };
+        
+        assert_eq!(
+            body(Some(Vec::new())).into_response::<CAuthentication_PollAuthSessionStatus_Request>().unwrap(),
+            CAuthentication_PollAuthSessionStatus_Response::default(),
+        );
+        assert!(body(None).into_response::<CAuthentication_UpdateAuthSessionWithMobileConfirmation_Request>().is_ok());
+        assert!(matches!(
+            ApiResponseBody {
+                eresult: None,
+                error_message: None,
+                body: None,
+            }.into_response::<CAuthentication_PollAuthSessionStatus_Request>(),
+            Err(Error::NoBodyInResponse),
+        ));
+    }
+}
@@ -0,0 +1,475 @@
+//! A loopback CM server for testing code which uses [`WebSocketCMTransport`] without 
+//! connecting to Steam. Enabled with the `testing` feature.
+//! 
+//! The server accepts websocket connections on `127.0.0.1` and speaks the CM framing. Each 
+//! request is passed to a responder, which returns the replies to send back, e.g. a response 
+//! routed to the request's job ID, a `Multi` message, a `ClientLogOnResponse` telling the client 
+//! to try another CM, or a close frame.
+
+use super::{Error, WebSocketCMTransport, ConnectOptions, CmListCache, CmServer, PROTO_MASK};
+use super::frame::split_frame;
+use super::helpers::connect_to_url;
+use crate::enums::{EMsg, EResult};
+use crate::proto::steammessages_base::{CMsgMulti, CMsgProtoBufHeader};
+use crate::proto::steammessages_clientserver_login::CMsgClientLogonResponse;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use futures::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::AbortHandle;
+use tokio_tungstenite::tungstenite;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use protobuf::Message as ProtoMessage;
+use byteorder::{LittleEndian, WriteBytesExt};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// A request received by a [`MockCmServer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockRequest {
+    /// The message type.
+    pub emsg: EMsg,
+    /// The job ID of the request, for service method calls.
+    pub jobid: Option<u64>,
+    /// The name of the service method, for service method calls.
+    pub target_job_name: Option<String>,
+    /// The message body.
+    pub body: Vec<u8>,
+}
+
+/// A reply sent by a [`MockCmServer`] to a [`MockRequest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockReply {
+    /// A `ServiceMethodResponse` to the request.
+    Response {
+        /// The result of the request.
+        eresult: EResult,
+        /// The response body.
+        body: Vec<u8>,
+    },
+    /// A `ServiceMethodResponse` to the request wrapped in a `Multi` message.
+    MultiResponse {
+        /// The result of the request.
+        eresult: EResult,
+        /// The response body.
+        body: Vec<u8>,
+        /// Whether the `Multi` payload is gzipped.
+        gzip: bool,
+    },
+    /// A `ClientLogOnResponse`, which tells the client to try another CM.
+    TryAnotherCm(EResult),
+    /// Closes the connection with a close frame.
+    Close {
+        /// The close code.
+        code: u16,
+        /// The reason for closing the connection.
+        reason: String,
+    },
+    /// A raw binary message.
+    Raw(Vec<u8>),
+}
+
+type Responder = dyn Fn(&MockRequest) -> Vec<MockReply> + Send + Sync;
+
+/// A loopback CM server. The server stops when dropped.
+#[derive(Debug)]
+pub struct MockCmServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    listener: AbortHandle,
+}
+
+impl MockCmServer {
+    /// Starts a server which replies to each request with the replies returned by `responder`.
+    pub async fn start<F>(responder: F) -> std::io::Result<Self>
+    where
+        F: Fn(&MockRequest) -> Vec<MockReply> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let responder: Arc<Responder> = Arc::new(responder);
+        let listener = {
+            let requests = requests.clone();
+            
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream, requests.clone(), responder.clone()));
+                }
+            })
+        };
+        
+        Ok(Self {
+            addr,
+            requests,
+            listener: listener.abort_handle(),
+        })
+    }
+    
+    /// Starts a server which responds to every service method call with the body of the 
+    /// request.
+    pub async fn echo() -> std::io::Result<Self> {
+        Self::start(|request| match request.jobid {
+            Some(_) => vec![MockReply::Response {
+                eresult: EResult::OK,
+                body: request.body.clone(),
+            }],
+            None => Vec::new(),
+        }).await
+    }
+    
+    /// Gets the URL of the server.
+    pub fn url(&self) -> String {
+        format!("ws://{}/cmsocket/", self.addr)
+    }
+    
+    /// Gets an entry for the server in a CM list, e.g. for a [`CmListCache`] created using 
+    /// [`CmListCache::with_servers`].
+    pub fn cm_server(&self) -> CmServer {
+        CmServer {
+            endpoint: self.url(),
+            legacy_endpoint: None,
+            r#type: "websockets".into(),
+            dc: None,
+            realm: "steamglobal".into(),
+            load: None,
+            wtd_load: None,
+        }
+    }
+    
+    /// Gets the requests received so far, across all connections.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+    
+    /// Connects a new [`WebSocketCMTransport`] to the server.
+    pub async fn connect(&self) -> Result<WebSocketCMTransport, Error> {
+        self.connect_with_options(&ConnectOptions::default()).await
+    }
+    
+    /// Connects a new [`WebSocketCMTransport`] to the server using `options`.
+    pub async fn connect_with_options(
+        &self,
+        options: &ConnectOptions,
+    ) -> Result<WebSocketCMTransport, Error> {
+        let cm_list = Arc::new(tokio::sync::Mutex::new(CmListCache::new()));
+        
+        connect_to_url(&self.url(), &cm_list, options).await
+    }
+}
+
+impl Drop for MockCmServer {
+    fn drop(&mut self) {
+        self.listener.abort();
+    }
+}
+
+/// Serves a single connection.
+async fn serve(
+    stream: TcpStream,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    responder: Arc<Responder>,
+) -> Result<(), tungstenite::Error> {
+    let mut websocket = tokio_tungstenite::accept_async(stream).await?;
+    
+    while let Some(message) = websocket.next().await {
+        let tungstenite::Message::Binary(frame) = message? else {
+            continue;
+        };
+        let Ok((emsg, header, body)) = split_frame(&frame) else {
+            log::warn!("Mock CM server received an invalid frame");
+            continue;
+        };
+        let request = MockRequest {
+            emsg,
+            jobid: header.has_jobid_source().then(|| header.jobid_source()),
+            target_job_name: header.target_job_name.clone(),
+            body: body.to_vec(),
+        };
+        
+        requests.lock().unwrap().push(request.clone());
+        
+        for reply in responder(&request) {
+            let jobid = request.jobid.unwrap_or_default();
+            let frame = match reply {
+                MockReply::Response { eresult, body } => {
+                    response_frame(EMsg::ServiceMethodResponse, jobid, eresult, &body)
+                },
+                MockReply::MultiResponse { eresult, body, gzip } => {
+                    let frame = response_frame(EMsg::ServiceMethodResponse, jobid, eresult, &body);
+                    
+                    multi_frame(&[frame], gzip)
+                },
+                MockReply::TryAnotherCm(eresult) => {
+                    let mut logon_response = CMsgClientLogonResponse::new();
+                    
+                    logon_response.set_eresult(eresult.into());
+                    response_frame(EMsg::ClientLogOnResponse, 0, EResult::OK, &logon_response.write_to_bytes().unwrap_or_default())
+                },
+                MockReply::Close { code, reason } => {
+                    return websocket.close(Some(CloseFrame {
+                        code: code.into(),
+                        reason: reason.into(),
+                    })).await;
+                },
+                MockReply::Raw(frame) => frame,
+            };
+            
+            websocket.send(tungstenite::Message::binary(frame)).await?;
+        }
+    }
+    
+    Ok(())
+}
+
+/// Builds a frame for `emsg` responding to `jobid` with `eresult` and `body`.
+pub fn response_frame(emsg: EMsg, jobid: u64, eresult: EResult, body: &[u8]) -> Vec<u8> {
+    let mut header = CMsgProtoBufHeader::new();
+    
+    header.set_jobid_target(jobid);
+    header.set_eresult(eresult.into());
+    
+    let header = header.write_to_bytes().unwrap_or_default();
+    let mut frame = Vec::with_capacity(8 + header.len() + body.len());
+    
+    frame.extend((u32::from(emsg) | PROTO_MASK).to_le_bytes());
+    frame.extend((header.len() as u32).to_le_bytes());
+    frame.extend(header);
+    frame.extend(body);
+    frame
+}
+
+/// Builds a `Multi` frame containing `frames`, gzipping the payload if `gzip` is set.
+pub fn multi_frame(frames: &[Vec<u8>], gzip: bool) -> Vec<u8> {
+    let mut payload = Vec::new();
+    
+    for frame in frames {
+        payload.write_u32::<LittleEndian>(frame.len() as u32).ok();
+        payload.extend(frame);
+    }
+    
+    let mut message = CMsgMulti::new();
+    
+    if gzip {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        
+        encoder.write_all(&payload).ok();
+        message.set_size_unzipped(payload.len() as u32);
+        message.set_message_body(encoder.finish().unwrap_or_default());
+    } else {
+        message.set_message_body(payload);
+    }
+    
+    response_frame(EMsg::Multi, 0, EResult::OK, &message.write_to_bytes().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::DisconnectReason;
+    use crate::transports::{Transport, TransportLayer, RetryLayer, RetryPolicy};
+    use crate::authentication_client::Error as AuthenticationClientError;
+    use tokio_util::sync::CancellationToken;
+    use crate::proto::steammessages_auth_steamclient::{
+        CAuthentication_GetPasswordRSAPublicKey_Request,
+        CAuthentication_GetPasswordRSAPublicKey_Response,
+    };
+    
+    async fn get_rsa_key(
+        transport: &impl Transport,
+        account_name: &str,
+    ) -> Result<CAuthentication_GetPasswordRSAPublicKey_Response, AuthenticationClientError> {
+        let mut msg = CAuthentication_GetPasswordRSAPublicKey_Request::new();
+        
+        msg.set_account_name(account_name.into());
+        transport.send_request(msg, None).await?.await?
+    }
+    
+    /// A response whose `publickey_mod` is "c0ffee".
+    fn rsa_key_response() -> Vec<u8> {
+        let mut response = CAuthentication_GetPasswordRSAPublicKey_Response::new();
+        
+        response.set_publickey_mod("c0ffee".into());
+        response.write_to_bytes().unwrap()
+    }
+    
+    #[tokio::test]
+    async fn routes_responses_by_jobid() {
+        let server = MockCmServer::echo().await.unwrap();
+        let transport = server.connect().await.unwrap();
+        
+        // the account name and public key modulus share a field number
+        let (gabe, robin) = tokio::join!(
+            get_rsa_key(&transport, "gabe"),
+            get_rsa_key(&transport, "robin"),
+        );
+        
+        assert_eq!(gabe.unwrap().publickey_mod(), "gabe");
+        assert_eq!(robin.unwrap().publickey_mod(), "robin");
+        assert_eq!(server.requests()[0].target_job_name.as_deref(), Some("Authentication.GetPasswordRSAPublicKey#1"));
+    }
+    
+    #[tokio::test]
+    async fn decompresses_multi_responses() {
+        let server = MockCmServer::start(|_request| vec![MockReply::MultiResponse {
+            eresult: EResult::OK,
+            body: rsa_key_response(),
+            gzip: true,
+        }]).await.unwrap();
+        let transport = server.connect().await.unwrap();
+        
+        assert_eq!(get_rsa_key(&transport, "gabe").await.unwrap().publickey_mod(), "c0ffee");
+    }
+    
+    #[tokio::test]
+    async fn records_try_another_cm() {
+        let server = MockCmServer::start(|_request| vec![
+            MockReply::TryAnotherCm(EResult::TryAnotherCM),
+            MockReply::Response {
+                eresult: EResult::OK,
+                body: rsa_key_response(),
+            },
+        ]).await.unwrap();
+        let transport = server.connect().await.unwrap();
+        
+        get_rsa_key(&transport, "gabe").await.unwrap();
+        
+        assert_eq!(transport.last_disconnect_reason(), Some(DisconnectReason::TryAnotherCm(EResult::TryAnotherCM)));
+    }
+    
+    #[tokio::test]
+    async fn fails_over_on_try_another_cm() {
+        let busy = MockCmServer::start(|request| match request.jobid {
+            Some(_) => vec![MockReply::Response {
+                eresult: EResult::TryAnotherCM,
+                body: Vec::new(),
+            }],
+            None => Vec::new(),
+        }).await.unwrap();
+        let other = MockCmServer::echo().await.unwrap();
+        let cm_list = Arc::new(tokio::sync::Mutex::new(CmListCache::with_servers(vec![
+            busy.cm_server(),
+            other.cm_server(),
+        ])));
+        let transport = connect_to_url(&busy.url(), &cm_list, &ConnectOptions::default()).await.unwrap();
+        let transport = RetryLayer::new(transport).policy(RetryPolicy {
+            max_retries: 1,
+            initial_backoff: std::time::Duration::ZERO,
+            ..RetryPolicy::default()
+        });
+        
+        assert_eq!(get_rsa_key(&transport, "gabe").await.unwrap().publickey_mod(), "gabe");
+        assert_eq!(transport.inner().current_cm(), Some(other.addr.to_string()));
+        assert_eq!(busy.requests().iter().filter(|request| request.jobid.is_some()).count(), 1);
+    }
+    
+    #[tokio::test]
+    async fn cancels_requests_in_flight() {
+        let server = MockCmServer::start(|_request| Vec::new()).await.unwrap();
+        let cancellation_token = CancellationToken::new();
+        let transport = server.connect_with_options(&ConnectOptions {
+            cancellation_token: cancellation_token.clone(),
+            ..ConnectOptions::default()
+        }).await.unwrap();
+        
+        tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            
+            async move {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                cancellation_token.cancel();
+            }
+        });
+        
+        assert!(matches!(
+            get_rsa_key(&transport, "gabe").await,
+            Err(AuthenticationClientError::WebSocketCM(Error::Cancelled)),
+        ));
+        assert_eq!(transport.last_disconnect_reason(), Some(DisconnectReason::Cancelled));
+        assert!(matches!(transport.reconnect().await, Err(Error::Cancelled)));
+        assert!(matches!(
+            get_rsa_key(&transport, "gabe").await,
+            Err(AuthenticationClientError::WebSocketCM(Error::Cancelled)),
+        ));
+        assert!(matches!(
+            WebSocketCMTransport::connect_with_cancellation(cancellation_token).await,
+            Err(Error::Cancelled),
+        ));
+    }
+    
+    #[tokio::test]
+    async fn keeps_cell_id_out_of_shared_cm_list() {
+        let server = MockCmServer::start(|request| match request.jobid {
+            Some(_) => {
+                let mut logon_response = CMsgClientLogonResponse::new();
+                
+                logon_response.set_eresult(EResult::OK.into());
+                logon_response.set_cell_id(66);
+                
+                vec![
+                    MockReply::Raw(response_frame(EMsg::ClientLogOnResponse, 0, EResult::OK, &logon_response.write_to_bytes().unwrap())),
+                    MockReply::Response {
+                        eresult: EResult::OK,
+                        body: rsa_key_response(),
+                    },
+                ]
+            },
+            None => Vec::new(),
+        }).await.unwrap();
+        let shared = Arc::new(tokio::sync::Mutex::new(CmListCache::with_servers(vec![server.cm_server()])));
+        let transport = connect_to_url(&server.url(), &shared, &ConnectOptions::default()).await.unwrap();
+        
+        get_rsa_key(&transport, "gabe").await.unwrap();
+        transport.apply_cell_id().await;
+        
+        assert_eq!(shared.lock().await.cell_id(), 0);
+        assert!(shared.lock().await.has_websocket_servers());
+        assert_eq!(transport.cm_list().lock().await.cell_id(), 66);
+    }
+    
+    #[tokio::test]
+    async fn close_fails_pending_requests() {
+        let server = MockCmServer::start(|_request| vec![MockReply::Close {
+            code: 1001,
+            reason: "going away".into(),
+        }]).await.unwrap();
+        let transport = server.connect().await.unwrap();
+        
+        assert!(matches!(
+            get_rsa_key(&transport, "gabe").await,
+            Err(AuthenticationClientError::WebSocketCM(Error::ClosedByServer { code: Some(1001), .. })),
+        ));
+    }
+    
+    #[tokio::test]
+    async fn drops_newest_unreceived_messages() {
+        let server = MockCmServer::start(|_request| {
+            let mut replies = (0..20)
+                .map(|_| MockReply::Raw(response_frame(EMsg::ClientLoggedOff, 0, EResult::OK, &[])))
+                .collect::<Vec<_>>();
+            
+            replies.push(MockReply::Response {
+                eresult: EResult::OK,
+                body: rsa_key_response(),
+            });
+            replies
+        }).await.unwrap();
+        let transport = server.connect().await.unwrap();
+        
+        // messages are read in order, so the pushed messages are queued once this resolves
+        get_rsa_key(&transport, "gabe").await.unwrap();
+        
+        let mut messages = transport.take_messages().unwrap();
+        let mut count = 0;
+        
+        while let Some(Ok(message)) = messages.receiver().try_recv() {
+            assert_eq!(message.emsg, EMsg::ClientLoggedOff);
+            count += 1;
+        }
+        
+        assert_eq!(count, 16);
+        assert_eq!(transport.dropped_messages(), 4);
+        assert!(transport.take_messages().is_none());
+    }
+}